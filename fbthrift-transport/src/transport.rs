@@ -87,6 +87,7 @@ where
 enum CallState {
     Pending,
     Writed,
+    Flushed,
 }
 
 struct Call<S, H>
@@ -157,6 +158,16 @@ where
             this.state = CallState::Writed;
         }
 
+        if this.state < CallState::Flushed {
+            // Streams like TLS streams hold on to what was written until
+            // they are flushed
+            let mut flush_future = stream.flush();
+
+            ready!(pin_write_future(&mut flush_future).poll(cx))?;
+
+            this.state = CallState::Flushed;
+        }
+
         let static_res_buf = configuration
             .response_handler
             .try_make_static_response_bytes(req.bytes())?;
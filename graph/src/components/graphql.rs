@@ -42,6 +42,15 @@ pub trait GraphQlRunner: Send + Sync + 'static {
         target: QueryTarget,
     ) -> Result<SubscriptionResult, SubscriptionError>;
 
+    /// Runs an nGQL statement that only reads from the NebulaGraph space of
+    /// the deployment and returns its rows as the data of the result. The
+    /// statement is subject to load management like a GraphQL query
+    async fn run_nebula_query(
+        self: Arc<Self>,
+        statement: String,
+        target: QueryTarget,
+    ) -> QueryResults;
+
     fn load_manager(&self) -> Arc<LoadManager>;
 
     fn metrics(&self) -> Arc<dyn GraphQLMetrics>;
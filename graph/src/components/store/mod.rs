@@ -22,6 +22,7 @@ use std::time::Duration;
 use crate::blockchain::{Block, Blockchain};
 use crate::data::store::scalar::Bytes;
 use crate::data::store::*;
+use crate::data::subgraph::status;
 use crate::data::value::Word;
 use crate::prelude::*;
 
@@ -511,6 +512,59 @@ impl EntityQuery {
     }
 }
 
+/// The direction in which a traversal of the NebulaGraph space of a
+/// deployment follows edges
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalDirection {
+    /// From the source of an edge to its target
+    Out,
+    /// From the target of an edge to its source
+    In,
+    Both,
+}
+
+/// A query for the vertices that can be reached from the vertex `from` in
+/// `1..=depth` steps over edges of type `edge`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NeighborsQuery {
+    pub from: String,
+    /// The name of the edge type in the space
+    pub edge: String,
+    pub direction: TraversalDirection,
+    pub depth: u32,
+    /// Only return the first `limit` vertices when they are ordered by id
+    pub limit: Option<u32>,
+}
+
+/// A query for the paths from the vertex `from` to the vertex `to` over
+/// edges of type `edge` that have at most `max_hops` steps and do not visit
+/// a vertex twice. If `shortest` is set, only the shortest of them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathsQuery {
+    pub from: String,
+    pub to: String,
+    /// The name of the edge type in the space
+    pub edge: String,
+    pub direction: TraversalDirection,
+    pub max_hops: u32,
+    pub shortest: bool,
+    /// Only return the first `limit` paths when they are ordered by their
+    /// length and then by the ids along them
+    pub limit: Option<u32>,
+}
+
+/// The rows that an nGQL statement from a client returned; see
+/// `QueryStore::nebula_query`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NebulaRows {
+    pub columns: Vec<String>,
+    /// The values of each row in the order of `columns`. Vertices, edges
+    /// and paths are objects
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Whether the statement returned more rows than `rows` holds
+    pub truncated: bool,
+}
+
 /// Operation types that lead to entity changes.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
@@ -1108,3 +1162,111 @@ pub trait PruneReporter: Send + 'static {
 
     fn finish_prune(&mut self) {}
 }
+
+/// Callbacks for `SubgraphStore.nebula_backfill` so that callers can report
+/// progress of loading the entities of a deployment into NebulaGraph
+#[allow(unused_variables)]
+pub trait BackfillReporter: Send + 'static {
+    /// Loading starts, or resumes if `resumed` is `true`, with the
+    /// entities as of `block`
+    fn start(&mut self, block: BlockNumber, resumed: bool) {}
+    /// The space of the deployment was dropped and created again
+    fn recreate_space(&mut self, space: &str) {}
+    fn start_entity_type(&mut self, entity_type: &str, total_rows: usize) {}
+    fn batch(&mut self, entity_type: &str, rows: usize, total_rows: usize, finished: bool) {}
+    fn finish(&mut self, total_rows: usize) {}
+}
+
+/// A difference between the entities of a deployment and what its
+/// NebulaGraph space holds for them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NebulaDifference {
+    /// The space has `actual` vertices with the tag `name`, or edges of
+    /// the type `name`, but the entities call for `expected` of them
+    Count {
+        name: String,
+        expected: i64,
+        actual: i64,
+    },
+    /// The vertex `id` does not have the tag `tag`
+    MissingVertex { tag: String, id: String },
+    /// The properties `props` of the tag `tag` of vertex `id` differ from
+    /// the entity
+    Vertex {
+        tag: String,
+        id: String,
+        props: Vec<String>,
+    },
+    /// The edge does not exist
+    MissingEdge {
+        edge: String,
+        from: String,
+        to: String,
+        rank: i64,
+    },
+    /// The properties `props` of the edge differ from the entity
+    Edge {
+        edge: String,
+        from: String,
+        to: String,
+        rank: i64,
+        props: Vec<String>,
+    },
+}
+
+impl fmt::Display for NebulaDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use NebulaDifference::*;
+
+        match self {
+            Count {
+                name,
+                expected,
+                actual,
+            } => write!(f, "{} has {} entries instead of {}", name, actual, expected),
+            MissingVertex { tag, id } => write!(f, "{} {} is missing", tag, id),
+            Vertex { tag, id, props } => {
+                write!(f, "{} {} differs in {}", tag, id, props.join(", "))
+            }
+            MissingEdge {
+                edge,
+                from,
+                to,
+                rank,
+            } => write!(f, "{} {}->{}@{} is missing", edge, from, to, rank),
+            Edge {
+                edge,
+                from,
+                to,
+                rank,
+                props,
+            } => write!(
+                f,
+                "{} {}->{}@{} differs in {}",
+                edge,
+                from,
+                to,
+                rank,
+                props.join(", ")
+            ),
+        }
+    }
+}
+
+/// Callbacks for `SubgraphStore.nebula_verify` so that callers can report
+/// progress of comparing a deployment with its NebulaGraph space
+#[allow(unused_variables)]
+pub trait VerifyReporter: Send + 'static {
+    /// The comparison starts with the entities as of `block`; if `sample`
+    /// is set, only that many entities of each type are compared
+    fn start(&mut self, block: BlockNumber, sample: Option<usize>) {}
+    fn start_entity_type(&mut self, entity_type: &str) {}
+    fn batch(&mut self, entity_type: &str, rows: usize, total_rows: usize) {}
+    fn difference(&mut self, difference: &NebulaDifference) {}
+    /// The differences of the last batch were repaired with `statements`
+    /// statements
+    fn repair(&mut self, statements: usize) {}
+    /// The number of vertices and edges in the space is counted
+    fn start_count(&mut self) {}
+    fn finish(&mut self, result: &status::NebulaConsistency) {}
+}
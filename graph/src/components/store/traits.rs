@@ -436,6 +436,28 @@ pub trait QueryStore: Send + Sync {
         query: EntityQuery,
     ) -> Result<(Vec<BTreeMap<Word, r::Value>>, Trace), QueryExecutionError>;
 
+    /// For each of `queries`, the ids of the vertices it reaches in the
+    /// NebulaGraph space of the deployment, without the vertex it starts
+    /// from, in no particular order
+    fn neighbors(
+        &self,
+        queries: &[NeighborsQuery],
+    ) -> Result<Vec<Vec<String>>, QueryExecutionError>;
+
+    /// For each of `queries`, the paths it finds in the NebulaGraph space
+    /// of the deployment as the ids of the vertices along each path, in no
+    /// particular order
+    fn paths(&self, queries: &[PathsQuery]) -> Result<Vec<Vec<Vec<String>>>, QueryExecutionError>;
+
+    /// Run `statement` in the NebulaGraph space of the deployment and
+    /// return at most `max_rows` of the rows it returns. The statement must
+    /// only read from the space; see `ngql::is_read_only`
+    async fn nebula_query(
+        &self,
+        statement: &str,
+        max_rows: usize,
+    ) -> Result<NebulaRows, QueryExecutionError>;
+
     async fn is_deployment_synced(&self) -> Result<bool, Error>;
 
     async fn block_ptr(&self) -> Result<Option<BlockPtr>, StoreError>;
@@ -0,0 +1,299 @@
+//! The `graph` section of the subgraph manifest, which declares how the
+//! entities of a subgraph are mirrored into a graph database.
+//!
+//! Entity types listed under `vertices` become vertices, and entity types
+//! listed under `edges` become edges between such vertices. For an edge,
+//! `source` and `target` name the fields that reference the vertices the
+//! edge connects, and the optional `rank` names an `Int` or `BigInt` field
+//! that distinguishes parallel edges between the same two vertices:
+//!
+//! ```yaml
+//! graph:
+//!   vertices:
+//!     - entity: Account
+//!       properties: [balance]
+//!   edges:
+//!     - entity: Transfer
+//!       source: from
+//!       target: to
+//!       rank: blockNumber
+//!       properties: [value]
+//! ```
+//!
+//! When `properties` is omitted, all fields of the entity are copied; the
+//! `id` of an entity is always copied. Subgraphs without a `graph` section
+//! are mirrored in full, with every entity type becoming a vertex type and
+//! every reference becoming an edge type.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{
+    components::store::EntityType,
+    data::{
+        graphql::{ext::DirectiveFinder, DocumentExt, ObjectTypeExt, TypeExt},
+        schema::Schema,
+    },
+    prelude::{s, Deserialize, Serialize},
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphMapping {
+    #[serde(default)]
+    pub vertices: Vec<VertexMapping>,
+    #[serde(default)]
+    pub edges: Vec<EdgeMapping>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VertexMapping {
+    pub entity: String,
+    pub properties: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeMapping {
+    pub entity: String,
+    pub source: String,
+    pub target: String,
+    pub rank: Option<String>,
+    pub properties: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum GraphMappingError {
+    #[error("entity type `{0}` is not defined in the schema")]
+    UnknownEntity(String),
+    #[error("entity type `{0}` is mapped more than once")]
+    DuplicateEntity(String),
+    #[error("entity type `{0}` has no field `{1}`")]
+    UnknownField(String, String),
+    #[error("field `{0}.{1}` is derived and can not be copied into the graph")]
+    DerivedField(String, String),
+    #[error(
+        "field `{0}.{1}` must reference a single entity whose type is mapped to vertices \
+         to be used as the source or target of an edge"
+    )]
+    InvalidEndpoint(String, String),
+    #[error("field `{0}.{1}` must be of type `Int` or `BigInt` to be used as the rank of an edge")]
+    InvalidRank(String, String),
+}
+
+impl GraphMapping {
+    /// Check that the mapping only refers to entity types and fields that
+    /// exist in `schema`, and that the fields used for edges have the right
+    /// types
+    pub fn validate(&self, schema: &Schema) -> Vec<GraphMappingError> {
+        let mut errors = vec![];
+
+        let mut mapped = HashSet::new();
+        let entities = self
+            .vertices
+            .iter()
+            .map(|vertex| (&vertex.entity, &vertex.properties))
+            .chain(
+                self.edges
+                    .iter()
+                    .map(|edge| (&edge.entity, &edge.properties)),
+            );
+        for (entity, properties) in entities {
+            if !mapped.insert(entity.as_str()) {
+                errors.push(GraphMappingError::DuplicateEntity(entity.clone()));
+            }
+            let object_type = match schema.document.get_object_type_definition(entity) {
+                Some(object_type) => object_type,
+                None => {
+                    errors.push(GraphMappingError::UnknownEntity(entity.clone()));
+                    continue;
+                }
+            };
+            for property in properties.iter().flatten() {
+                if let Err(e) = stored_field(object_type, property) {
+                    errors.push(e);
+                }
+            }
+        }
+
+        let vertices: HashSet<_> = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.entity.as_str())
+            .collect();
+        let is_vertex = |name: &str| {
+            vertices.contains(name)
+                || schema
+                    .types_for_interface
+                    .get(&EntityType::from(name))
+                    .map_or(false, |types| {
+                        types
+                            .iter()
+                            .all(|object_type| vertices.contains(object_type.name.as_str()))
+                    })
+        };
+
+        for edge in &self.edges {
+            let object_type = match schema.document.get_object_type_definition(&edge.entity) {
+                Some(object_type) => object_type,
+                None => continue,
+            };
+            for endpoint in [&edge.source, &edge.target] {
+                match stored_field(object_type, endpoint) {
+                    Ok(field) => {
+                        if field.field_type.is_list()
+                            || !is_vertex(field.field_type.get_base_type())
+                        {
+                            errors.push(GraphMappingError::InvalidEndpoint(
+                                edge.entity.clone(),
+                                endpoint.clone(),
+                            ));
+                        }
+                    }
+                    Err(e) => errors.push(e),
+                }
+            }
+            if let Some(rank) = &edge.rank {
+                match stored_field(object_type, rank) {
+                    Ok(field) => {
+                        let base_type = field.field_type.get_base_type();
+                        if field.field_type.is_list()
+                            || (base_type != "Int" && base_type != "BigInt")
+                        {
+                            errors.push(GraphMappingError::InvalidRank(
+                                edge.entity.clone(),
+                                rank.clone(),
+                            ));
+                        }
+                    }
+                    Err(e) => errors.push(e),
+                }
+            }
+        }
+        errors
+    }
+
+    /// The mapping serialized as JSON; this is how it is stored with the
+    /// deployment
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("graph mappings can always be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Look up `name` in `object_type` and make sure it is stored with the
+/// entity, i.e., not derived
+fn stored_field<'a>(
+    object_type: &'a s::ObjectType,
+    name: &str,
+) -> Result<&'a s::Field, GraphMappingError> {
+    match object_type.field(name) {
+        None => Err(GraphMappingError::UnknownField(
+            object_type.name.clone(),
+            name.to_owned(),
+        )),
+        Some(field) if field.is_derived() => Err(GraphMappingError::DerivedField(
+            object_type.name.clone(),
+            name.to_owned(),
+        )),
+        Some(field) => Ok(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::DeploymentHash;
+
+    const SCHEMA: &str = "
+        type Account @entity {
+            id: ID!
+            balance: BigInt!
+            sent: [Transfer!]! @derivedFrom(field: \"from\")
+        }
+        type Transfer @entity {
+            id: ID!
+            from: Account!
+            to: Account!
+            recipients: [Account!]!
+            value: BigInt!
+            blockNumber: BigInt!
+            memo: String
+        }";
+
+    const MAPPING: &str = "
+        vertices:
+          - entity: Account
+            properties: [balance]
+        edges:
+          - entity: Transfer
+            source: from
+            target: to
+            rank: blockNumber
+            properties: [value]
+        ";
+
+    fn validate(mapping: &str) -> Vec<GraphMappingError> {
+        let schema = Schema::parse(SCHEMA, DeploymentHash::new("test").unwrap()).unwrap();
+        let mapping: GraphMapping = serde_yaml::from_str(mapping).unwrap();
+        mapping.validate(&schema)
+    }
+
+    #[test]
+    fn valid_mapping() {
+        assert_eq!(Vec::<GraphMappingError>::new(), validate(MAPPING));
+
+        let mapping: GraphMapping = serde_yaml::from_str(MAPPING).unwrap();
+        assert_eq!(
+            mapping,
+            GraphMapping::from_json(&mapping.to_json()).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_mappings() {
+        use GraphMappingError::*;
+
+        let errors = validate(
+            "
+            vertices:
+              - entity: Account
+                properties: [balance, sent, nickname]
+              - entity: Account
+              - entity: Nothing
+            ",
+        );
+        assert_eq!(
+            vec![
+                DerivedField("Account".to_owned(), "sent".to_owned()),
+                UnknownField("Account".to_owned(), "nickname".to_owned()),
+                DuplicateEntity("Account".to_owned()),
+                UnknownEntity("Nothing".to_owned()),
+            ],
+            errors
+        );
+
+        let errors = validate(
+            "
+            edges:
+              - entity: Transfer
+                source: from
+                target: recipients
+                rank: memo
+            ",
+        );
+        assert_eq!(
+            vec![
+                InvalidEndpoint("Transfer".to_owned(), "from".to_owned()),
+                InvalidEndpoint("Transfer".to_owned(), "recipients".to_owned()),
+                InvalidRank("Transfer".to_owned(), "memo".to_owned()),
+            ],
+            errors
+        );
+    }
+}
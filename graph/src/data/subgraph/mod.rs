@@ -6,9 +6,11 @@ pub mod api_version;
 pub use api_version::*;
 
 pub mod features;
+pub mod graph_mapping;
 pub mod status;
 
 pub use features::{SubgraphFeature, SubgraphFeatureValidationError};
+pub use graph_mapping::{EdgeMapping, GraphMapping, GraphMappingError, VertexMapping};
 
 use anyhow::ensure;
 use anyhow::{anyhow, Error};
@@ -345,6 +347,8 @@ pub enum SubgraphManifestValidationError {
     FeatureValidationError(#[from] SubgraphFeatureValidationError),
     #[error("data source {0} is invalid: {1}")]
     DataSourceValidation(String, Error),
+    #[error("the graph mapping is invalid: {0}")]
+    GraphMappingInvalid(GraphMappingError),
 }
 
 #[derive(Error, Debug)]
@@ -491,6 +495,8 @@ pub struct BaseSubgraphManifest<C, S, D, T> {
     pub graft: Option<Graft>,
     #[serde(default)]
     pub templates: Vec<T>,
+    /// How entities are mirrored into the graph database
+    pub graph: Option<GraphMapping>,
     #[serde(skip_serializing, default)]
     pub chain: PhantomData<C>,
 }
@@ -579,6 +585,15 @@ impl<C: Blockchain> UnvalidatedSubgraphManifest<C> {
                 ));
             });
 
+        if let Some(graph) = &self.0.graph {
+            errors.extend(
+                graph
+                    .validate(&self.0.schema)
+                    .into_iter()
+                    .map(SubgraphManifestValidationError::GraphMappingInvalid),
+            );
+        }
+
         if let Some(graft) = &self.0.graft {
             if ENV_VARS.disable_grafts {
                 errors.push(SubgraphManifestValidationError::GraftBaseInvalid(
@@ -704,6 +719,7 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             data_sources,
             graft,
             templates,
+            graph,
             chain,
         } = self;
 
@@ -764,6 +780,7 @@ impl<C: Blockchain> UnresolvedSubgraphManifest<C> {
             data_sources,
             graft,
             templates,
+            graph,
             chain,
         })
     }
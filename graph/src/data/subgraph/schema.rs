@@ -163,6 +163,8 @@ pub struct SubgraphManifestEntity {
     pub repository: Option<String>,
     pub features: Vec<String>,
     pub schema: String,
+    /// The `graph` section of the manifest as JSON
+    pub graph_mapping: Option<String>,
 }
 
 impl<'a, C: Blockchain> From<&'a super::SubgraphManifest<C>> for SubgraphManifestEntity {
@@ -173,6 +175,7 @@ impl<'a, C: Blockchain> From<&'a super::SubgraphManifest<C>> for SubgraphManifes
             repository: manifest.repository.clone(),
             features: manifest.features.iter().map(|f| f.to_string()).collect(),
             schema: manifest.schema.document.clone().to_string(),
+            graph_mapping: manifest.graph.as_ref().map(|graph| graph.to_json()),
         }
     }
 }
@@ -91,6 +91,76 @@ impl IntoValue for ChainInfo {
     }
 }
 
+/// How far the NebulaGraph mirror of a deployment has caught up with the
+/// deployment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NebulaSync {
+    /// The sync cursor: all mutations up to and including this block have
+    /// been written to NebulaGraph
+    pub synced_block: Option<BlockNumber>,
+    /// The latest block that the subgraph has synced to
+    pub head_block: Option<BlockNumber>,
+    /// The number of blocks whose mutations still need to be written
+    pub pending_blocks: i64,
+}
+
+impl NebulaSync {
+    /// The number of blocks by which the mirror lags behind the deployment
+    pub fn lag(&self) -> BlockNumber {
+        match (self.head_block, self.synced_block) {
+            (Some(head), Some(synced)) => head - synced,
+            (Some(head), None) => head + 1,
+            (None, _) => 0,
+        }
+    }
+}
+
+/// The result of comparing the NebulaGraph mirror of a deployment with
+/// its entities
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NebulaConsistency {
+    /// The block as of which the entities were compared
+    pub block: BlockNumber,
+    /// Whether all entities were compared, or only a sample of them
+    pub complete: bool,
+    /// The number of entities that were compared
+    pub entities: i64,
+    /// The number of tags and edge types with more or fewer vertices or
+    /// edges than the entities call for; counted after repairs
+    pub count_mismatches: i32,
+    /// The number of vertices and edges that are missing
+    pub missing: i64,
+    /// The number of vertices and edges whose properties differ
+    pub different: i64,
+    /// How many of the missing and different vertices and edges were
+    /// written again
+    pub repaired: i64,
+}
+
+impl NebulaConsistency {
+    /// Whether the space holds what the entities call for, possibly after
+    /// repairs
+    pub fn is_consistent(&self) -> bool {
+        self.count_mismatches == 0 && self.missing + self.different == self.repaired
+    }
+}
+
+impl IntoValue for NebulaConsistency {
+    fn into_value(self) -> r::Value {
+        object! {
+            __typename: "NebulaConsistency",
+            block: self.block,
+            complete: self.complete,
+            entities: format!("{}", self.entities),
+            countMismatches: self.count_mismatches,
+            missing: format!("{}", self.missing),
+            different: format!("{}", self.different),
+            repaired: format!("{}", self.repaired),
+            consistent: self.is_consistent(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Info {
     pub id: DeploymentId,
@@ -111,6 +181,9 @@ pub struct Info {
 
     /// ID of the Graph Node that the subgraph is indexed by.
     pub node: Option<String>,
+
+    /// The last comparison of the NebulaGraph mirror with the entities
+    pub nebula_consistency: Option<NebulaConsistency>,
 }
 
 impl IntoValue for Info {
@@ -125,6 +198,7 @@ impl IntoValue for Info {
             node,
             non_fatal_errors,
             synced,
+            nebula_consistency,
         } = self;
 
         fn subgraph_error_to_value(subgraph_error: SubgraphError) -> r::Value {
@@ -166,6 +240,7 @@ impl IntoValue for Info {
             chains: chains.into_iter().map(|chain| chain.into_value()).collect::<Vec<_>>(),
             entityCount: format!("{}", entity_count),
             node: node,
+            nebulaConsistency: nebula_consistency.map_or(r::Value::Null, |c| c.into_value()),
         }
     }
 }
@@ -69,6 +69,10 @@ pub struct EnvVarsGraphQl {
     /// Set by the environment variable `GRAPH_GRAPHQL_MAX_SKIP`. The default
     /// value is 4294967295 ([`u32::MAX`]).
     pub max_skip: u32,
+    /// Set by the environment variable `GRAPH_GRAPHQL_MAX_TRAVERSAL_DEPTH`.
+    /// The most steps that the `depth` and `maxHops` arguments of fields
+    /// that traverse NebulaGraph can ask for. The default value is 10.
+    pub max_traversal_depth: u32,
     /// Allow skipping the check whether a deployment has changed while
     /// we were running a query. Once we are sure that the check mechanism
     /// is reliable, this variable should be removed.
@@ -124,6 +128,7 @@ impl From<InnerGraphQl> for EnvVarsGraphQl {
             max_depth: x.max_depth.0,
             max_first: x.max_first,
             max_skip: x.max_skip.0,
+            max_traversal_depth: x.max_traversal_depth,
             allow_deployment_change: x.allow_deployment_change.0,
             warn_result_size: x.warn_result_size.0 .0,
             error_result_size: x.error_result_size.0 .0,
@@ -165,6 +170,8 @@ pub struct InnerGraphQl {
     max_first: u32,
     #[envconfig(from = "GRAPH_GRAPHQL_MAX_SKIP", default = "")]
     max_skip: WithDefaultUsize<u32, { u32::MAX as usize }>,
+    #[envconfig(from = "GRAPH_GRAPHQL_MAX_TRAVERSAL_DEPTH", default = "10")]
+    max_traversal_depth: u32,
     #[envconfig(from = "GRAPHQL_ALLOW_DEPLOYMENT_CHANGE", default = "false")]
     allow_deployment_change: EnvVarBoolean,
     #[envconfig(from = "GRAPH_GRAPHQL_WARN_RESULT_SIZE", default = "")]
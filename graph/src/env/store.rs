@@ -91,6 +91,49 @@ pub struct EnvVarsStore {
     /// once the new behavior has run in the hosted service for a few days
     /// without issues.
     pub disable_error_for_toplevel_parents: bool,
+
+    /// The maximum size in bytes of the multi-row `INSERT` statements that
+    /// mirror the changes of a block into NebulaGraph.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_MAX_STATEMENT_SIZE`.
+    /// The default value is 1048576, i.e., 1MB.
+    pub nebula_max_statement_size: usize,
+
+    /// How many requests can be waiting for the task that writes to
+    /// NebulaGraph before writers have to wait for it to catch up.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_SINK_QUEUE_SIZE`.
+    /// The default value is 16.
+    pub nebula_sink_queue_size: usize,
+
+    /// How many sessions serve queries that only read from NebulaGraph,
+    /// like the ones for GraphQL fields that traverse the graph. Reads do
+    /// not wait for writes.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_READ_SESSIONS`. The
+    /// default value is 4.
+    pub nebula_read_sessions: usize,
+
+    /// How long to wait for NebulaGraph to make a newly created space and
+    /// its tags and edge types usable.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_SCHEMA_TIMEOUT`
+    /// (expressed in seconds). The default value is 60s.
+    pub nebula_schema_timeout: Duration,
+
+    /// How long an nGQL statement that a client sends to the `/nebula`
+    /// route of a deployment may run before it is abandoned.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_QUERY_TIMEOUT`
+    /// (expressed in seconds). The default value is 30s.
+    pub nebula_query_timeout: Duration,
+
+    /// The most rows that the `/nebula` route returns for a statement; the
+    /// rest are dropped and the response is marked as truncated.
+    ///
+    /// Set by the environment variable `GRAPH_NEBULA_QUERY_MAX_ROWS`. The
+    /// default value is 1000.
+    pub nebula_query_max_rows: usize,
 }
 
 // This does not print any values avoid accidentally leaking any sensitive env vars
@@ -128,6 +171,12 @@ impl From<InnerStore> for EnvVarsStore {
             connection_idle_timeout: Duration::from_secs(x.connection_idle_timeout_in_secs),
             write_queue_size: x.write_queue_size,
             disable_error_for_toplevel_parents: x.disable_error_for_toplevel_parents.0,
+            nebula_max_statement_size: x.nebula_max_statement_size,
+            nebula_sink_queue_size: x.nebula_sink_queue_size,
+            nebula_read_sessions: x.nebula_read_sessions,
+            nebula_schema_timeout: Duration::from_secs(x.nebula_schema_timeout_in_secs),
+            nebula_query_timeout: Duration::from_secs(x.nebula_query_timeout_in_secs),
+            nebula_query_max_rows: x.nebula_query_max_rows,
         }
     }
 }
@@ -173,4 +222,16 @@ pub struct InnerStore {
     write_queue_size: usize,
     #[envconfig(from = "GRAPH_DISABLE_ERROR_FOR_TOPLEVEL_PARENTS", default = "false")]
     disable_error_for_toplevel_parents: EnvVarBoolean,
+    #[envconfig(from = "GRAPH_NEBULA_MAX_STATEMENT_SIZE", default = "1048576")]
+    nebula_max_statement_size: usize,
+    #[envconfig(from = "GRAPH_NEBULA_SINK_QUEUE_SIZE", default = "16")]
+    nebula_sink_queue_size: usize,
+    #[envconfig(from = "GRAPH_NEBULA_READ_SESSIONS", default = "4")]
+    nebula_read_sessions: usize,
+    #[envconfig(from = "GRAPH_NEBULA_SCHEMA_TIMEOUT", default = "60")]
+    nebula_schema_timeout_in_secs: u64,
+    #[envconfig(from = "GRAPH_NEBULA_QUERY_TIMEOUT", default = "30")]
+    nebula_query_timeout_in_secs: u64,
+    #[envconfig(from = "GRAPH_NEBULA_QUERY_MAX_ROWS", default = "1000")]
+    nebula_query_max_rows: usize,
 }
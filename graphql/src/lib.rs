@@ -32,7 +32,7 @@ pub mod prelude {
     pub use super::execution::{ast as a, ExecutionContext, Query, Resolver};
     pub use super::introspection::IntrospectionResolver;
     pub use super::query::{execute_query, ext::BlockConstraint, QueryExecutionOptions};
-    pub use super::schema::{api_schema, APISchemaError};
+    pub use super::schema::{add_traversal_fields, api_schema, APISchemaError};
     pub use super::store::StoreResolver;
     pub use super::subscription::SubscriptionExecutionOptions;
     pub use super::values::MaybeCoercible;
@@ -1,3 +1,5 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -9,14 +11,15 @@ use graph::prelude::MetricsRegistry;
 use graph::{
     components::store::SubscriptionManager,
     prelude::{
-        async_trait, o, CheapClone, DeploymentState, GraphQLMetrics as GraphQLMetricsTrait,
+        async_trait, o, r, CheapClone, DeploymentState, GraphQLMetrics as GraphQLMetricsTrait,
         GraphQlRunner as GraphQlRunnerTrait, Logger, Query, QueryExecutionError, Subscription,
         SubscriptionError, SubscriptionResult, ENV_VARS,
     },
 };
 use graph::{data::graphql::effort::LoadManager, prelude::QueryStoreManager};
 use graph::{
-    data::query::{QueryResults, QueryTarget},
+    data::query::{CacheStatus, QueryResults, QueryTarget},
+    data::value::Object,
     prelude::QueryStore,
 };
 
@@ -181,6 +184,53 @@ where
             .map_err(QueryResults::from)
             .map(|()| result)
     }
+
+    async fn execute_nebula(
+        &self,
+        statement: String,
+        target: QueryTarget,
+    ) -> Result<QueryResults, QueryExecutionError> {
+        let store = self.store.query_store(target, false).await?;
+
+        // nGQL statements have no shape that we could compute; statements
+        // only count as the same for load management if their text is
+        let mut hasher = DefaultHasher::new();
+        statement.hash(&mut hasher);
+        let shape_hash = hasher.finish();
+
+        self.load_manager
+            .decide(
+                &store.wait_stats().map_err(QueryExecutionError::from)?,
+                shape_hash,
+                &statement,
+            )
+            .to_result()?;
+        let _permit = store.query_permit().await?;
+
+        let start = Instant::now();
+        let rows = graph::tokio::time::timeout(
+            ENV_VARS.store.nebula_query_timeout,
+            store.nebula_query(&statement, ENV_VARS.store.nebula_query_max_rows),
+        )
+        .await
+        .map_err(|_| QueryExecutionError::Timeout)??;
+        let truncated = rows.truncated;
+        self.load_manager
+            .record_work(shape_hash, start.elapsed(), CacheStatus::Miss);
+
+        let columns = rows.columns.into_iter().map(r::Value::String).collect();
+        let rows = rows
+            .rows
+            .into_iter()
+            .map(|row| r::Value::List(row.into_iter().map(r::Value::from).collect()))
+            .collect();
+        let data = Object::from_iter(vec![
+            (String::from("columns"), r::Value::List(columns)),
+            (String::from("rows"), r::Value::List(rows)),
+            (String::from("truncated"), r::Value::Boolean(truncated)),
+        ]);
+        Ok(QueryResults::from(data))
+    }
 }
 
 #[async_trait]
@@ -270,6 +320,16 @@ where
         )
     }
 
+    async fn run_nebula_query(
+        self: Arc<Self>,
+        statement: String,
+        target: QueryTarget,
+    ) -> QueryResults {
+        self.execute_nebula(statement, target)
+            .await
+            .unwrap_or_else(QueryResults::from)
+    }
+
     fn load_manager(&self) -> Arc<LoadManager> {
         self.load_manager.clone()
     }
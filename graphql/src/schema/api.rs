@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
 use graphql_parser::Pos;
@@ -7,7 +8,7 @@ use lazy_static::lazy_static;
 use crate::schema::ast;
 
 use graph::data::{
-    graphql::ext::{DirectiveExt, DocumentExt, ValueExt},
+    graphql::ext::{DirectiveExt, DirectiveFinder, DocumentExt, ValueExt},
     schema::{META_FIELD_NAME, META_FIELD_TYPE, SCHEMA_TYPE_NAME},
 };
 use graph::prelude::s::{Value, *};
@@ -29,6 +30,10 @@ const BLOCK_HEIGHT: &str = "Block_height";
 const CHANGE_BLOCK_FILTER_NAME: &str = "BlockChangedFilter";
 const ERROR_POLICY_TYPE: &str = "_SubgraphErrorPolicy_";
 
+/// The directive that marks the fields that `add_traversal_fields` adds
+const TRAVERSAL_DIRECTIVE: &str = "traversal";
+const TRAVERSAL_DIRECTION_TYPE: &str = "_TraversalDirection_";
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum ErrorPolicy {
     Allow,
@@ -764,12 +769,252 @@ fn add_field_arguments(
     Ok(())
 }
 
+/// The fields that `add_traversal_fields` generates. Query execution
+/// resolves them from the graph of the deployment rather than from its
+/// entity tables
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalField {
+    /// `T.neighbors`, the entities reachable from an entity
+    Neighbors,
+    /// `T.paths`, the paths from an entity to another one
+    Paths,
+    /// `Query.<t>ShortestPath`
+    ShortestPath,
+    /// `T_path.vertices`, the entities along a path
+    Vertices,
+}
+
+impl TraversalField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TraversalField::Neighbors => "neighbors",
+            TraversalField::Paths => "paths",
+            TraversalField::ShortestPath => "shortestPath",
+            TraversalField::Vertices => "vertices",
+        }
+    }
+
+    /// The traversal field that `field` is, if it is one
+    pub fn of(field: &Field) -> Option<TraversalField> {
+        let kind = field
+            .find_directive(TRAVERSAL_DIRECTIVE)?
+            .argument("kind")?;
+        match kind.as_str()? {
+            "neighbors" => Some(TraversalField::Neighbors),
+            "paths" => Some(TraversalField::Paths),
+            "shortestPath" => Some(TraversalField::ShortestPath),
+            "vertices" => Some(TraversalField::Vertices),
+            _ => None,
+        }
+    }
+
+    fn directive(&self) -> Directive {
+        Directive {
+            position: Pos::default(),
+            name: TRAVERSAL_DIRECTIVE.to_string(),
+            arguments: vec![("kind".to_string(), Value::String(self.as_str().to_string()))],
+        }
+    }
+}
+
+fn named(name: &str) -> Type {
+    Type::NamedType(name.to_string())
+}
+
+fn non_null(ty: Type) -> Type {
+    Type::NonNullType(Box::new(ty))
+}
+
+fn non_null_list(name: &str) -> Type {
+    non_null(Type::ListType(Box::new(non_null(named(name)))))
+}
+
+fn int_argument(name: &str, default: i32) -> InputValue {
+    let mut arg = input_value(name, "", named("Int"));
+    arg.default_value = Some(Value::Int(default.into()));
+    arg
+}
+
+fn traversal_arguments(edge_type: &str) -> Vec<InputValue> {
+    let mut direction = input_value("direction", "", named(TRAVERSAL_DIRECTION_TYPE));
+    direction.default_value = Some(Value::Enum("out".to_string()));
+    vec![
+        input_value("edge", "", non_null(named(edge_type))),
+        direction,
+    ]
+}
+
+fn traversal_field(
+    name: String,
+    field: TraversalField,
+    arguments: Vec<InputValue>,
+    field_type: Type,
+) -> Field {
+    Field {
+        position: Pos::default(),
+        description: None,
+        name,
+        arguments,
+        field_type,
+        directives: vec![field.directive()],
+    }
+}
+
+/// Adds fields that traverse the graph of a deployment to the API schema
+/// `schema`. `edges` maps the name of an object type to the edge types
+/// that connect entities of that type with each other. For each such type
+/// `T`, this adds
+///
+/// - an enum `T_edge` of the edge types
+/// - a type `T_path { vertices: [T!]!, length: Int! }`
+/// - fields `neighbors` and `paths` to `T`
+/// - a field `<t>ShortestPath` to `Query`
+///
+/// Types whose fields or types would clash with existing ones are skipped
+pub fn add_traversal_fields(schema: &mut Document, edges: &BTreeMap<String, Vec<String>>) {
+    if schema.get_named_type(TRAVERSAL_DIRECTION_TYPE).is_some() {
+        return;
+    }
+
+    let mut definitions = vec![];
+    let mut query_fields = vec![];
+    for (type_name, edges) in edges {
+        let edge_type = format!("{}_edge", type_name);
+        let path_type = format!("{}_path", type_name);
+        let shortest_path = format!("{}ShortestPath", type_name.to_camel_case());
+        let clashes = schema.get_named_type(&edge_type).is_some()
+            || schema.get_named_type(&path_type).is_some()
+            || schema.get_root_query_type().map_or(true, |query| {
+                query.fields.iter().any(|field| field.name == shortest_path)
+            });
+        let object_type = match ast::get_object_type_mut(schema, type_name) {
+            Some(object_type) if !clashes && !edges.is_empty() => object_type,
+            _ => continue,
+        };
+        if object_type
+            .fields
+            .iter()
+            .any(|field| field.name == "neighbors" || field.name == "paths")
+        {
+            continue;
+        }
+
+        let mut neighbors_arguments = traversal_arguments(&edge_type);
+        neighbors_arguments.extend(vec![
+            int_argument("depth", 1),
+            int_argument("first", 100),
+            int_argument("skip", 0),
+        ]);
+        object_type.fields.push(traversal_field(
+            "neighbors".to_string(),
+            TraversalField::Neighbors,
+            neighbors_arguments,
+            non_null_list(type_name),
+        ));
+
+        let mut paths_arguments = vec![input_value("to", "", non_null(named("ID")))];
+        paths_arguments.extend(traversal_arguments(&edge_type));
+        paths_arguments.extend(vec![
+            int_argument("maxHops", 3),
+            int_argument("first", 100),
+            int_argument("skip", 0),
+        ]);
+        object_type.fields.push(traversal_field(
+            "paths".to_string(),
+            TraversalField::Paths,
+            paths_arguments,
+            non_null_list(&path_type),
+        ));
+
+        let mut shortest_path_arguments = vec![
+            input_value("from", "", non_null(named("ID"))),
+            input_value("to", "", non_null(named("ID"))),
+        ];
+        shortest_path_arguments.extend(traversal_arguments(&edge_type));
+        shortest_path_arguments.extend(vec![
+            int_argument("maxHops", 3),
+            block_argument(),
+            subgraph_error_argument(),
+        ]);
+        query_fields.push(traversal_field(
+            shortest_path,
+            TraversalField::ShortestPath,
+            shortest_path_arguments,
+            named(&path_type),
+        ));
+
+        definitions.push(TypeDefinition::Enum(EnumType {
+            position: Pos::default(),
+            description: None,
+            name: edge_type,
+            directives: vec![],
+            values: edges
+                .iter()
+                .map(|name| EnumValue {
+                    position: Pos::default(),
+                    description: None,
+                    name: name.to_owned(),
+                    directives: vec![],
+                })
+                .collect(),
+        }));
+        definitions.push(TypeDefinition::Object(ObjectType {
+            position: Pos::default(),
+            description: None,
+            name: path_type,
+            implements_interfaces: vec![],
+            directives: vec![],
+            fields: vec![
+                traversal_field(
+                    "vertices".to_string(),
+                    TraversalField::Vertices,
+                    vec![],
+                    non_null_list(type_name),
+                ),
+                Field {
+                    position: Pos::default(),
+                    description: None,
+                    name: "length".to_string(),
+                    arguments: vec![],
+                    field_type: non_null(named("Int")),
+                    directives: vec![],
+                },
+            ],
+        }));
+    }
+
+    if definitions.is_empty() {
+        return;
+    }
+    definitions.push(TypeDefinition::Enum(EnumType {
+        position: Pos::default(),
+        description: None,
+        name: TRAVERSAL_DIRECTION_TYPE.to_string(),
+        directives: vec![],
+        values: ["out", "in", "both"]
+            .iter()
+            .map(|name| EnumValue {
+                position: Pos::default(),
+                description: None,
+                name: name.to_string(),
+                directives: vec![],
+            })
+            .collect(),
+    }));
+    schema
+        .definitions
+        .extend(definitions.into_iter().map(Definition::TypeDefinition));
+    if let Some(query) = ast::get_object_type_mut(schema, "Query") {
+        query.fields.extend(query_fields);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use graph::data::graphql::DocumentExt;
     use graphql_parser::schema::*;
 
-    use super::api_schema;
+    use super::{add_traversal_fields, api_schema, TraversalField};
     use crate::schema::ast;
 
     #[test]
@@ -1400,4 +1645,79 @@ type Gravatar @entity {
         }
         .expect("\"metadata\" field is missing on Query type");
     }
+
+    #[test]
+    fn api_schema_contains_traversal_fields() {
+        const SCHEMA: &str = "
+            type Account @entity { id: ID!, name: String }
+            type Token @entity { id: ID!, paths: [String!] }";
+        let input_schema = parse_schema(SCHEMA).expect("Failed to parse input schema");
+        let mut schema = api_schema(&input_schema).expect("Failed to derive API schema");
+        let edges = [
+            ("Account", vec!["Transfer", "Account_friends"]),
+            ("Token", vec!["Token_parent"]),
+        ]
+        .iter()
+        .map(|(name, edges)| {
+            let edges = edges.iter().map(|edge| edge.to_string()).collect();
+            (name.to_string(), edges)
+        })
+        .collect();
+        add_traversal_fields(&mut schema, &edges);
+
+        let object_type = |name: &str| match schema.get_named_type(name) {
+            Some(TypeDefinition::Object(t)) => t,
+            _ => panic!("{} is not an object type", name),
+        };
+
+        let account = object_type("Account");
+        let neighbors = ast::get_field(account, "neighbors").expect("neighbors is missing");
+        assert_eq!(
+            Some(TraversalField::Neighbors),
+            TraversalField::of(neighbors)
+        );
+        assert_eq!(
+            vec!["edge", "direction", "depth", "first", "skip"],
+            neighbors
+                .arguments
+                .iter()
+                .map(|arg| arg.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        let paths = ast::get_field(account, "paths").expect("paths is missing");
+        assert_eq!(Some(TraversalField::Paths), TraversalField::of(paths));
+        assert_eq!(
+            None,
+            TraversalField::of(ast::get_field(account, "name").unwrap())
+        );
+
+        let path = object_type("Account_path");
+        let vertices = ast::get_field(path, "vertices").expect("vertices is missing");
+        assert_eq!(Some(TraversalField::Vertices), TraversalField::of(vertices));
+
+        let shortest_path = ast::get_field(object_type("Query"), "accountShortestPath")
+            .expect("accountShortestPath is missing");
+        assert_eq!(
+            Some(TraversalField::ShortestPath),
+            TraversalField::of(shortest_path)
+        );
+
+        match schema.get_named_type("Account_edge") {
+            Some(TypeDefinition::Enum(t)) => assert_eq!(
+                vec!["Transfer", "Account_friends"],
+                t.values
+                    .iter()
+                    .map(|value| value.name.as_str())
+                    .collect::<Vec<_>>()
+            ),
+            _ => panic!("Account_edge is not an enum"),
+        }
+        schema
+            .get_named_type("_TraversalDirection_")
+            .expect("_TraversalDirection_ is missing");
+
+        // `Token` already has a `paths` field and is skipped
+        assert!(schema.get_named_type("Token_path").is_none());
+        assert!(ast::get_field(object_type("Token"), "neighbors").is_none());
+    }
 }
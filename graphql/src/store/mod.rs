@@ -1,6 +1,7 @@
 mod prefetch;
 mod query;
 mod resolver;
+mod traversal;
 
 pub use self::query::parse_subgraph_id;
 pub use self::resolver::StoreResolver;
@@ -26,9 +26,10 @@ use graph::{
 
 use crate::execution::{ast as a, ExecutionContext, Resolver};
 use crate::metrics::GraphQLMetrics;
+use crate::schema::api::TraversalField;
 use crate::schema::ast as sast;
 use crate::store::query::build_query;
-use crate::store::StoreResolver;
+use crate::store::{traversal, StoreResolver};
 
 lazy_static! {
     static ref ARG_FIRST: String = String::from("first");
@@ -40,7 +41,7 @@ lazy_static! {
 /// and their nested associations. For each association of `entity`, `children`
 /// has an entry mapping the response key to the list of nodes.
 #[derive(Debug, Clone)]
-struct Node {
+pub(super) struct Node {
     /// Estimate the size of the children using their `CacheWeight`. This
     /// field will have the cache weight of the `entity` plus the weight of
     /// the keys and values of the `children` map, but not of the map itself
@@ -175,7 +176,7 @@ impl ValueExt for r::Value {
 }
 
 impl Node {
-    fn id(&self) -> Result<String, Error> {
+    pub(super) fn id(&self) -> Result<String, Error> {
         match self.get("id") {
             None => Err(anyhow!("Entity is missing an `id` attribute")),
             Some(r::Value::String(s)) => Ok(s.to_owned()),
@@ -183,7 +184,7 @@ impl Node {
         }
     }
 
-    fn get(&self, key: &str) -> Option<&r::Value> {
+    pub(super) fn get(&self, key: &str) -> Option<&r::Value> {
         self.entity.get(&key.into())
     }
 
@@ -194,7 +195,7 @@ impl Node {
             .expect("__typename must be a string")
     }
 
-    fn set_children(&mut self, response_key: String, nodes: Vec<Rc<Node>>) {
+    pub(super) fn set_children(&mut self, response_key: String, nodes: Vec<Rc<Node>>) {
         fn nodes_weight(nodes: &Vec<Rc<Node>>) -> usize {
             let vec_weight = nodes.capacity() * std::mem::size_of::<Rc<Node>>();
             let children_weight = nodes.iter().map(|node| node.weight()).sum::<usize>();
@@ -526,7 +527,7 @@ fn check_result_size<'a>(
     Ok(())
 }
 
-fn execute_selection_set<'a>(
+pub(super) fn execute_selection_set<'a>(
     resolver: &StoreResolver,
     ctx: &'a ExecutionContext<impl Resolver>,
     mut parents: Vec<Node>,
@@ -563,6 +564,22 @@ fn execute_selection_set<'a>(
             let field_type = object_type
                 .field(&field.name)
                 .expect("field names are valid");
+
+            // Fields that traverse the graph are not resolved with a join
+            if let Some(kind) = TraversalField::of(field_type) {
+                let result =
+                    traversal::execute_field(resolver, ctx, &mut parents, field, field_type, kind);
+                match result {
+                    Ok(trace) => {
+                        let weight = parents.iter().map(|parent| parent.weight()).sum::<usize>();
+                        check_result_size(ctx, weight)?;
+                        parent_trace.push(field.response_key(), trace);
+                    }
+                    Err(mut e) => errors.append(&mut e),
+                }
+                continue;
+            }
+
             let child_type = schema
                 .object_or_interface(field_type.field_type.get_base_type())
                 .expect("we only collect fields that are objects or interfaces");
@@ -731,10 +748,12 @@ impl SelectedAttributes {
         for (object_type, fields) in field.selection_set.fields() {
             let column_names = fields
                 .filter(|field| {
-                    // Keep fields that are not derived and for which we
-                    // can find the field type
+                    // Keep fields that are not derived or traversals of
+                    // the graph and for which we can find the field type
                     sast::get_field(object_type, &field.name)
-                        .map(|field_type| !field_type.is_derived())
+                        .map(|field_type| {
+                            !field_type.is_derived() && TraversalField::of(field_type).is_none()
+                        })
                         .unwrap_or(false)
                 })
                 .filter_map(|field| {
@@ -69,7 +69,7 @@ pub(crate) fn build_query<'a>(
 }
 
 /// Parses GraphQL arguments into a EntityRange, if present.
-fn build_range(
+pub(crate) fn build_range(
     field: &a::Field,
     max_first: u32,
     max_skip: u32,
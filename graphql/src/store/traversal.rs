@@ -0,0 +1,344 @@
+//! Resolve the fields that traverse the graph of a deployment, see
+//! `schema::api::add_traversal_fields`.
+//!
+//! The graph only tells us the ids of the entities that a traversal
+//! reaches; the entities themselves are loaded at the block of the query
+//! with one query per field, like any other level of the prefetch. Paths
+//! become objects of type `T_path` that only exist in the result; the ids
+//! of their vertices are kept under `g$vertices` until the `vertices`
+//! field is resolved.
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use graph::components::store::{NeighborsQuery, PathsQuery, TraversalDirection};
+use graph::data::graphql::TypeExt;
+use graph::data::query::Trace;
+use graph::data::value::Word;
+use graph::prelude::{
+    r, s, AttributeNames, EntityCollection, EntityFilter, EntityQuery, EntityRange,
+    QueryExecutionError, Value as StoreValue, ENV_VARS,
+};
+
+use crate::execution::{ast as a, ExecutionContext, Resolver};
+use crate::schema::api::TraversalField;
+use crate::store::prefetch::{execute_selection_set, Node};
+use crate::store::query::build_range;
+use crate::store::StoreResolver;
+
+const VERTICES: &str = "g$vertices";
+
+/// The string or enum value of the argument `name` of `field`
+fn str_argument<'a>(field: &'a a::Field, name: &str) -> Option<&'a str> {
+    match field.argument_value(name) {
+        Some(r::Value::String(s)) | Some(r::Value::Enum(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+fn required_argument<'a>(field: &'a a::Field, name: &str) -> Result<&'a str, QueryExecutionError> {
+    str_argument(field, name)
+        .ok_or_else(|| QueryExecutionError::MissingArgumentError(field.position, name.to_owned()))
+}
+
+fn direction(field: &a::Field) -> TraversalDirection {
+    match str_argument(field, "direction") {
+        Some("in") => TraversalDirection::In,
+        Some("both") => TraversalDirection::Both,
+        _ => TraversalDirection::Out,
+    }
+}
+
+/// The number of steps that the argument `name` allows; it must be at
+/// least 1 and at most `GRAPH_GRAPHQL_MAX_TRAVERSAL_DEPTH`
+fn steps(field: &a::Field, name: &'static str) -> Result<u32, QueryExecutionError> {
+    let max = ENV_VARS.graphql.max_traversal_depth;
+    match field.argument_value(name) {
+        Some(r::Value::Int(n)) if *n >= 1 && *n <= max as i64 => Ok(*n as u32),
+        Some(r::Value::Int(n)) => Err(QueryExecutionError::RangeArgumentsError(name, max, *n)),
+        _ => Ok(1),
+    }
+}
+
+/// A trace for a request to the graph, in the same form as the traces
+/// that the store makes for SQL queries so that they can be nested
+fn graph_trace(what: &str, elapsed: Duration, count: usize) -> Trace {
+    if ENV_VARS.log_sql_timing() {
+        Trace::query(what, elapsed, count)
+    } else {
+        Trace::None
+    }
+}
+
+/// How many results the graph needs to return at most to fill `range`;
+/// the results are paged with `page` after that
+fn limit(range: &EntityRange) -> Option<u32> {
+    range.first.map(|first| range.skip.saturating_add(first))
+}
+
+/// Apply `range` to `items`
+fn page<T>(items: Vec<T>, range: &EntityRange) -> Vec<T> {
+    let first = range.first.map_or(usize::MAX, |first| first as usize);
+    items
+        .into_iter()
+        .skip(range.skip as usize)
+        .take(first)
+        .collect()
+}
+
+/// Sort `paths` by their length, and paths of the same length by the ids
+/// along them, so that paging through them is stable
+fn sort_paths(paths: &mut Vec<Vec<String>>) {
+    paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    paths.dedup();
+}
+
+fn path_node(type_name: &str, path: Vec<String>) -> Node {
+    let mut entity = BTreeMap::new();
+    entity.insert(
+        Word::from("__typename"),
+        r::Value::String(type_name.to_owned()),
+    );
+    entity.insert(
+        Word::from("length"),
+        r::Value::Int(path.len().saturating_sub(1) as i64),
+    );
+    entity.insert(
+        Word::from(VERTICES),
+        r::Value::List(path.into_iter().map(r::Value::String).collect()),
+    );
+    Node::from(entity)
+}
+
+fn path_vertices(node: &Node) -> Vec<String> {
+    match node.get(VERTICES) {
+        Some(r::Value::List(ids)) => ids
+            .iter()
+            .filter_map(|id| match id {
+                r::Value::String(id) => Some(id.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Load the entities of type `type_name` with the given `ids`, resolve the
+/// selection set of `field` for them, and return them by id
+fn load_vertices(
+    resolver: &StoreResolver,
+    ctx: &ExecutionContext<impl Resolver>,
+    type_name: &str,
+    ids: BTreeSet<String>,
+    field: &a::Field,
+) -> Result<(BTreeMap<String, Rc<Node>>, Trace), Vec<QueryExecutionError>> {
+    if ids.is_empty() {
+        return Ok((BTreeMap::new(), graph_trace(type_name, Duration::ZERO, 0)));
+    }
+
+    let mut query = EntityQuery::new(
+        ctx.query.schema.id().clone(),
+        resolver.block_number(),
+        EntityCollection::All(vec![(type_name.into(), AttributeNames::All)]),
+    )
+    .filter(EntityFilter::In(
+        "id".to_owned(),
+        ids.iter().cloned().map(StoreValue::from).collect(),
+    ))
+    .first(ids.len() as u32);
+    query.query_id = Some(ctx.query.query_id.clone());
+    query.logger = Some(ctx.logger.clone());
+
+    let (entities, trace) = resolver.store.find_query_values(query)?;
+    let children = entities.into_iter().map(Node::from).collect();
+    let (children, trace) =
+        execute_selection_set(resolver, ctx, children, trace, &field.selection_set)?;
+    let children = children
+        .into_iter()
+        .filter_map(|node| node.id().ok().map(|id| (id, Rc::new(node))))
+        .collect();
+    Ok((children, trace))
+}
+
+/// Resolve `field` for `parents`, where `field_definition` is one of the
+/// fields that `add_traversal_fields` generates, and store the result in
+/// the parents under the response key of `field`
+pub(super) fn execute_field(
+    resolver: &StoreResolver,
+    ctx: &ExecutionContext<impl Resolver>,
+    parents: &mut [&mut Node],
+    field: &a::Field,
+    field_definition: &s::Field,
+    kind: TraversalField,
+) -> Result<Trace, Vec<QueryExecutionError>> {
+    let type_name = field_definition.field_type.get_base_type();
+    let response_key = field.response_key();
+
+    match kind {
+        TraversalField::Neighbors => {
+            let range = build_range(field, ctx.max_first, ctx.max_skip)?;
+            let edge = required_argument(field, "edge")?;
+            let direction = direction(field);
+            let depth = steps(field, "depth")?;
+
+            let starts: BTreeSet<_> = parents.iter().filter_map(|p| p.id().ok()).collect();
+            let queries: Vec<_> = starts
+                .iter()
+                .map(|from| NeighborsQuery {
+                    from: from.clone(),
+                    edge: edge.to_owned(),
+                    direction,
+                    depth,
+                    limit: limit(&range),
+                })
+                .collect();
+            let start = Instant::now();
+            let neighbors: BTreeMap<_, _> = starts
+                .into_iter()
+                .zip(resolver.store.neighbors(&queries)?)
+                .map(|(from, mut ids)| {
+                    ids.sort();
+                    (from, page(ids, &range))
+                })
+                .collect();
+
+            let ids: BTreeSet<_> = neighbors.values().flatten().cloned().collect();
+            let what = format!("neighbors over {}", edge);
+            let mut trace = graph_trace(&what, start.elapsed(), ids.len());
+            let (children, vertex_trace) = load_vertices(resolver, ctx, type_name, ids, field)?;
+            trace.push(type_name, vertex_trace);
+            for parent in parents.iter_mut() {
+                let nodes = parent
+                    .id()
+                    .ok()
+                    .and_then(|id| neighbors.get(&id))
+                    .map(|ids| ids.iter().filter_map(|id| children.get(id).cloned()))
+                    .map(|nodes| nodes.collect())
+                    .unwrap_or_default();
+                parent.set_children(response_key.to_owned(), nodes);
+            }
+            Ok(trace)
+        }
+        TraversalField::Paths | TraversalField::ShortestPath => {
+            let shortest = kind == TraversalField::ShortestPath;
+            let range = if shortest {
+                EntityRange {
+                    first: Some(1),
+                    skip: 0,
+                }
+            } else {
+                build_range(field, ctx.max_first, ctx.max_skip)?
+            };
+            let to = required_argument(field, "to")?;
+            let edge = required_argument(field, "edge")?;
+            let direction = direction(field);
+            let max_hops = steps(field, "maxHops")?;
+
+            // `shortestPath` is a field of `Query`, and its only parent is
+            // the root node
+            let starts: BTreeSet<_> = if shortest {
+                BTreeSet::from([required_argument(field, "from")?.to_owned()])
+            } else {
+                parents.iter().filter_map(|p| p.id().ok()).collect()
+            };
+            let queries: Vec<_> = starts
+                .iter()
+                .map(|from| PathsQuery {
+                    from: from.clone(),
+                    to: to.to_owned(),
+                    edge: edge.to_owned(),
+                    direction,
+                    max_hops,
+                    shortest,
+                    limit: limit(&range),
+                })
+                .collect();
+            let start = Instant::now();
+            let paths = resolver.store.paths(&queries)?;
+            let what = format!("paths over {}", edge);
+            let trace = graph_trace(&what, start.elapsed(), paths.iter().map(Vec::len).sum());
+
+            // Resolve the selection set for the paths of all parents at
+            // once, and remember which of them belong to which start
+            let mut counts = BTreeMap::new();
+            let mut nodes = vec![];
+            for (from, mut paths) in starts.into_iter().zip(paths) {
+                sort_paths(&mut paths);
+                let paths = page(paths, &range);
+                counts.insert(from, (nodes.len(), paths.len()));
+                nodes.extend(paths.into_iter().map(|path| path_node(type_name, path)));
+            }
+            let (nodes, trace) =
+                execute_selection_set(resolver, ctx, nodes, trace, &field.selection_set)?;
+            let nodes: Vec<_> = nodes.into_iter().map(Rc::new).collect();
+
+            for parent in parents.iter_mut() {
+                let from = if shortest {
+                    str_argument(field, "from").map(str::to_owned)
+                } else {
+                    parent.id().ok()
+                };
+                let children = from
+                    .and_then(|from| counts.get(&from))
+                    .map(|(start, len)| nodes[*start..*start + *len].to_vec())
+                    .unwrap_or_default();
+                parent.set_children(response_key.to_owned(), children);
+            }
+            Ok(trace)
+        }
+        TraversalField::Vertices => {
+            let ids = parents
+                .iter()
+                .flat_map(|parent| path_vertices(parent))
+                .collect();
+            let (children, trace) = load_vertices(resolver, ctx, type_name, ids, field)?;
+            for parent in parents.iter_mut() {
+                let nodes = path_vertices(parent)
+                    .iter()
+                    .filter_map(|id| children.get(id).cloned())
+                    .collect();
+                parent.set_children(response_key.to_owned(), nodes);
+            }
+            Ok(trace)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn paths_are_paged_by_length() {
+        let mut paths = vec![
+            path(&["a", "c", "d", "b"]),
+            path(&["a", "d", "b"]),
+            path(&["a", "c", "b"]),
+            path(&["a", "c", "b"]),
+        ];
+        sort_paths(&mut paths);
+        assert_eq!(
+            vec![
+                path(&["a", "c", "b"]),
+                path(&["a", "d", "b"]),
+                path(&["a", "c", "d", "b"])
+            ],
+            paths
+        );
+
+        let range = EntityRange {
+            first: Some(1),
+            skip: 1,
+        };
+        assert_eq!(Some(2), limit(&range));
+        assert_eq!(vec![path(&["a", "d", "b"])], page(paths, &range));
+
+        let node = path_node("Account_path", path(&["a", "c", "b"]));
+        assert_eq!(Some(&r::Value::Int(2)), node.get("length"));
+        assert_eq!(path(&["a", "c", "b"]), path_vertices(&node));
+    }
+}
@@ -130,6 +130,7 @@ async fn setup(
             data_sources: vec![],
             graft: None,
             templates: vec![],
+            graph: None,
             chain: PhantomData,
         };
 
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 /* Copyright (c) 2021 vesoft inc. All rights reserved.
  *
  * This source code is licensed under Apache 2.0 License,
@@ -10,15 +11,62 @@ use fbthrift::BinaryProtocol;
 use fbthrift_transport::{tokio_io::transport::AsyncTransport, AsyncTransportConfiguration};
 use graph::client;
 use graph::client::GraphService;
-use tokio::net::TcpStream;
 
+use crate::graph_client::error::error_msg;
 use crate::graph_client::transport_response_handler;
 use crate::graph_client::pool_config::PoolConfig;
+use crate::graph_client::tls::{GraphStream, TlsConfig};
 use crate::graph_client::nebula_schema::Tag;
 use crate::graph_client::nebula_schema::ColType;
 use crate::graph_client::nebula_schema::InsertTagQuery;
 use crate::graph_client::nebula_schema::InsertEdgeQueryWithRank;
-pub use common::types::{Value};
+use crate::graph_client::nebula_schema::{batch_insert_edges, batch_insert_tags, DEFAULT_MAX_STATEMENT_SIZE};
+use crate::graph_client::ngql::{in_space, CreateIndex, CreateSchema, CreateSpace, Identifier, IndexField, Literal, NgqlError, Use, VidType};
+use crate::value::{ResultError, ResultSet};
+
+pub use common::types::{ErrorCode, Value};
+pub use crate::graph_client::error::NebulaError;
+
+/// How long to wait for a tag or edge type before inserting into it
+pub(crate) const SCHEMA_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often to check whether a tag or edge type is visible
+pub(crate) const SCHEMA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The configuration of the transport, with the read timeout and buffer
+/// sizes of `conf` where they are set
+fn transport_configuration(
+    conf: &PoolConfig,
+) -> AsyncTransportConfiguration<transport_response_handler::GraphTransportResponseHandler> {
+    let mut transport =
+        AsyncTransportConfiguration::new(transport_response_handler::GraphTransportResponseHandler);
+    if conf.read_timeout > 0 {
+        transport.set_read_timeout(conf.read_timeout);
+    }
+
+    let buf_size = match conf.buf_size {
+        0 => transport.get_buf_size(),
+        size => size,
+    };
+    let max_buf_size = match conf.max_buf_size {
+        0 => transport.get_max_buf_size(),
+        size => size,
+    }
+    .max(buf_size);
+    // The buffer may never be bigger than the max, also not in between
+    transport.set_max_buf_size(max_buf_size.max(transport.get_buf_size()));
+    transport.set_buf_size(buf_size);
+    transport.set_max_buf_size(max_buf_size);
+
+    // Answers are read `buf_size` bytes at a time, and the transport gives
+    // up after a few reads; allow enough reads to fill `max_buf_size`
+    let reads = (max_buf_size + buf_size - 1) / buf_size;
+    let reads = reads
+        .max(transport.get_max_parse_response_bytes_count() as usize)
+        .min(u8::MAX as usize);
+    transport.set_max_parse_response_bytes_count(reads as u8);
+    transport
+}
+
 /// The simple abstraction of a connection to nebula graph server
 #[derive(Default)]
 pub struct Connection {
@@ -28,7 +76,7 @@ pub struct Connection {
     client: Option<
         client::GraphServiceImpl<
             BinaryProtocol,
-            AsyncTransport<TcpStream, transport_response_handler::GraphTransportResponseHandler>,
+            AsyncTransport<GraphStream, transport_response_handler::GraphTransportResponseHandler>,
         >,
     >,
 }
@@ -37,13 +85,30 @@ impl Connection {
     /// Create connection with the specified [host:port] address
     /// 使用指定的 [host:port] 地址创建连接
     pub async fn new_from_address(address: &str) -> Result<Connection> {
-        let stream = TcpStream::connect(address).await?;
-        let transport = AsyncTransport::new(
-            stream,
-            AsyncTransportConfiguration::new(
-                transport_response_handler::GraphTransportResponseHandler,
-            ),
-        );
+        Connection::new_from_address_with_tls(address, None).await
+    }
+
+    /// Create connection with the specified [host:port] address, secured
+    /// with TLS if `tls` is set
+    pub async fn new_from_address_with_tls(
+        address: &str,
+        tls: Option<&TlsConfig>,
+    ) -> Result<Connection> {
+        let conf = PoolConfig {
+            tls: tls.cloned(),
+            ..Default::default()
+        };
+        Connection::new_from_address_with_conf(address, &conf).await
+    }
+
+    /// Create connection with the specified [host:port] address, using the
+    /// TLS, read timeout and buffer sizes of `conf`
+    pub async fn new_from_address_with_conf(
+        address: &str,
+        conf: &PoolConfig,
+    ) -> Result<Connection> {
+        let stream = GraphStream::connect(address, conf.tls.as_ref()).await?;
+        let transport = AsyncTransport::new(stream, transport_configuration(conf));
         Ok(Connection {
             client: Some(client::GraphServiceImpl::new(transport)),
         })
@@ -58,7 +123,7 @@ impl Connection {
     /// Create connection from nebula configuration
     pub async fn new_from_conf(conf: &PoolConfig) -> Result<Connection> {
         let address = conf.addresses[0].clone();
-        Connection::new_from_address(&address).await
+        Connection::new_from_address_with_conf(&address, conf).await
     }
 
     /// Authenticate by username and password
@@ -71,8 +136,8 @@ impl Connection {
         &self,
         username: &str,
         password: &str,
-    ) -> std::result::Result<graph::types::AuthResponse, common::types::ErrorCode> {
-        let result = self
+    ) -> std::result::Result<graph::types::AuthResponse, NebulaError> {
+        let resp = self
             .client
             .as_ref()
             .unwrap()
@@ -80,11 +145,8 @@ impl Connection {
                 &username.to_string().into_bytes(),
                 &password.to_string().into_bytes(),
             )
-            .await;
-        if let Err(_) = result {
-            return Err(common::types::ErrorCode::E_RPC_FAILURE);
-        }
-        Ok(result.unwrap())
+            .await?;
+        Ok(resp)
     }
 
     /// Sign out the authentication by session id which got by authenticating previous
@@ -92,14 +154,18 @@ impl Connection {
     pub async fn signout(
         &self,
         session_id: i64,
-    ) -> std::result::Result<(), common::types::ErrorCode> {
-        let result = self.client.as_ref().unwrap().signout(session_id).await;
-        if let Err(_) = result {
-            return Err(common::types::ErrorCode::E_RPC_FAILURE);
-        }
+    ) -> std::result::Result<(), NebulaError> {
+        self.client.as_ref().unwrap().signout(session_id).await?;
         Ok(())
     }
 
+    /// Check that the server still answers on this connection. The query
+    /// is rejected for lack of a session, but getting an answer at all is
+    /// what counts
+    pub async fn ping(&self) -> std::result::Result<(), NebulaError> {
+        self.execute(0, "YIELD 1").await.map(|_| ())
+    }
+
     /// Execute the query with current session id which got by authenticating previous
     /// The returned error of `Result` only means the request/response status
     /// The error from Nebula Graph is still in `error_code` field in response, so you need check it
@@ -110,415 +176,308 @@ impl Connection {
         &self,
         session_id: i64,
         query: &str,
-    ) -> std::result::Result<graph::types::ExecutionResponse, common::types::ErrorCode> {
-        let result = self
+    ) -> std::result::Result<graph::types::ExecutionResponse, NebulaError> {
+        let resp = self
             .client
             .as_ref()
             .unwrap()
             .execute(session_id, &query.to_string().into_bytes())
-            .await;
-        if let Err(_) = result {
-            return Err(common::types::ErrorCode::E_RPC_FAILURE);
-        }
-        Ok(result.unwrap())
+            .await?;
+        Ok(resp)
+    }
+
+    /// Execute the query like `execute`, but turn the errors from Nebula
+    /// Graph into `NebulaError::Server` and read the result
+    pub async fn execute_checked(
+        &self,
+        session_id: i64,
+        query: &str,
+    ) -> std::result::Result<ResultSet, NebulaError> {
+        let resp = self.execute(session_id, query).await?;
+        check_response(resp, query)
+    }
+
+    /// Execute the query with Nebula Graph answering in JSON instead of
+    /// thrift values. The error from Nebula Graph is still in the `errors`
+    /// of the answer, so you need check it like with `execute`
+    pub async fn execute_json(
+        &self,
+        session_id: i64,
+        query: &str,
+    ) -> std::result::Result<serde_json::Value, NebulaError> {
+        let json = self
+            .client
+            .as_ref()
+            .unwrap()
+            .executeJson(session_id, &query.to_string().into_bytes())
+            .await?;
+        serde_json::from_slice(&json)
+            .map_err(|e| NebulaError::Decode(ResultError::Deserialize(e.to_string())))
+    }
+
+    /// Execute the query like `execute_json`, but turn the errors from
+    /// Nebula Graph into `NebulaError::Server`
+    pub async fn execute_json_checked(
+        &self,
+        session_id: i64,
+        query: &str,
+    ) -> std::result::Result<serde_json::Value, NebulaError> {
+        let json = self.execute_json(session_id, query).await?;
+        check_json(json, query)
     }
 
 
     #[inline]
-    pub async fn show_spaces(&self, session_id: i64){
+    pub async fn show_spaces(&self, session_id: i64) -> std::result::Result<(), NebulaError>{
         let query = "show spaces;";
-        let resp = self.execute(session_id, query).await.unwrap();
+        let resp = self.execute(session_id, query).await?;
         resp.show_data();
+        Ok(())
     }
 
     #[inline]
-    pub fn get_create_space_query(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str) -> String{
-        let mut query = String::from("CREATE SPACE IF NOT EXISTS `");
-        query += space_name;
-        query += "` (partition_num = ";
-        query += partition_num.to_string().as_str();
-        query += ", replica_factor = ";
-        query += replica_factor.to_string().as_str();
-        query += ", vid_type = ";
-        if is_fixed_string{
-            query += "FIXED_STRING(";
-            query += fixed_string_len.to_string().as_str();
-            query += "))";
-        }else{
-            query += "INT64)";
-        }
-        if comment!=""{
-            query += " COMMENT = \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-        query
+    pub fn get_create_space_query(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str) -> std::result::Result<String, NgqlError>{
+        let query = CreateSpace{
+            name: Identifier::new(space_name)?,
+            partition_num: partition_num as u32,
+            replica_factor: replica_factor as u32,
+            vid_type: if is_fixed_string { VidType::FixedString(fixed_string_len as u32) } else { VidType::Int64 },
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        Ok(format!("{};", query))
     }
     #[inline]
     // CREATE SPACE `testGraph` (partition_num = 15, replica_factor = 1, vid_type = FIXED_STRING(50)) COMMENT = "this is a graph for test"
-    pub async fn create_space(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str, session_id: i64){
-        let query = self.get_create_space_query(space_name, partition_num, replica_factor, is_fixed_string, fixed_string_len, comment);
-        // println!("{}", query);
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
-    }
-
-    #[inline]
-    pub fn get_create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>) -> String{
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; CREATE ";
-        query += col_type.to_string().as_str();
-        query += " IF NOT EXISTS `";
-        query += tag_name;
-        query += "` (";
-        for i in 0..tags.len() {
-            query += tags[i].to_string().as_str();
-            if i!=tags.len()-1{
-                query += ",";
-            }
-        }
-        query += ")";
-        if comment!="".to_string(){
-            query += " COMMENT = \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-        query
+    pub async fn create_space(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str, session_id: i64) -> std::result::Result<(), NebulaError>{
+        let query = self.get_create_space_query(space_name, partition_num, replica_factor, is_fixed_string, fixed_string_len, comment)?;
+        self.execute_checked(session_id, query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub async fn create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>, session_id: i64){
-
-        let query = self.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags);
-        //println!("{}", query);
-
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
-
-        //println!("{:?}", _resp);
+    pub fn get_create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>) -> std::result::Result<String, NgqlError>{
+        let query = CreateSchema{
+            kind: col_type,
+            name: Identifier::new(tag_name)?,
+            properties: tags.iter().map(Tag::to_property_def).collect::<std::result::Result<_, _>>()?,
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        Ok(in_space(&Identifier::new(space_name)?, &query))
     }
 
     #[inline]
-    // INSERT VERTEX t2 (name, age) VALUES "11":("n1", 12);
-    pub async fn insert_tag(&self, space_name: &str, tag_name: &str, kv: HashMap<String, String>, vid: &str, value: i64, session_id: i64){
-
-        if self.find_tag_or_edge(space_name, tag_name, ColType::Tag, session_id).await == false{
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-        }
-
-        let mut from_value = 0;
-        let mut to_value = 0;
-
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT VERTEX ";
-        query += tag_name;
-        query += " ";
-        let mut keys = String::from("(id,value)");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-
-            if k.clone() == "from_account" {
-                from_value = self.find_value_by_id(space_name, tag_name, v.clone().as_str(), session_id).await;
-                from_value -= value;
-                values += v.clone().as_str();
-                values += ",";
-                values += from_value.to_string().as_str();
-                values += ")";
-            }
-            // if keys.len()!=1{
-            //     keys += ",";
-            //     values += ",";
-            // }
-            if k.clone() == "to_account" {
-                to_value = self.find_value_by_id(space_name, tag_name, v.clone().as_str(), session_id).await;
-                to_value += value;
-                values += v.clone().as_str();
-                values += ",";
-                values += to_value.to_string().as_str();
-                values += ")";
-            }
-            // keys += k.as_str();
-            // values += v.as_str();
-        }
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += vid;
-        query += "\":";
-        query += values.as_str();
-        query += ";";
-
-        // println!("---------------insert tag----------------");
-        // println!("{}", query);
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>, session_id: i64) -> std::result::Result<(), NebulaError>{
+        let query = self.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags)?;
+        self.execute_checked(session_id, query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub async fn insert_tags(&self, insert_tag_queries: Vec<InsertTagQuery>, session_id: i64){
-        for query in insert_tag_queries{
-            self.insert_tag(query.space_name.as_str(), query.tag_name.as_str(), query.kv, query.vid.as_str(), query.value, session_id).await;
+    pub async fn insert_tags(&self, insert_tag_queries: Vec<InsertTagQuery>, session_id: i64) -> std::result::Result<(), NebulaError>{
+        for query in batch_insert_tags(&insert_tag_queries, DEFAULT_MAX_STATEMENT_SIZE){
+            self.execute_checked(session_id, query.as_str()).await?;
         }
+        Ok(())
     }
 
     #[inline]
-    pub async fn insert_edges(&self, insert_edge_queries: Vec<InsertEdgeQueryWithRank>, session_id: i64){
-        for query in insert_edge_queries{
-            self.insert_edge_with_rank(query.space_name.as_str(), query.edge_name.as_str(), query.kv, query.from_vertex.as_str(), query.to_vertex.as_str(), query.rank, query.value, session_id).await;
+    pub async fn insert_edges(&self, insert_edge_queries: Vec<InsertEdgeQueryWithRank>, session_id: i64) -> std::result::Result<(), NebulaError>{
+        for query in batch_insert_edges(&insert_edge_queries, DEFAULT_MAX_STATEMENT_SIZE){
+            self.execute_checked(session_id, query.as_str()).await?;
         }
+        Ok(())
     }
 
     #[inline]
     // INSERT EDGE e2 (name, age) VALUES "11"->"13":("n1", 12);
-    pub async fn insert_edge(&self, space_name: &str, edge_name: &str, kv: HashMap<String, String>, from_vertex: &str, to_vertex: &str, session_id: i64){
+    pub async fn insert_edge(&self, space_name: &str, edge_name: &str, kv: HashMap<Identifier, Literal>, from_vertex: &str, to_vertex: &str, session_id: i64) -> std::result::Result<(), NebulaError>{
 
-        if self.find_tag_or_edge(space_name, edge_name, ColType::Edge, session_id).await == false{
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-        }
+        self.wait_for_tag_or_edge(space_name, edge_name, ColType::Edge, SCHEMA_TIMEOUT, session_id).await?;
 
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT EDGE IF NOT EXISTS ";
-        query += edge_name;
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            keys += k.as_str();
-            values += v.as_str();
-        }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += from_vertex;
-        query += "\" -> \"";
-        query += to_vertex;
-        query += "\":";
-        query += values.as_str();
-        query += ";";
-
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
+        self.insert_edge_with_rank(space_name, edge_name, kv, from_vertex, to_vertex, 0, session_id).await
     }
 
 
     #[inline]
     // INSERT EDGE e2 (name, age) VALUES "11"->"13"@1:("n1", 12);
-    pub async fn insert_edge_with_rank(&self, space_name: &str, edge_name: &str, kv: HashMap<String, String>, from_vertex: &str, to_vertex: &str, rank: i32, value: i64, session_id: i64){
-
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT EDGE IF NOT EXISTS ";
-        query += edge_name;
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-            if k.clone() == "value" {
-                continue;
-            }
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            if k.clone() == "transactions" {
-                let transaction = self.find_trasaction_by_rank(space_name, edge_name, from_vertex, to_vertex, rank, session_id).await;
-                let mut t = String::from(transaction);
-                t += v.as_str();
-                keys += k.as_str();
-                values += "\"";
-                values += t.as_str();
-                values += "\"";
-                continue;
-            }
-            keys += k.as_str();
-            values += v.as_str();
-        }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += from_vertex;
-        query += "\" -> \"";
-        query += to_vertex;
-        query += "\"@";
-        query += rank.to_string().as_str();
-        query += ":";
-        query += values.as_str();
-        query += ";";
-
-        // println!("{}", query);
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn insert_edge_with_rank(&self, space_name: &str, edge_name: &str, kv: HashMap<Identifier, Literal>, from_vertex: &str, to_vertex: &str, rank: i64, session_id: i64) -> std::result::Result<(), NebulaError>{
+        let query = InsertEdgeQueryWithRank::new(
+            Identifier::new(space_name)?,
+            Identifier::new(edge_name)?,
+            kv,
+            from_vertex.to_string(),
+            to_vertex.to_string(),
+            rank,
+        );
+        self.execute_checked(session_id, query.to_string().as_str()).await?;
+        Ok(())
     }
 
     #[inline]
     // CREATE TAG INDEX `index_tag` on `stu`      (`name`(10), `age`) COMMENT "this is an index for tag"
-    pub async fn create_index(&self, space_name: &str, index_type: ColType, tag_or_edge_name: &str, index_name: &str, comment: &str, indexed_properties: HashMap<String, u8>, session_id: i64){
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "CREATE ";
-        query += index_type.to_string().as_str();
-        query += " INDEX `";
-        query += index_name;
-        query += "` on `";
-        query += tag_or_edge_name;
-        query += "`(";
-
-        let mut properties = String::from("");
-        for (k,v) in indexed_properties {
-            if properties.len()!=0{
-                properties += ",";
-            }
-            let mut property = String::from("`");
-            property += k.as_str();
-            property += "`";
-            if v==0 {
-            }else{
-                property += "(";
-                property += v.to_string().as_str();
-                property += ")"
-            }
-            properties += property.as_str();
-        }
-
-        query += properties.as_str();
-        query += ") ";
-        if comment!=""{
-            query += "COMMENT \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-
-        // println!("{}", query);
-        let _resp = self.execute(session_id, query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn create_index(&self, space_name: &str, index_type: ColType, tag_or_edge_name: &str, index_name: &str, comment: &str, indexed_properties: HashMap<String, u8>, session_id: i64) -> std::result::Result<(), NebulaError>{
+        let mut fields: Vec<IndexField> = indexed_properties
+            .iter()
+            .map(|(name, len)| Ok(IndexField{
+                name: Identifier::new(name)?,
+                // a length of 0 means that the property is not a string
+                length: if *len == 0 { None } else { Some(*len as u16) },
+            }))
+            .collect::<std::result::Result<_, NgqlError>>()?;
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+        let query = CreateIndex{
+            kind: index_type,
+            name: Identifier::new(index_name)?,
+            schema: Identifier::new(tag_or_edge_name)?,
+            fields,
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        let query = in_space(&Identifier::new(space_name)?, &query);
+        self.execute_checked(session_id, query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub async fn find_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType, session_id: i64) -> bool{
-        let mut query = Self::use_space(space_name);
+    pub async fn find_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType, session_id: i64) -> std::result::Result<bool, NebulaError>{
+        let mut query = Self::use_space(space_name)?;
         match col_type {
-            ColType::Edge => query += "show edges;",
-            ColType::Tag => query += "show tags;",
+            ColType::Edge => query += " SHOW EDGES;",
+            ColType::Tag => query += " SHOW TAGS;",
         }
-        let resp = self.execute(session_id, query.as_str()).await.unwrap();
-        // print!("{:?}", resp);
+        let resp = self.execute(session_id, query.as_str()).await?;
         let res = resp.get_sVal();
         match res {
             Some(tags) => {
                 for tag in tags{
                     if tag == tag_or_edge_name.to_string(){
-                        return true;
+                        return Ok(true);
                     }
                 }
             }
-            None => return false
+            None => return Ok(false)
         }
-        return false;
+        Ok(false)
     }
 
-    #[inline]
-    pub async fn find_value_by_id(&self, space_name: &str, tag_name: &str, vid:&str, session_id: i64) -> i64{
-        //FETCH PROP ON TokenTransfer_tag "0x256a0b3cb2174d042e4445563ca4977f837a7803" YIELD TokenTransfer_tag.value; 
-        let mut query = String::from("use ");
-        query += space_name;
-        query += ";";
-        query += "FETCH PROP ON ";
-        query += tag_name;
-        query += " \"";
-        query += vid;
-        query += "\" ";
-        query += "YIELD ";
-        query += tag_name;
-        query += ".value";
-       
-        let resp = self.execute(session_id, query.as_str()).await.unwrap();
-       
-        if let None = resp.data{
-            return 0;
-        }
-       
-        resp.show_data();
-        let rows = resp.data.to_owned().unwrap().rows;
-        
-        for row in rows{
-            let values = row.values;
-            for value in values{
-                let my_int = value.parse_simple_type();
-                return my_int;
+    /// Wait until the tag or edge type is visible in the space since nebula
+    /// creates them asynchronously. Returns false if it is still not visible
+    /// after `timeout`, and an error if checking whether it is visible fails
+    pub async fn wait_for_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType, timeout: Duration, session_id: i64) -> std::result::Result<bool, NebulaError>{
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.find_tag_or_edge(space_name, tag_or_edge_name, col_type, session_id).await? {
+                return Ok(true);
             }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(SCHEMA_POLL_INTERVAL).await;
         }
-        return 0;
     }
 
-    pub async fn find_trasaction_by_rank(&self, space_name: &str, edge_name: &str, from_vertex: &str, to_vertex: &str, rank: i32, session_id: i64) -> String {
-        // FETCH PROP ON tx "0x256a0b3cb2174d042e4445563ca4977f837a7803" -> "0x893c9b967542ea01e58e1006e96e8b41e308194e"@193 yield tx.transactions;
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "FETCH PROP ON ";
-        query += edge_name;
-        query += " \"";
-        query += from_vertex;
-        query += "\" -> \"";
-        query += to_vertex;
-        query += "\"@";
-        query += rank.to_string().as_str();
-        query += " yield ";
-        query += edge_name;
-        query += ".";
-        query += "transactions";
-        query += ";";
-
-        let resp = self.execute(session_id, query.as_str()).await.unwrap();
-        if let None = resp.data{
-            return String::from("");
-        }
-        let rows = resp.data.to_owned().unwrap().rows;
-    
-        for row in rows{
-            let values = row.values;
-            for value in values{
-                match value {
-                    Value::mVal(m) => {
-                        let kvs = m.kvs;
-                        for kv in kvs{
-                            match kv.1 {
-                                Value::sVal(s) => {
-                                    let s1 = String::from_utf8(s).clone().unwrap();
-                                    return s1;
-                                }
-                                _ => ()
-                            }
-                            
-                        }
-                    }
-                    _ => ()
-                }
+    #[inline]
+    pub fn use_space(space_name: &str) -> std::result::Result<String, NgqlError>{
+        Ok(format!("{};", Use(Identifier::new(space_name)?)))
+    }
+
+}
+
+/// The result of `query`, or `NebulaError::Server` if Nebula Graph could not
+/// execute it
+pub(crate) fn check_response(
+    resp: graph::types::ExecutionResponse,
+    query: &str,
+) -> std::result::Result<ResultSet, NebulaError> {
+    if resp.error_code != ErrorCode::SUCCEEDED {
+        return Err(NebulaError::Server {
+            code: resp.error_code,
+            msg: error_msg(resp.error_msg),
+            statement: query.to_string(),
+        });
+    }
+    Ok(ResultSet::from_response(resp)?)
+}
+
+/// The error code in the JSON answer of Nebula Graph, which has the form
+/// `{"errors": [{"code": 0}], "results": [..]}`
+pub(crate) fn json_error_code(json: &serde_json::Value) -> ErrorCode {
+    let code = json
+        .pointer("/errors/0/code")
+        .and_then(serde_json::Value::as_i64)
+        .unwrap_or(0);
+    ErrorCode(code as i32)
+}
+
+/// The JSON answer for `query`, or `NebulaError::Server` if Nebula Graph
+/// could not execute it
+pub(crate) fn check_json(
+    json: serde_json::Value,
+    query: &str,
+) -> std::result::Result<serde_json::Value, NebulaError> {
+    let code = json_error_code(&json);
+    if code != ErrorCode::SUCCEEDED {
+        let msg = json
+            .pointer("/errors/0/message")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default();
+        return Err(NebulaError::Server {
+            code,
+            msg: msg.to_string(),
+            statement: query.to_string(),
+        });
+    }
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn json_errors() {
+        let ok = json!({
+            "errors": [{ "code": 0 }],
+            "results": [{ "spaceName": "sgd1", "columns": ["a"], "data": [{ "row": [1] }] }]
+        });
+        assert_eq!(
+            Ok(ok.clone()),
+            check_json(ok, "YIELD 1 AS a").map_err(|e| e.code())
+        );
+
+        let failed = json!({
+            "errors": [{ "code": -1009, "message": "SemanticError: `x' not exist" }],
+            "results": [{ "spaceName": "" }]
+        });
+        match check_json(failed, "YIELD x") {
+            Err(NebulaError::Server { code, msg, .. }) => {
+                assert_eq!(ErrorCode::E_SEMANTIC_ERROR, code);
+                assert_eq!("SemanticError: `x' not exist", msg);
             }
+            other => panic!("expected a server error but got {:?}", other),
         }
-        return "".to_string();
-        
     }
 
-    #[inline]
-    pub fn use_space(space_name: &str) -> String{
-        let mut line = String::from("use ");
-        line += space_name;
-        line += ";";
-        line
+    #[test]
+    fn transport_sizes() {
+        let transport = transport_configuration(&PoolConfig::new());
+        assert_eq!(Duration::from_secs(5), transport.get_read_timeout());
+        assert_eq!(1024, transport.get_buf_size());
+        assert_eq!(4 * 1024, transport.get_max_buf_size());
+        assert_eq!(4, transport.get_max_parse_response_bytes_count());
+
+        let mut conf = PoolConfig::new();
+        conf.read_timeout(30_000).buf_size(8 * 1024);
+        let transport = transport_configuration(&conf);
+        assert_eq!(Duration::from_secs(30), transport.get_read_timeout());
+        assert_eq!(8 * 1024, transport.get_buf_size());
+        assert_eq!(8 * 1024, transport.get_max_buf_size());
+
+        let mut conf = PoolConfig::new();
+        conf.max_buf_size(16 * 1024 * 1024);
+        let transport = transport_configuration(&conf);
+        assert_eq!(16 * 1024 * 1024, transport.get_max_buf_size());
+        assert_eq!(u8::MAX, transport.get_max_parse_response_bytes_count());
     }
-
 }
@@ -1,64 +1,133 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Semaphore;
+
 use crate::graph_client::connection::Connection;
+use crate::graph_client::error::{error_msg, NebulaError};
+use crate::graph_client::nebula_url::UrlError;
+use crate::graph_client::ngql::Use;
 use crate::graph_client::pool_config::PoolConfig;
-use crate::graph_client::session::Session;
+use crate::graph_client::session::{OwnedSession, PoolRef, Session};
+
+/// How long an address that could not be reached is skipped before it is
+/// tried again
+const DEAD_HOST_RETRY: Duration = Duration::from_secs(10);
+
+/// A snapshot of the connections of a pool
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolState {
+    /// The connections that are open, whether a session uses them or not
+    pub open: usize,
+    /// The open connections that no session uses
+    pub idle: usize,
+    /// The callers that wait for a connection because the pool is exhausted
+    pub waiting: usize,
+}
+
+/// Receives the events of a pool, e.g., to report them as metrics
+pub trait PoolEventHandler: Send + Sync {
+    /// The state of the pool changed
+    fn handle_state(&self, _state: PoolState) {}
+    /// Connecting to `address` failed, and it is skipped for a while
+    fn handle_host_down(&self, _address: &str) {}
+    /// `address` could be reached again after it was marked as down
+    fn handle_host_up(&self, _address: &str) {}
+}
+
+struct NoEvents;
+
+impl PoolEventHandler for NoEvents {}
+
+struct IdleConnection {
+    conn: Connection,
+    since: Instant,
+    /// The session that was dropped with the connection; it is signed out
+    /// before the connection is used again
+    signout: Option<i64>,
+}
+
+struct Host {
+    address: String,
+    /// When connecting to the host last failed; `None` if it is up
+    down_since: Option<Instant>,
+}
+
+struct PoolInner {
+    /// The most recently given back connection is at the back
+    idle: VecDeque<IdleConnection>,
+    hosts: Vec<Host>,
+    /// The open connections, including the ones sessions use and the ones
+    /// that are being opened
+    open: usize,
+    waiting: usize,
+}
 
 /// The pool of connection to server, it's MT-safe to access.
 /// 与服务器的连接池，它是 MT 安全访问的。
+///
+/// At most `max_connection_pool_size` connections are handed out at the
+/// same time; callers wait for a connection to be given back after that,
+/// for at most `timeout` ms if it is not 0. Connections that were idle for
+/// longer than `idle_time` ms are closed by `check_health` unless that
+/// would leave fewer than `min_connection_pool_size` connections open.
+/// Addresses are used round-robin, skipping addresses that recently could
+/// not be reached
 pub struct ConnectionPool_nebula {
-    /// The connections
-    /// The interior mutable to enable could get multiple sessions in one scope
-    /// 内部可变启用可以在一个范围内获得多个会话
-    conns: std::sync::Mutex<std::cell::RefCell<std::collections::LinkedList<Connection>>>,
     /// It should be immutable
     /// 它应该是不可变的
     config: PoolConfig,
+    inner: Mutex<PoolInner>,
+    /// One permit for each connection that can still be handed out
+    permits: Semaphore,
     /// Address cursor
     /// 地址光标
-    cursor: std::sync::Mutex<usize>,
-    /// The total count of connections, contains which hold by session
-    /// 连接总数，包含会话持有的连接数
-    conns_count: std::sync::Mutex<usize>,
+    cursor: AtomicUsize,
+    events: Box<dyn PoolEventHandler>,
 }
 
 impl ConnectionPool_nebula {
     /// Construct pool by the configuration
     pub fn new(conf: &PoolConfig) -> Self {
+        Self::with_events(conf, Box::new(NoEvents))
+    }
 
-        // println!("conf: {:?}", conf.clone());
-
-        let conns = std::collections::LinkedList::<Connection>::new();
-        let pool = ConnectionPool_nebula {
-            conns: std::sync::Mutex::new(std::cell::RefCell::new(conns)),
+    /// Construct pool by the configuration that reports its events to
+    /// `events`
+    pub fn with_events(conf: &PoolConfig, events: Box<dyn PoolEventHandler>) -> Self {
+        assert!(conf.min_connection_pool_size <= conf.max_connection_pool_size);
+        assert!(!conf.addresses.is_empty());
+        let hosts = conf
+            .addresses
+            .iter()
+            .map(|address| Host {
+                address: address.clone(),
+                down_since: None,
+            })
+            .collect();
+        ConnectionPool_nebula {
             config: conf.clone(),
-            cursor: std::sync::Mutex::new(0),
-            conns_count: std::sync::Mutex::new(0),
-        };
-        assert!(pool.config.min_connection_pool_size <= pool.config.max_connection_pool_size);
-        // pool.new_connection(pool.config.min_connection_pool_size).await;
-        pool
+            inner: Mutex::new(PoolInner {
+                idle: VecDeque::new(),
+                hosts,
+                open: 0,
+                waiting: 0,
+            }),
+            permits: Semaphore::new(conf.max_connection_pool_size as usize),
+            cursor: AtomicUsize::new(0),
+            events,
+        }
     }
 
     // 创建一个新的connection，但是不在这个方法里连接nebula
-    pub fn new_pool(nebula_url: &str) -> Self{
-        let v:Vec<&str> = nebula_url.split('@').collect();
-        let v2:Vec<&str> = v[1].split('/').collect();
-        let add = String::from(v2[0]);
-        let v3:Vec<&str> = v[0].split(':').collect();
-        let username = String::from(v3[0]);
-        let password = String::from(v3[1]);
-
-        let mut conf = PoolConfig::new();
-        conf.min_connection_pool_size(2)
-            .max_connection_pool_size(10)
-            .address(add)
-            .set_username(username)
-            .set_password(password);
-        let pool = ConnectionPool_nebula::new(&conf);
-        // pool.create_new_connection().await;
-        pool
+    pub fn new_pool(nebula_url: &str) -> std::result::Result<Self, UrlError> {
+        Ok(ConnectionPool_nebula::new(&PoolConfig::new_conf(
+            nebula_url,
+        )?))
     }
 
-
     pub async fn create_new_connection(&self){
         self.new_connection(self.config.min_connection_pool_size).await;
     }
@@ -66,145 +135,448 @@ impl ConnectionPool_nebula {
     /// Get a session authenticated by username and password
     /// retry_connect means keep the connection available if true
     /// 获取由用户名和密码验证的会话 retry_connect 表示如果为真则保持连接可用
+    ///
+    /// Waits while the pool is exhausted. Fails with `NebulaError::Connect`
+    /// when no address can be reached, and with `NebulaError::Timeout` when
+    /// waiting took longer than `timeout`
     pub async fn get_session(
         &self,
-        // username: &str,
-        // password: &str,
         retry_connect: bool,
-    ) -> std::result::Result<Session<'_>, common::types::ErrorCode> {
+    ) -> std::result::Result<Session<'_>, NebulaError> {
+        self.session(PoolRef::Borrowed(self), retry_connect).await
+    }
+
+    /// Get a session like `get_session` that holds on to the pool instead
+    /// of borrowing it, e.g., to use it in a spawned task
+    pub async fn get_owned_session(
+        self: &Arc<Self>,
+        retry_connect: bool,
+    ) -> std::result::Result<OwnedSession, NebulaError> {
+        self.session(PoolRef::Shared(self.clone()), retry_connect)
+            .await
+    }
+
+    async fn session<'a>(
+        &self,
+        pool: PoolRef<'a>,
+        retry_connect: bool,
+    ) -> std::result::Result<Session<'a>, NebulaError> {
+        self.acquire().await?;
+        let result = self.checkout(pool, retry_connect).await;
+        if result.is_err() {
+            self.permits.add_permits(1);
+        }
+        self.report();
+        result
+    }
 
-        let username = self.config.username.clone();
-        let password = self.config.password.clone();
+    /// Wait for a connection to become available and take its permit. The
+    /// permit is returned when the connection is given back or discarded
+    async fn acquire(&self) -> std::result::Result<(), NebulaError> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            permit.forget();
+            return Ok(());
+        }
+
+        self.inner.lock().unwrap().waiting += 1;
+        self.report();
+        let permit = match self.timeout() {
+            Some(timeout) => tokio::time::timeout(timeout, self.permits.acquire())
+                .await
+                .ok(),
+            None => Some(self.permits.acquire().await),
+        };
+        self.inner.lock().unwrap().waiting -= 1;
+        self.report();
 
+        match permit {
+            Some(Ok(permit)) => {
+                permit.forget();
+                Ok(())
+            }
+            // The semaphore is never closed
+            Some(Err(_)) | None => Err(NebulaError::Timeout(format!(
+                "after waiting {}ms for a connection",
+                self.config.timeout
+            ))),
+        }
+    }
 
-        // println!("==========getSession=============");
-        if self.conns.lock().unwrap().borrow_mut().is_empty() {
-            self.new_connection(1).await;
+    /// Log in with the credentials of the pool
+    async fn checkout<'a>(
+        &self,
+        pool: PoolRef<'a>,
+        retry_connect: bool,
+    ) -> std::result::Result<Session<'a>, NebulaError> {
+        let (conn, session_id, resp) = self
+            .login(&self.config.username, &self.config.password)
+            .await?;
+        let session = Session::new(
+            session_id,
+            conn,
+            pool,
+            self.config.username.clone(),
+            self.config.password.clone(),
+            if let Some(time_zone_name) = resp.time_zone_name {
+                String::from_utf8_lossy(&time_zone_name).to_string()
+            } else {
+                String::new()
+            },
+            resp.time_zone_offset_seconds.unwrap_or(0),
+            retry_connect,
+        );
+        if let Some(space) = &self.config.space {
+            session
+                .execute_checked(&format!("{};", Use(space.clone())))
+                .await?;
         }
-        let conn = self.conns.lock().unwrap().borrow_mut().pop_back();
-        if let Some(conn) = conn {
-            // get authentication with username and password
-            let resp = conn.authenticate(username.as_str(), password.as_str()).await?;
+        Ok(session)
+    }
+
+    /// Authenticate on an idle connection or, if there is none, on a new
+    /// one, and return the connection, the id of the new session, and the
+    /// answer of graphd. Connections that turn out to be broken are closed.
+    /// The caller must hold a permit for the connection
+    pub(crate) async fn login(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> std::result::Result<(Connection, i64, graph::types::AuthResponse), NebulaError> {
+        let mut attempts = 0;
+        let mut error = None;
+        loop {
+            let conn = match self.take_idle() {
+                Some(IdleConnection {
+                    conn,
+                    signout: Some(session_id),
+                    ..
+                }) => match conn.signout(session_id).await {
+                    Ok(()) => conn,
+                    Err(e) => {
+                        self.close(conn);
+                        error = Some(e);
+                        continue;
+                    }
+                },
+                Some(idle) => idle.conn,
+                None if attempts < self.config.addresses.len() => {
+                    attempts += 1;
+                    match self.open_connection().await {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            error = Some(e);
+                            continue;
+                        }
+                    }
+                }
+                None => {
+                    return Err(error.unwrap_or_else(|| {
+                        NebulaError::Timeout("while connecting to graphd".to_string())
+                    }))
+                }
+            };
+
+            let resp = match conn.authenticate(username, password).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    self.close(conn);
+                    error = Some(e);
+                    continue;
+                }
+            };
             if resp.error_code != common::types::ErrorCode::SUCCEEDED {
-                return Err(resp.error_code);
+                // The connection works, only the credentials don't
+                self.put_idle(conn, None);
+                return Err(NebulaError::Authentication {
+                    code: resp.error_code,
+                    msg: error_msg(resp.error_msg),
+                });
             }
-            Ok(Session::new(
-                resp.session_id.unwrap(),
-                conn,
-                self,
-                username.to_string(),
-                password.to_string(),
-                if let Some(time_zone_name) = resp.time_zone_name {
-                    std::str::from_utf8(&time_zone_name).unwrap().to_string()
-                } else {
-                    String::new()
-                },
-                resp.time_zone_offset_seconds.unwrap(),
-                retry_connect,
-            ))
-        } else {
-            Err(common::types::ErrorCode::E_UNKNOWN)
+            let session_id = match resp.session_id {
+                Some(session_id) => session_id,
+                None => {
+                    // Treated like a session that graphd lost, so that
+                    // callers log in again after backing off
+                    self.put_idle(conn, None);
+                    return Err(NebulaError::Server {
+                        code: common::types::ErrorCode::E_SESSION_INVALID,
+                        msg: "graphd did not return a session id".to_string(),
+                        statement: "AUTHENTICATE".to_string(),
+                    });
+                }
+            };
+            return Ok((conn, session_id, resp));
         }
     }
 
-    /// Get the count of connections
+    /// Get the count of idle connections
     #[inline]
     pub fn len(&self) -> usize {
-        self.conns.lock().unwrap().borrow().len()
+        self.inner.lock().unwrap().idle.len()
+    }
+
+    /// The current state of the pool
+    pub fn state(&self) -> PoolState {
+        let inner = self.inner.lock().unwrap();
+        PoolState {
+            open: inner.open,
+            idle: inner.idle.len(),
+            waiting: inner.waiting,
+        }
     }
 
-    /// Give back the connection to pool
+    /// Give back the connection of a session to pool
     #[inline]
     pub fn give_back(&self, conn: Connection) {
-        self.conns.lock().unwrap().borrow_mut().push_back(conn);
+        self.put_idle(conn, None);
+        self.permits.add_permits(1);
+        self.report();
+    }
+
+    /// Give back the connection of a session that was not signed out yet.
+    /// The session is signed out before the connection is used again
+    pub fn give_back_session(&self, conn: Connection, session_id: i64) {
+        self.put_idle(conn, Some(session_id));
+        self.permits.add_permits(1);
+        self.report();
     }
 
+    /// Close the connection of a session that no longer works
+    pub fn discard(&self, conn: Connection) {
+        self.close(conn);
+        self.permits.add_permits(1);
+        self.report();
+    }
+
+    /// Open up to `inc` idle connections without exceeding
+    /// `max_connection_pool_size`
     pub async fn new_connection(&self, inc: u32) {
         assert!(inc != 0);
-        // TODO concurrent these
         let mut count = 0;
-        let mut loop_count = 0;
-        let loop_limit = inc as usize * self.config.addresses.len();
-        while count < inc {
-
-            let conns_count_mutex: usize;
-
-            {
-                conns_count_mutex = * self.conns_count.lock().unwrap();
-            }
-
-            if count as usize
-                + conns_count_mutex
-                >= self.config.max_connection_pool_size as usize
-            {
+        let mut attempts = 0;
+        let attempt_limit = inc as usize * self.config.addresses.len();
+        while count < inc && attempts < attempt_limit {
+            // Take a permit while connecting so that the connection is
+            // counted against the size of the pool
+            let permit = match self.permits.try_acquire() {
+                Ok(permit) => permit,
                 // Reach the pool size limit
+                Err(_) => break,
+            };
+            if self.state().open >= self.config.max_connection_pool_size as usize {
                 break;
             }
-            let cursor = { self.cursor() };
-            match Connection::new_from_address(&self.config.addresses[cursor]).await {
-                Ok(conn) => {
-                    // append the conn to the conenction list
-                    self.conns.lock().unwrap().borrow_mut().push_back(conn);
-                    count += 1;
+            attempts += 1;
+            if let Ok(conn) = self.open_connection().await {
+                self.put_idle(conn, None);
+                count += 1;
+            }
+            drop(permit);
+        }
+        self.report();
+    }
+
+    /// Close connections that were idle for too long, check that the other
+    /// idle connections still work, and open connections until there are
+    /// `min_connection_pool_size` of them. Meant to be called periodically
+    pub async fn check_health(&self) {
+        let idle = {
+            let mut inner = self.inner.lock().unwrap();
+            let min = self.config.min_connection_pool_size as usize;
+            let mut idle = Vec::new();
+            // The connections that were idle the longest are at the front
+            while let Some(conn) = inner.idle.pop_front() {
+                let expired = match self.idle_time() {
+                    Some(idle_time) => conn.since.elapsed() > idle_time,
+                    None => false,
+                };
+                if expired && inner.open > min {
+                    inner.open -= 1;
+                } else {
+                    idle.push(conn);
                 }
-                Err(_) => (),
+            }
+            idle
+        };
+
+        for IdleConnection {
+            conn,
+            since,
+            signout,
+        } in idle
+        {
+            // Signing out also shows that the connection works
+            let works = match signout {
+                Some(session_id) => conn.signout(session_id).await.is_ok(),
+                None => conn.ping().await.is_ok(),
             };
-            loop_count += 1;
-            if loop_count > loop_limit {
-                // Can't get so many connections, avoid dead loop
-                break;
+            if works {
+                self.inner.lock().unwrap().idle.push_back(IdleConnection {
+                    conn,
+                    since,
+                    signout: None,
+                });
+            } else {
+                self.close(conn);
             }
         }
-        // Release ordering make sure inc happened after creating new connections
-        // 发布订单确保在创建新连接后发生 inc
-        
-        let mut conns_count_add = self.conns_count.lock().unwrap();
-        * conns_count_add += count as usize;
 
-        // self.conns_count
-        //     .borrow_mut()
-        //     .fetch_add(count as usize, std::sync::atomic::Ordering::Release);
+        let open = self.state().open;
+        let min = self.config.min_connection_pool_size as usize;
+        if open < min {
+            self.new_connection((min - open) as u32).await;
+        }
+        self.report();
     }
 
+    fn take_idle(&self) -> Option<IdleConnection> {
+        self.inner.lock().unwrap().idle.pop_back()
+    }
 
-        // cursor on the server addresses
-    // 服务器地址上的光标
-    fn cursor(&self) -> usize {
-        // println!("current cursor: {:?}", self.cursor.borrow().load(std::sync::atomic::Ordering::Relaxed));
-
-        // println!("current config.addresses.len(): {:?}", self.config.addresses.len());
-        let cursor_mutex: usize ;
+    fn put_idle(&self, conn: Connection, signout: Option<i64>) {
+        self.inner.lock().unwrap().idle.push_back(IdleConnection {
+            conn,
+            since: Instant::now(),
+            signout,
+        });
+    }
 
-        {
-            cursor_mutex = * self.cursor.lock().unwrap();
-        }
+    /// Close a connection without giving back its permit
+    pub(crate) fn close(&self, conn: Connection) {
+        drop(conn);
+        self.inner.lock().unwrap().open -= 1;
+    }
 
-        if cursor_mutex  >= self.config.addresses.len()
-        {
-            {
-                let mut a = self.cursor.lock().unwrap();
-                * a = 0;
-            }
-                // println!("return 0");
-            0
-        } else {
-            // println!("return cursor");
+    /// Connect to the next address that is not down. When all of them are
+    /// down, the next address is tried anyway. Marks the address as down if
+    /// connecting fails
+    async fn open_connection(&self) -> std::result::Result<Connection, NebulaError> {
+        let address = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.open += 1;
+            let hosts = inner.hosts.len();
+            let start = self.cursor();
+            let host = (0..hosts)
+                .map(|i| (start + i) % hosts)
+                .find(|i| match inner.hosts[*i].down_since {
+                    Some(down_since) => down_since.elapsed() >= DEAD_HOST_RETRY,
+                    None => true,
+                })
+                .unwrap_or(start);
+            inner.hosts[host].address.clone()
+        };
 
-            let b: usize;
+        let connect = Connection::new_from_address_with_conf(&address, &self.config);
+        let conn = match self.timeout() {
+            Some(timeout) => match tokio::time::timeout(timeout, connect).await {
+                Ok(conn) => conn,
+                Err(_) => Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("no answer within {}ms", self.config.timeout),
+                )),
+            },
+            None => connect.await,
+        };
 
-            {
-                b = * self.cursor.lock().unwrap();
+        let mut inner = self.inner.lock().unwrap();
+        let host = inner
+            .hosts
+            .iter_mut()
+            .find(|host| host.address == address)
+            .unwrap();
+        match conn {
+            Ok(conn) => {
+                if host.down_since.take().is_some() {
+                    self.events.handle_host_up(&address);
+                }
+                Ok(conn)
             }
-            {
-                let mut cursor_add = self.cursor.lock().unwrap();
-                * cursor_add += 1 as usize;
+            Err(source) => {
+                if host.down_since.is_none() {
+                    self.events.handle_host_down(&address);
+                }
+                host.down_since = Some(Instant::now());
+                inner.open -= 1;
+                Err(NebulaError::Connect { address, source })
             }
-            
-            b
         }
     }
 
+    /// The index of the next address to connect to
+    fn cursor(&self) -> usize {
+        self.cursor.fetch_add(1, Ordering::Relaxed) % self.config.addresses.len()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        match self.config.timeout {
+            0 => None,
+            timeout => Some(Duration::from_millis(timeout as u64)),
+        }
+    }
+
+    fn idle_time(&self) -> Option<Duration> {
+        match self.config.idle_time {
+            0 => None,
+            idle_time => Some(Duration::from_millis(idle_time as u64)),
+        }
+    }
+
+    fn report(&self) {
+        self.events.handle_state(self.state());
+    }
+
     pub fn get_config(&self){
         println!("{:?}", self.config);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(pool: &ConnectionPool_nebula, session_id: i64) -> Session<'_> {
+        Session::new(
+            session_id,
+            Connection::default(),
+            pool,
+            "root".to_string(),
+            "nebula".to_string(),
+            String::new(),
+            0,
+            true,
+        )
+    }
+
+    #[test]
+    fn abandoned_sessions_are_not_reused() {
+        let mut conf = PoolConfig::new();
+        conf.max_connection_pool_size(2)
+            .address("graphd:9669".to_string());
+        let pool = ConnectionPool_nebula::new(&conf);
+        // Pretend that two sessions were checked out
+        pool.inner.lock().unwrap().open = 2;
+        pool.permits.try_acquire_many(2).unwrap().forget();
+
+        drop(session(&pool, 1));
+        assert_eq!(
+            PoolState {
+                open: 2,
+                idle: 1,
+                waiting: 0
+            },
+            pool.state()
+        );
+        assert_eq!(Some(1), pool.inner.lock().unwrap().idle[0].signout);
+
+        session(&pool, 2).discard();
+        assert_eq!(
+            PoolState {
+                open: 1,
+                idle: 1,
+                waiting: 0
+            },
+            pool.state()
+        );
+        assert_eq!(2, pool.permits.available_permits());
+    }
+}
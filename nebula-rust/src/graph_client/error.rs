@@ -0,0 +1,199 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+use std::fmt;
+use std::io;
+
+use common::types::ErrorCode;
+use fbthrift::NonthrowingFunctionError;
+
+use crate::graph_client::ngql::NgqlError;
+use crate::value::error::ResultError;
+
+/// Why a request to graphd failed
+#[derive(Debug)]
+pub enum NebulaError {
+    /// Opening a connection to `address` failed
+    Connect { address: String, source: io::Error },
+    /// Sending a request or receiving its answer failed; the connection can
+    /// not be used any more
+    Transport(NonthrowingFunctionError),
+    /// Waiting for a connection or for an answer took too long
+    Timeout(String),
+    /// graphd rejected the username or password
+    Authentication { code: ErrorCode, msg: String },
+    /// graphd could not execute `statement`
+    Server {
+        code: ErrorCode,
+        msg: String,
+        statement: String,
+    },
+    /// The result of a statement could not be read
+    Decode(ResultError),
+    /// The statement could not be built, e.g., because a name is not a
+    /// valid identifier
+    Ngql(NgqlError),
+}
+
+impl NebulaError {
+    /// The error code of graphd, if the error came from graphd
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            NebulaError::Authentication { code, .. } | NebulaError::Server { code, .. } => {
+                Some(*code)
+            }
+            NebulaError::Connect { .. }
+            | NebulaError::Transport(_)
+            | NebulaError::Timeout(_)
+            | NebulaError::Decode(_)
+            | NebulaError::Ngql(_) => None,
+        }
+    }
+
+    /// Whether the session that the error happened on has to be replaced,
+    /// because its connection broke or graphd forgot the session
+    pub fn is_connection_lost(&self) -> bool {
+        match self {
+            NebulaError::Connect { .. } | NebulaError::Transport(_) => true,
+            NebulaError::Server { code, .. } => is_session_lost(*code),
+            NebulaError::Timeout(_)
+            | NebulaError::Authentication { .. }
+            | NebulaError::Decode(_)
+            | NebulaError::Ngql(_) => false,
+        }
+    }
+
+    /// Whether the same request might succeed when it is made again later,
+    /// possibly on a new session. Errors that are not retryable will fail
+    /// again no matter how often the request is repeated
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            NebulaError::Connect { .. } | NebulaError::Transport(_) | NebulaError::Timeout(_) => {
+                true
+            }
+            NebulaError::Server { code, .. } => [
+                ErrorCode::E_DISCONNECTED,
+                ErrorCode::E_FAIL_TO_CONNECT,
+                ErrorCode::E_RPC_FAILURE,
+                ErrorCode::E_LEADER_CHANGED,
+                ErrorCode::E_SESSION_INVALID,
+                ErrorCode::E_SESSION_TIMEOUT,
+                ErrorCode::E_TOO_MANY_CONNECTIONS,
+                ErrorCode::E_PARTIAL_SUCCEEDED,
+            ]
+            .contains(code),
+            NebulaError::Authentication { .. } | NebulaError::Decode(_) | NebulaError::Ngql(_) => {
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for NebulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NebulaError::Connect { address, source } => {
+                write!(f, "can not connect to {}: {}", address, source)
+            }
+            NebulaError::Transport(e) => write!(f, "request to graphd failed: {}", e),
+            NebulaError::Timeout(msg) => write!(f, "timed out {}", msg),
+            NebulaError::Authentication { code, msg } => {
+                write!(f, "authentication failed with {}: {}", code, msg)
+            }
+            NebulaError::Server {
+                code,
+                msg,
+                statement,
+            } => write!(f, "`{}` failed with {}: {}", statement, code, msg),
+            NebulaError::Decode(e) => write!(f, "{}", e),
+            NebulaError::Ngql(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for NebulaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NebulaError::Connect { source, .. } => Some(source),
+            NebulaError::Transport(e) => Some(e),
+            NebulaError::Decode(e) => Some(e),
+            NebulaError::Ngql(e) => Some(e),
+            NebulaError::Timeout(_)
+            | NebulaError::Authentication { .. }
+            | NebulaError::Server { .. } => None,
+        }
+    }
+}
+
+impl From<NonthrowingFunctionError> for NebulaError {
+    fn from(e: NonthrowingFunctionError) -> Self {
+        NebulaError::Transport(e)
+    }
+}
+
+impl From<ResultError> for NebulaError {
+    fn from(e: ResultError) -> Self {
+        NebulaError::Decode(e)
+    }
+}
+
+impl From<NgqlError> for NebulaError {
+    fn from(e: NgqlError) -> Self {
+        NebulaError::Ngql(e)
+    }
+}
+
+/// Whether graphd answered with `code` because it does not know the
+/// session any more. It did not run the statement in that case
+pub(crate) fn is_session_lost(code: ErrorCode) -> bool {
+    code == ErrorCode::E_SESSION_INVALID || code == ErrorCode::E_SESSION_TIMEOUT
+}
+
+/// The message of graphd as a string
+pub(crate) fn error_msg(msg: Option<Vec<u8>>) -> String {
+    msg.map(|msg| String::from_utf8_lossy(&msg).into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(code: ErrorCode) -> NebulaError {
+        NebulaError::Server {
+            code,
+            msg: String::new(),
+            statement: "YIELD 1".to_string(),
+        }
+    }
+
+    #[test]
+    fn classification() {
+        let lost = server(ErrorCode::E_SESSION_INVALID);
+        assert!(lost.is_connection_lost() && lost.is_retryable());
+
+        let busy = server(ErrorCode::E_LEADER_CHANGED);
+        assert!(!busy.is_connection_lost() && busy.is_retryable());
+
+        let syntax = server(ErrorCode::E_SYNTAX_ERROR);
+        assert!(!syntax.is_connection_lost() && !syntax.is_retryable());
+        assert_eq!(Some(ErrorCode::E_SYNTAX_ERROR), syntax.code());
+
+        let connect = NebulaError::Connect {
+            address: "graphd:9669".to_string(),
+            source: io::Error::new(io::ErrorKind::ConnectionRefused, "refused"),
+        };
+        assert!(connect.is_connection_lost() && connect.is_retryable());
+        assert!(std::error::Error::source(&connect).is_some());
+
+        let decode = NebulaError::from(ResultError::NoSuchIndex(3));
+        assert!(!decode.is_retryable());
+
+        let ngql = NebulaError::from(NgqlError::InvalidIdentifier("a`b".to_string()));
+        assert!(!ngql.is_connection_lost() && !ngql.is_retryable());
+        assert_eq!(None, ngql.code());
+    }
+}
@@ -6,7 +6,11 @@
 
 pub mod connection;
 pub mod connection_pool;
+pub mod error;
+pub mod nebula_url;
 pub mod pool_config;
 pub mod session;
 pub mod nebula_schema;
+pub mod ngql;
+pub mod tls;
 mod transport_response_handler;
@@ -1,4 +1,11 @@
 use std::collections::HashMap;
+use std::fmt;
+
+use crate::graph_client::ngql::{
+    in_space, DeleteEdges, DeleteTags, DeleteVertices, EdgeKey, EdgeRow, Identifier,
+    InsertEdges, InsertVertices, Literal, NgqlError, PropertyDef, UpsertEdge, UpsertVertex,
+    VertexRow,
+};
 
 /// contains all properties of both tag and edge
 pub struct Tag{
@@ -24,32 +31,29 @@ impl Tag {
             defaults: defaults.to_string(), 
             comment: comment.to_string() }
     }
-    pub fn to_string(&self)-> String{
-        let mut line = "`".to_string();
-        line += self.property_name.as_str();
-        line += "` ";
-        line += self.data_type.to_string().as_str();
-        line += " ";
-        if self.allow_null{
-            line += "NULL ";
-        }else{
-            line += "NOT NULL ";
-        }
-        if self.defaults!="".to_string(){
-            line += "DEFAULT \"";
-            line += self.defaults.as_str();
-            line += "\" ";
-        }
-        if self.comment!="".to_string(){
-            line += "COMMENT \"";
-            line += self.comment.as_str();
-            line += "\" ";
-        }
-        line.to_string()
+    /// the definition of the property in `CREATE TAG` and `CREATE EDGE`;
+    /// an empty default or comment is left out
+    pub fn to_property_def(&self) -> Result<PropertyDef, NgqlError>{
+        Ok(PropertyDef{
+            name: Identifier::new(&self.property_name)?,
+            data_type: self.data_type,
+            nullable: self.allow_null,
+            default: if self.defaults.is_empty(){
+                None
+            }else{
+                Some(Literal::String(self.defaults.clone()))
+            },
+            comment: if self.comment.is_empty(){
+                None
+            }else{
+                Some(self.comment.clone())
+            },
+        })
     }
 }
 
 /// frequently-used data type in NebulaGraph
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DataType {
     Int,
     Bool,
@@ -65,26 +69,28 @@ pub enum DataType {
     Time,
 }
 
-impl DataType {
-    fn to_string(&self) -> String{
-        match self{
-            DataType::Int => String::from("int"),
-            DataType::Bool => String::from("bool"),
-            DataType::String => String::from("string"),
-            DataType::FixedString => String::from("fixed_string"),
-            DataType::Double => String::from("double"),
-            DataType::Int64 => String::from("int64"),
-            DataType::Int32 => String::from("int32"),
-            DataType::Int16 => String::from("int16"),
-            DataType::Int8 => String::from("int8"),
-            DataType::Float => String::from("float"),
-            DataType::Date => String::from("date"),
-            DataType::Time => String::from("time"),
-        }
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let name = match self{
+            DataType::Int => "int",
+            DataType::Bool => "bool",
+            DataType::String => "string",
+            DataType::FixedString => "fixed_string",
+            DataType::Double => "double",
+            DataType::Int64 => "int64",
+            DataType::Int32 => "int32",
+            DataType::Int16 => "int16",
+            DataType::Int8 => "int8",
+            DataType::Float => "float",
+            DataType::Date => "date",
+            DataType::Time => "time",
+        };
+        write!(f, "{}", name)
     }
 }
 
 /// tag or edge
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ColType {
     Tag,
     Edge,
@@ -99,82 +105,77 @@ impl ColType {
     }
 }
 
-/// query of inserting tag
+/// the property names, sorted, and the values in the same order
+fn names_and_values(kv: &HashMap<Identifier, Literal>) -> (Vec<Identifier>, Vec<Literal>){
+    let mut names: Vec<&Identifier> = kv.keys().collect();
+    names.sort();
+    let values = names.iter().map(|name| kv[*name].clone()).collect();
+    (names.into_iter().cloned().collect(), values)
+}
+
+
+/// the assignments of an UPSERT, sorted by property name
+fn assignments(kv: &HashMap<Identifier, Literal>) -> Vec<(Identifier, Literal)>{
+    let (names, values) = names_and_values(kv);
+    names.into_iter().zip(values).collect()
+}
+
+
+/// query of inserting tag; `vid` is the id of the vertex as it is, and is
+/// quoted when the query is rendered
 pub struct InsertTagQuery{
-    pub space_name: String, 
-    pub tag_name: String, 
-    pub kv: HashMap<String, String>, 
+    pub space_name: Identifier, 
+    pub tag_name: Identifier, 
+    pub kv: HashMap<Identifier, Literal>, 
     pub vid: String,
-    pub add: bool,
-    pub value: i64,
 }
 impl InsertTagQuery{
     pub fn new(
-        space_name: String, 
-        tag_name: String, 
-        kv: HashMap<String, String>, 
+        space_name: Identifier, 
+        tag_name: Identifier, 
+        kv: HashMap<Identifier, Literal>, 
         vid: String,
-        add: bool,
-        value: i64,
     ) -> Self{
         InsertTagQuery{
             space_name, 
             tag_name, 
             kv, 
             vid,
-            add,
-            value,
         }
     }
-    pub fn to_string(&self)-> String{
-        let mut query = String::from("use ");
-        query += self.space_name.as_str();
-        query += "; ";
-        query += "INSERT VERTEX ";
-        query += self.tag_name.as_str();
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in &self.kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            keys += k.as_str();
-            values += v.as_str();
+    fn to_ngql(&self) -> InsertVertices{
+        let (properties, values) = names_and_values(&self.kv);
+        InsertVertices{
+            tag: self.tag_name.clone(),
+            properties,
+            rows: vec![VertexRow{ vid: self.vid.clone(), values }],
         }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += self.vid.as_str();
-        query += "\":";
-        query += values.as_str();
-        query += ";";
-        query
+    }
+}
+impl fmt::Display for InsertTagQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", in_space(&self.space_name, &self.to_ngql()))
     }
 }
 
 
 /// query of inserting edge with rank
 pub struct InsertEdgeQueryWithRank{
-    pub space_name: String, 
-    pub edge_name: String, 
-    pub kv: HashMap<String, String>, 
+    pub space_name: Identifier, 
+    pub edge_name: Identifier, 
+    pub kv: HashMap<Identifier, Literal>, 
     pub from_vertex: String, 
     pub to_vertex: String,
-    pub rank: i32,
-    pub value: i64,
+    pub rank: i64,
 }
 impl InsertEdgeQueryWithRank{
     pub fn new(
-        space_name: String, 
-        edge_name: String, 
-        kv: HashMap<String, String>, 
+        space_name: Identifier, 
+        edge_name: Identifier, 
+        kv: HashMap<Identifier, Literal>, 
         from_vertex: String, 
         to_vertex: String,
-        rank: i32,
-        value: i64,
+        rank: i64,
     ) -> Self{
         InsertEdgeQueryWithRank{
             space_name,
@@ -183,39 +184,277 @@ impl InsertEdgeQueryWithRank{
             from_vertex, 
             to_vertex,
             rank,
-            value
         }
     }
-    pub fn to_string(&self)-> String{
-        let mut query = String::from("use ");
-        query += self.space_name.as_str();
-        query += "; ";
-        query += "INSERT EDGE IF NOT EXISTS ";
-        query += self.edge_name.as_str();
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in &self.kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
+    pub fn key(&self) -> EdgeKey{
+        EdgeKey{
+            src: self.from_vertex.clone(),
+            dst: self.to_vertex.clone(),
+            rank: self.rank,
+        }
+    }
+    fn to_ngql(&self) -> InsertEdges{
+        let (properties, values) = names_and_values(&self.kv);
+        InsertEdges{
+            edge: self.edge_name.clone(),
+            properties,
+            rows: vec![EdgeRow{ key: self.key(), values }],
+        }
+    }
+}
+impl fmt::Display for InsertEdgeQueryWithRank{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", in_space(&self.space_name, &self.to_ngql()))
+    }
+}
+
+
+/// query of deleting the tag of a vertex
+pub struct DeleteTagQuery{
+    pub space_name: Identifier, 
+    pub tag_name: Identifier, 
+    pub vid: String,
+}
+impl DeleteTagQuery{
+    pub fn new(
+        space_name: Identifier, 
+        tag_name: Identifier, 
+        vid: String,
+    ) -> Self{
+        DeleteTagQuery{
+            space_name, 
+            tag_name, 
+            vid,
+        }
+    }
+}
+impl fmt::Display for DeleteTagQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let query = DeleteTags{
+            tags: vec![self.tag_name.clone()],
+            vids: vec![self.vid.clone()],
+        };
+        write!(f, "{}", in_space(&self.space_name, &query))
+    }
+}
+
+
+/// query of deleting edge with rank
+pub struct DeleteEdgeQuery{
+    pub space_name: Identifier, 
+    pub edge_name: Identifier, 
+    pub from_vertex: String, 
+    pub to_vertex: String,
+    pub rank: i64,
+}
+impl DeleteEdgeQuery{
+    pub fn new(
+        space_name: Identifier, 
+        edge_name: Identifier, 
+        from_vertex: String, 
+        to_vertex: String,
+        rank: i64,
+    ) -> Self{
+        DeleteEdgeQuery{
+            space_name,
+            edge_name, 
+            from_vertex, 
+            to_vertex,
+            rank,
+        }
+    }
+}
+impl fmt::Display for DeleteEdgeQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let query = DeleteEdges{
+            edge: self.edge_name.clone(),
+            keys: vec![EdgeKey{
+                src: self.from_vertex.clone(),
+                dst: self.to_vertex.clone(),
+                rank: self.rank,
+            }],
+        };
+        write!(f, "{}", in_space(&self.space_name, &query))
+    }
+}
+
+
+/// query of deleting a vertex; its edges are left in place
+pub struct DeleteVertexQuery{
+    pub space_name: Identifier, 
+    pub vid: String,
+}
+impl DeleteVertexQuery{
+    pub fn new(
+        space_name: Identifier, 
+        vid: String,
+    ) -> Self{
+        DeleteVertexQuery{
+            space_name, 
+            vid,
+        }
+    }
+}
+impl fmt::Display for DeleteVertexQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let query = DeleteVertices{ vids: vec![self.vid.clone()] };
+        write!(f, "{}", in_space(&self.space_name, &query))
+    }
+}
+
+
+/// query of updating the properties of a tag, inserting the tag if it
+/// does not exist
+pub struct UpsertTagQuery{
+    pub space_name: Identifier, 
+    pub tag_name: Identifier, 
+    pub kv: HashMap<Identifier, Literal>, 
+    pub vid: String,
+}
+impl UpsertTagQuery{
+    pub fn new(
+        space_name: Identifier, 
+        tag_name: Identifier, 
+        kv: HashMap<Identifier, Literal>, 
+        vid: String,
+    ) -> Self{
+        UpsertTagQuery{
+            space_name, 
+            tag_name, 
+            kv, 
+            vid,
+        }
+    }
+}
+impl fmt::Display for UpsertTagQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let query = UpsertVertex{
+            tag: self.tag_name.clone(),
+            vid: self.vid.clone(),
+            set: assignments(&self.kv),
+        };
+        write!(f, "{}", in_space(&self.space_name, &query))
+    }
+}
+
+
+/// query of updating the properties of an edge, inserting the edge if it
+/// does not exist
+pub struct UpsertEdgeQuery{
+    pub space_name: Identifier, 
+    pub edge_name: Identifier, 
+    pub kv: HashMap<Identifier, Literal>, 
+    pub from_vertex: String, 
+    pub to_vertex: String,
+    pub rank: i64,
+}
+impl UpsertEdgeQuery{
+    pub fn new(
+        space_name: Identifier, 
+        edge_name: Identifier, 
+        kv: HashMap<Identifier, Literal>, 
+        from_vertex: String, 
+        to_vertex: String,
+        rank: i64,
+    ) -> Self{
+        UpsertEdgeQuery{
+            space_name,
+            edge_name, 
+            kv, 
+            from_vertex, 
+            to_vertex,
+            rank,
+        }
+    }
+}
+impl fmt::Display for UpsertEdgeQuery{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let query = UpsertEdge{
+            edge: self.edge_name.clone(),
+            key: EdgeKey{
+                src: self.from_vertex.clone(),
+                dst: self.to_vertex.clone(),
+                rank: self.rank,
+            },
+            set: assignments(&self.kv),
+        };
+        write!(f, "{}", in_space(&self.space_name, &query))
+    }
+}
+
+
+/// The default for the maximum size in bytes of the statements that
+/// `batch_insert_tags` and `batch_insert_edges` generate
+pub const DEFAULT_MAX_STATEMENT_SIZE: usize = 1024 * 1024;
+
+
+/// Accumulates the rows of multi-row INSERT statements, starting a new
+/// statement whenever adding a row would make the current one longer than
+/// `max_statement_size`. A statement always holds at least one row
+struct StatementBatcher{
+    max_statement_size: usize,
+    /// The position in `statements` of the statement that is currently
+    /// being filled for each header
+    open: HashMap<String, usize>,
+    statements: Vec<String>,
+}
+impl StatementBatcher{
+    fn new(max_statement_size: usize) -> Self{
+        StatementBatcher{
+            max_statement_size,
+            open: HashMap::new(),
+            statements: Vec::new(),
+        }
+    }
+    fn push(&mut self, header: String, row: String){
+        if let Some(pos) = self.open.get(&header){
+            let statement = &mut self.statements[*pos];
+            // the row is appended as `, <row>` and the `;` stays at the end
+            if statement.len() + row.len() + 2 <= self.max_statement_size{
+                statement.pop();
+                *statement += ", ";
+                *statement += row.as_str();
+                *statement += ";";
+                return;
             }
-            keys += k.as_str();
-            values += v.as_str();
         }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += self.from_vertex.as_str();
-        query += "\" -> \"";
-        query += self.to_vertex.as_str();
-        query += "\"@";
-        query += self.rank.to_string().as_str();
-        query += ":";
-        query += values.as_str();
-        query += ";";
-        query
+        let mut statement = header.clone();
+        statement += row.as_str();
+        statement += ";";
+        self.open.insert(header, self.statements.len());
+        self.statements.push(statement);
+    }
+    fn finish(self) -> Vec<String>{
+        self.statements
     }
 }
 
+
+/// Combine the queries for the same space, tag and properties into
+/// multi-row `INSERT VERTEX` statements of at most `max_statement_size`
+/// bytes; a single row that is longer than that gets its own statement.
+/// The statements keep the order in which the queries were given for each
+/// tag
+pub fn batch_insert_tags(queries: &[InsertTagQuery], max_statement_size: usize) -> Vec<String>{
+    let mut batcher = StatementBatcher::new(max_statement_size);
+    for query in queries{
+        let insert = query.to_ngql();
+        let header = format!("USE {}; {}", query.space_name, insert.header());
+        batcher.push(header, InsertVertices::row(&insert.rows[0]));
+    }
+    batcher.finish()
+}
+
+
+/// Combine the queries for the same space, edge type and properties into
+/// multi-row `INSERT EDGE` statements of at most `max_statement_size`
+/// bytes, like `batch_insert_tags`
+pub fn batch_insert_edges(queries: &[InsertEdgeQueryWithRank], max_statement_size: usize) -> Vec<String>{
+    let mut batcher = StatementBatcher::new(max_statement_size);
+    for query in queries{
+        let insert = query.to_ngql();
+        let header = format!("USE {}; {}", query.space_name, insert.header());
+        batcher.push(header, InsertEdges::row(&insert.rows[0]));
+    }
+    batcher.finish()
+}
@@ -0,0 +1,1191 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! A typed builder for nGQL statements.
+//!
+//! Names of spaces, tags, edge types, indexes and properties are
+//! `Identifier`s, which are always rendered between backticks, and values
+//! are `Literal`s, which are rendered as nGQL literals with strings quoted
+//! and escaped. Statements implement `Display` and render without a
+//! trailing `;`; `in_space` prefixes a statement with `USE`.
+//!
+//! Expressions in `WHERE`, `YIELD` and `RETURN` clauses are passed through
+//! as they are; they should be built from the `Display` of `Identifier`s
+//! and `Literal`s rather than from untrusted strings.
+
+use std::borrow::Borrow;
+use std::fmt;
+
+use crate::graph_client::nebula_schema::{ColType, DataType};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NgqlError {
+    /// The name can not be used as an identifier
+    InvalidIdentifier(String),
+}
+
+impl fmt::Display for NgqlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NgqlError::InvalidIdentifier(name) => {
+                write!(f, "`{}` can not be used as an nGQL identifier", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NgqlError {}
+
+/// The name of a space, tag, edge type, index or property. NebulaGraph has
+/// no way to escape a backtick in a quoted name, and names therefore must
+/// not be empty and must not contain backticks or control characters
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Identifier(String);
+
+impl Identifier {
+    pub fn new(name: &str) -> Result<Identifier, NgqlError> {
+        if name.is_empty() || name.chars().any(|c| c == '`' || c.is_control()) {
+            return Err(NgqlError::InvalidIdentifier(name.to_string()));
+        }
+        Ok(Identifier(name.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.0)
+    }
+}
+
+impl Borrow<str> for Identifier {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Quote `s` as an nGQL string literal
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A constant value in an nGQL statement
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// Non-finite numbers have no literal and are rendered as `NULL`
+    Float(f64),
+    String(String),
+    List(Vec<Literal>),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Null => write!(f, "NULL"),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Int(i) => write!(f, "{}", i),
+            Literal::Float(x) if !x.is_finite() => write!(f, "NULL"),
+            Literal::Float(x) => {
+                // Make sure that the literal is not read as an integer
+                let s = format!("{:?}", x);
+                if s.contains('.') || s.contains('e') {
+                    write!(f, "{}", s)
+                } else {
+                    write!(f, "{}.0", s)
+                }
+            }
+            Literal::String(s) => write!(f, "{}", quote(s)),
+            Literal::List(values) => write!(f, "[{}]", join(values, ", ")),
+        }
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(s: &str) -> Self {
+        Literal::String(s.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(s: String) -> Self {
+        Literal::String(s)
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(i: i64) -> Self {
+        Literal::Int(i)
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(i: i32) -> Self {
+        Literal::Int(i as i64)
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(b: bool) -> Self {
+        Literal::Bool(b)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(x: f64) -> Self {
+        Literal::Float(x)
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], sep: &str) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn comment(comment: &Option<String>) -> String {
+    match comment {
+        Some(comment) => format!(" COMMENT = {}", quote(comment)),
+        None => String::new(),
+    }
+}
+
+/// `USE <space>; <statement>;`
+pub fn in_space(space: &Identifier, statement: &dyn fmt::Display) -> String {
+    format!("USE {}; {};", space, statement)
+}
+
+/// `USE <space>`
+pub struct Use(pub Identifier);
+
+impl fmt::Display for Use {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "USE {}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VidType {
+    Int64,
+    FixedString(u32),
+}
+
+impl fmt::Display for VidType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VidType::Int64 => write!(f, "INT64"),
+            VidType::FixedString(len) => write!(f, "FIXED_STRING({})", len),
+        }
+    }
+}
+
+/// `CREATE SPACE IF NOT EXISTS`
+pub struct CreateSpace {
+    pub name: Identifier,
+    pub partition_num: u32,
+    pub replica_factor: u32,
+    pub vid_type: VidType,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for CreateSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CREATE SPACE IF NOT EXISTS {} (partition_num = {}, replica_factor = {}, vid_type = {}){}",
+            self.name,
+            self.partition_num,
+            self.replica_factor,
+            self.vid_type,
+            comment(&self.comment)
+        )
+    }
+}
+
+/// `DROP SPACE IF EXISTS`
+pub struct DropSpace(pub Identifier);
+
+impl fmt::Display for DropSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DROP SPACE IF EXISTS {}", self.0)
+    }
+}
+
+/// The definition of a property in `CREATE TAG` and `CREATE EDGE`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PropertyDef {
+    pub name: Identifier,
+    pub data_type: DataType,
+    pub nullable: bool,
+    pub default: Option<Literal>,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for PropertyDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.data_type)?;
+        if self.nullable {
+            write!(f, " NULL")?;
+        } else {
+            write!(f, " NOT NULL")?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, " DEFAULT {}", default)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " COMMENT {}", quote(comment))?;
+        }
+        Ok(())
+    }
+}
+
+/// `CREATE TAG IF NOT EXISTS` or `CREATE EDGE IF NOT EXISTS`
+pub struct CreateSchema {
+    pub kind: ColType,
+    pub name: Identifier,
+    pub properties: Vec<PropertyDef>,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for CreateSchema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CREATE {} IF NOT EXISTS {}({}){}",
+            keyword(&self.kind),
+            self.name,
+            join(&self.properties, ", "),
+            comment(&self.comment)
+        )
+    }
+}
+
+fn keyword(kind: &ColType) -> &'static str {
+    match kind {
+        ColType::Tag => "TAG",
+        ColType::Edge => "EDGE",
+    }
+}
+
+/// A property of an index; string properties need a prefix length
+pub struct IndexField {
+    pub name: Identifier,
+    pub length: Option<u16>,
+}
+
+impl fmt::Display for IndexField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.length {
+            Some(length) => write!(f, "{}({})", self.name, length),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// `CREATE TAG INDEX IF NOT EXISTS` or `CREATE EDGE INDEX IF NOT EXISTS`
+pub struct CreateIndex {
+    pub kind: ColType,
+    pub name: Identifier,
+    pub schema: Identifier,
+    pub fields: Vec<IndexField>,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for CreateIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "CREATE {} INDEX IF NOT EXISTS {} ON {}({}){}",
+            keyword(&self.kind),
+            self.name,
+            self.schema,
+            join(&self.fields, ", "),
+            comment(&self.comment)
+        )
+    }
+}
+
+/// The vertices of a space use strings as ids; all builders quote vertex
+/// ids as strings
+fn vid(vid: &str) -> String {
+    quote(vid)
+}
+
+fn vids(vids: &[String]) -> String {
+    vids.iter().map(|v| vid(v)).collect::<Vec<_>>().join(", ")
+}
+
+/// The key of an edge: its source, target and rank
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EdgeKey {
+    pub src: String,
+    pub dst: String,
+    pub rank: i64,
+}
+
+impl fmt::Display for EdgeKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}->{}@{}", vid(&self.src), vid(&self.dst), self.rank)
+    }
+}
+
+pub struct VertexRow {
+    pub vid: String,
+    pub values: Vec<Literal>,
+}
+
+/// `INSERT VERTEX` with one or more rows; the values of each row are in
+/// the order of `properties`
+pub struct InsertVertices {
+    pub tag: Identifier,
+    pub properties: Vec<Identifier>,
+    pub rows: Vec<VertexRow>,
+}
+
+impl InsertVertices {
+    /// `INSERT VERTEX <tag>(<properties>) VALUES `; rows are appended to it
+    pub fn header(&self) -> String {
+        format!(
+            "INSERT VERTEX {}({}) VALUES ",
+            self.tag,
+            join(&self.properties, ", ")
+        )
+    }
+
+    pub fn row(row: &VertexRow) -> String {
+        format!("{}:({})", vid(&row.vid), join(&row.values, ", "))
+    }
+}
+
+impl fmt::Display for InsertVertices {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<_> = self.rows.iter().map(InsertVertices::row).collect();
+        write!(f, "{}{}", self.header(), rows.join(", "))
+    }
+}
+
+pub struct EdgeRow {
+    pub key: EdgeKey,
+    pub values: Vec<Literal>,
+}
+
+/// `INSERT EDGE` with one or more rows; the values of each row are in the
+/// order of `properties`
+pub struct InsertEdges {
+    pub edge: Identifier,
+    pub properties: Vec<Identifier>,
+    pub rows: Vec<EdgeRow>,
+}
+
+impl InsertEdges {
+    /// `INSERT EDGE <edge>(<properties>) VALUES `; rows are appended to it
+    pub fn header(&self) -> String {
+        format!(
+            "INSERT EDGE {}({}) VALUES ",
+            self.edge,
+            join(&self.properties, ", ")
+        )
+    }
+
+    pub fn row(row: &EdgeRow) -> String {
+        format!("{}:({})", row.key, join(&row.values, ", "))
+    }
+}
+
+impl fmt::Display for InsertEdges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<_> = self.rows.iter().map(InsertEdges::row).collect();
+        write!(f, "{}{}", self.header(), rows.join(", "))
+    }
+}
+
+fn assignments(set: &[(Identifier, Literal)]) -> String {
+    set.iter()
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `UPSERT VERTEX ON <tag> <vid> SET ...`
+pub struct UpsertVertex {
+    pub tag: Identifier,
+    pub vid: String,
+    pub set: Vec<(Identifier, Literal)>,
+}
+
+impl fmt::Display for UpsertVertex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UPSERT VERTEX ON {} {} SET {}",
+            self.tag,
+            vid(&self.vid),
+            assignments(&self.set)
+        )
+    }
+}
+
+/// `UPSERT EDGE ON <edge> <src>-><dst>@<rank> SET ...`
+pub struct UpsertEdge {
+    pub edge: Identifier,
+    pub key: EdgeKey,
+    pub set: Vec<(Identifier, Literal)>,
+}
+
+impl fmt::Display for UpsertEdge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UPSERT EDGE ON {} {} SET {}",
+            self.edge,
+            self.key,
+            assignments(&self.set)
+        )
+    }
+}
+
+/// `DELETE VERTEX`. Since NebulaGraph 3.0, this leaves the edges of the
+/// vertices in place; they are only deleted with `WITH EDGE`
+pub struct DeleteVertices {
+    pub vids: Vec<String>,
+}
+
+impl fmt::Display for DeleteVertices {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DELETE VERTEX {}", vids(&self.vids))
+    }
+}
+
+/// `DELETE TAG <tags> FROM <vids>`
+pub struct DeleteTags {
+    pub tags: Vec<Identifier>,
+    pub vids: Vec<String>,
+}
+
+impl fmt::Display for DeleteTags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DELETE TAG {} FROM {}",
+            join(&self.tags, ", "),
+            vids(&self.vids)
+        )
+    }
+}
+
+/// `DELETE EDGE <edge> <keys>`
+pub struct DeleteEdges {
+    pub edge: Identifier,
+    pub keys: Vec<EdgeKey>,
+}
+
+impl fmt::Display for DeleteEdges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DELETE EDGE {} {}", self.edge, join(&self.keys, ", "))
+    }
+}
+
+fn yield_clause(yields: &[String]) -> String {
+    if yields.is_empty() {
+        String::new()
+    } else {
+        format!(" YIELD {}", yields.join(", "))
+    }
+}
+
+/// `FETCH PROP ON <tags> <vids>`; without tags, all tags are fetched
+pub struct FetchVertices {
+    pub tags: Vec<Identifier>,
+    pub vids: Vec<String>,
+    pub yields: Vec<String>,
+}
+
+impl fmt::Display for FetchVertices {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tags = if self.tags.is_empty() {
+            "*".to_string()
+        } else {
+            join(&self.tags, ", ")
+        };
+        write!(
+            f,
+            "FETCH PROP ON {} {}{}",
+            tags,
+            vids(&self.vids),
+            yield_clause(&self.yields)
+        )
+    }
+}
+
+/// `FETCH PROP ON <edge> <keys>`
+pub struct FetchEdges {
+    pub edge: Identifier,
+    pub keys: Vec<EdgeKey>,
+    pub yields: Vec<String>,
+}
+
+impl fmt::Display for FetchEdges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FETCH PROP ON {} {}{}",
+            self.edge,
+            join(&self.keys, ", "),
+            yield_clause(&self.yields)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+    Both,
+}
+
+/// `GO [<min> TO] <max> STEPS FROM <vids> OVER <edges>`
+pub struct Go {
+    pub min_steps: Option<u32>,
+    pub max_steps: u32,
+    pub from: Vec<String>,
+    /// Without edge types, all edge types are traversed
+    pub over: Vec<Identifier>,
+    pub direction: Direction,
+    pub filter: Option<String>,
+    pub yields: Vec<String>,
+}
+
+impl fmt::Display for Go {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GO ")?;
+        if let Some(min_steps) = self.min_steps {
+            write!(f, "{} TO ", min_steps)?;
+        }
+        let over = if self.over.is_empty() {
+            "*".to_string()
+        } else {
+            join(&self.over, ", ")
+        };
+        write!(
+            f,
+            "{} STEPS FROM {} OVER {}",
+            self.max_steps,
+            vids(&self.from),
+            over
+        )?;
+        match self.direction {
+            Direction::Outgoing => {}
+            Direction::Incoming => write!(f, " REVERSELY")?,
+            Direction::Both => write!(f, " BIDIRECT")?,
+        }
+        if let Some(filter) = &self.filter {
+            write!(f, " WHERE {}", filter)?;
+        }
+        write!(f, "{}", yield_clause(&self.yields))
+    }
+}
+
+/// Which paths `FIND PATH` looks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathKind {
+    Shortest,
+    All,
+    /// All paths that do not visit a vertex twice
+    NoLoop,
+}
+
+/// `FIND <kind> PATH FROM <vids> TO <vids> OVER <edges> UPTO <max> STEPS
+/// YIELD path AS <alias>`
+pub struct FindPath {
+    pub kind: PathKind,
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+    /// Without edge types, all edge types are traversed
+    pub over: Vec<Identifier>,
+    pub direction: Direction,
+    pub max_steps: u32,
+    pub alias: String,
+}
+
+impl fmt::Display for FindPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            PathKind::Shortest => "SHORTEST",
+            PathKind::All => "ALL",
+            PathKind::NoLoop => "NOLOOP",
+        };
+        let over = if self.over.is_empty() {
+            "*".to_string()
+        } else {
+            join(&self.over, ", ")
+        };
+        write!(
+            f,
+            "FIND {} PATH FROM {} TO {} OVER {}",
+            kind,
+            vids(&self.from),
+            vids(&self.to),
+            over
+        )?;
+        match self.direction {
+            Direction::Outgoing => {}
+            Direction::Incoming => write!(f, " REVERSELY")?,
+            Direction::Both => write!(f, " BIDIRECT")?,
+        }
+        write!(
+            f,
+            " UPTO {} STEPS YIELD path AS {}",
+            self.max_steps, self.alias
+        )
+    }
+}
+
+/// `MATCH <pattern> [WHERE <filter>] RETURN <returns>`
+pub struct Match {
+    pub pattern: String,
+    pub filter: Option<String>,
+    pub returns: Vec<String>,
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MATCH {}", self.pattern)?;
+        if let Some(filter) = &self.filter {
+            write!(f, " WHERE {}", filter)?;
+        }
+        write!(f, " RETURN {}", self.returns.join(", "))
+    }
+}
+
+/// The statements that have the same effect whether they run once or
+/// twice. `INSERT` overwrites and `DELETE` ignores what is already gone;
+/// `CREATE` and `DROP` only count with `IF [NOT] EXISTS`
+const IDEMPOTENT: &[&str] = &[
+    "USE", "MATCH", "OPTIONAL", "UNWIND", "WITH", "RETURN", "GO", "FETCH", "LOOKUP", "FIND", "GET",
+    "SHOW", "DESCRIBE", "DESC", "YIELD", "ORDER", "LIMIT", "GROUP", "EXPLAIN", "INSERT", "DELETE",
+];
+
+/// Whether `query` can be sent again when it is not known whether graphd
+/// ran it, e.g., because the connection broke while waiting for the
+/// answer. Statements that are not known to be idempotent, like `UPDATE`
+/// or `UPSERT`, make the whole query not idempotent
+pub fn is_idempotent(query: &str) -> bool {
+    clauses(query).into_iter().all(|clause| {
+        let words = match clause_words(clause) {
+            Some(words) => words,
+            None => return false,
+        };
+        let has = |needle: &[&str]| words.windows(needle.len()).any(|w| w == needle);
+        match words.first().map(String::as_str) {
+            None => true,
+            Some("CREATE") => has(&["IF", "NOT", "EXISTS"]),
+            Some("DROP") => has(&["IF", "EXISTS"]),
+            Some(word) => IDEMPOTENT.contains(&word),
+        }
+    })
+}
+
+/// The statements that only read from the current space. `USE` and
+/// `SHOW` are not among them since they reach beyond it, and neither are
+/// `EXPLAIN` and `PROFILE`, which run the statement they wrap
+const READ_ONLY: &[&str] = &[
+    "MATCH", "OPTIONAL", "UNWIND", "WITH", "RETURN", "GO", "FETCH", "LOOKUP", "FIND", "GET",
+    "YIELD", "ORDER", "LIMIT", "GROUP",
+];
+
+/// Whether `query` consists only of statements that read from the current
+/// space, like `MATCH`, `GO`, `FETCH` and `LOOKUP`, possibly piped into
+/// `YIELD`, `ORDER BY` or `LIMIT`. Queries with comments or with strings
+/// that are not closed are not read-only, since graphd might split them
+/// into statements differently than we do
+pub fn is_read_only(query: &str) -> bool {
+    let (chars, closed) = unquoted(query);
+    // `--` stopped being a comment in 3.0; it is an edge in `MATCH`
+    let comment = chars.iter().any(|(_, c)| *c == '#')
+        || chars.windows(2).any(|w| {
+            let ((i, a), (j, b)) = (w[0], w[1]);
+            j == i + 1 && a == '/' && (b == '/' || b == '*')
+        });
+    if !closed || comment {
+        return false;
+    }
+    let mut statements = 0;
+    let read_only = clauses(query).into_iter().all(|clause| {
+        match clause_words(clause).as_ref().map(|words| words.first()) {
+            Some(None) => true,
+            Some(Some(word)) => {
+                statements += 1;
+                READ_ONLY.contains(&word.as_str())
+            }
+            None => false,
+        }
+    });
+    read_only && statements > 0
+}
+
+/// The clauses that openCypher statements start with
+const OPENCYPHER: &[&str] = &["MATCH", "OPTIONAL", "UNWIND", "WITH", "RETURN"];
+
+/// Whether the last statement of `query` is an openCypher statement like
+/// `MATCH ... RETURN`. Unlike native nGQL statements, such statements can
+/// not be piped into `YIELD`, `ORDER BY` or `LIMIT`
+pub fn is_opencypher(query: &str) -> bool {
+    let (chars, _) = unquoted(query);
+    let mut start = 0;
+    let mut statements = vec![];
+    for (i, c) in chars {
+        if c == ';' {
+            statements.push(&query[start..i]);
+            start = i + 1;
+        }
+    }
+    statements.push(&query[start..]);
+    statements
+        .into_iter()
+        .rev()
+        .find_map(|statement| clause_words(statement)?.into_iter().next())
+        .map_or(false, |word| OPENCYPHER.contains(&word.as_str()))
+}
+
+/// The words of `clause` in upper case, without the `$var =` of an
+/// assignment; `None` if the clause starts with a variable but does not
+/// assign to it
+fn clause_words(clause: &str) -> Option<Vec<String>> {
+    let mut words: Vec<String> = clause.split_whitespace().map(str::to_uppercase).collect();
+    // `$var = GO ...`
+    if words.first().map_or(false, |word| word.starts_with('$')) {
+        let pos = words.iter().position(|word| word == "=")?;
+        words.drain(..=pos);
+    }
+    Some(words)
+}
+
+/// The characters of `query` outside of strings and quoted names, with
+/// their byte offsets, and whether all strings and quoted names are
+/// closed
+fn unquoted(query: &str) -> (Vec<(usize, char)>, bool) {
+    let mut chars = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in query.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' | '`' => quote = Some(c),
+                c => chars.push((i, c)),
+            },
+        }
+    }
+    (chars, quote.is_none())
+}
+
+/// The clauses of `query`, which are separated by `;` or by a pipe outside
+/// of strings, quoted names and brackets; list comprehensions like
+/// `[n IN nodes(p) | id(n)]` use a pipe, too
+fn clauses(query: &str) -> Vec<&str> {
+    let mut clauses = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    let (chars, _) = unquoted(query);
+    let mut chars = chars.into_iter().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            // `||` is a logical or
+            '|' if chars.peek().map(|(j, c)| (*j, *c)) == Some((i + 1, '|')) => {
+                chars.next();
+            }
+            ';' | '|' if depth == 0 => {
+                clauses.push(&query[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    clauses.push(&query[start..]);
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Identifier {
+        Identifier::new(name).unwrap()
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!("`Transfer`", ident("Transfer").to_string());
+        assert_eq!("`a b-c.d`", ident("a b-c.d").to_string());
+        assert_eq!("`match`", ident("match").to_string());
+        for name in ["", "a`b", "`); DROP SPACE x; (`", "a\nb", "a\0b"] {
+            assert_eq!(
+                Err(NgqlError::InvalidIdentifier(name.to_string())),
+                Identifier::new(name)
+            );
+        }
+    }
+
+    #[test]
+    fn string_literals() {
+        assert_eq!(r#""plain""#, quote("plain"));
+        assert_eq!(r#""""#, quote(""));
+        assert_eq!(r#""say \"hi\"""#, quote(r#"say "hi""#));
+        assert_eq!(r#""C:\\dir\\""#, quote(r"C:\dir\"));
+        assert_eq!(r#""\\\"""#, quote(r#"\""#));
+        assert_eq!(r#""a\nb\r\tc""#, quote("a\nb\r\tc"));
+        assert_eq!(r#""it's `x`""#, quote("it's `x`"));
+        assert_eq!(r#""\"; DROP SPACE x; \"""#, quote(r#""; DROP SPACE x; ""#));
+        assert_eq!("\"ünïcødé 🎉\"", quote("ünïcødé 🎉"));
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!("NULL", Literal::Null.to_string());
+        assert_eq!("true", Literal::Bool(true).to_string());
+        assert_eq!("-42", Literal::Int(-42).to_string());
+        assert_eq!("9223372036854775807", Literal::Int(i64::MAX).to_string());
+        assert_eq!("1.0", Literal::Float(1.0).to_string());
+        assert_eq!("0.5", Literal::Float(0.5).to_string());
+        assert_eq!("1e300", Literal::Float(1e300).to_string());
+        assert_eq!("NULL", Literal::Float(f64::NAN).to_string());
+        assert_eq!(
+            r#"[1, "a\"b", NULL, []]"#,
+            Literal::List(vec![
+                Literal::Int(1),
+                Literal::from("a\"b"),
+                Literal::Null,
+                Literal::List(vec![]),
+            ])
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn ddl() {
+        let space = CreateSpace {
+            name: ident("sgd1"),
+            partition_num: 1,
+            replica_factor: 3,
+            vid_type: VidType::FixedString(50),
+            comment: Some("a \"test\"".to_string()),
+        };
+        assert_eq!(
+            "CREATE SPACE IF NOT EXISTS `sgd1` (partition_num = 1, replica_factor = 3, \
+             vid_type = FIXED_STRING(50)) COMMENT = \"a \\\"test\\\"\"",
+            space.to_string()
+        );
+        assert_eq!(
+            "DROP SPACE IF EXISTS `sgd1`",
+            DropSpace(ident("sgd1")).to_string()
+        );
+
+        let tag = CreateSchema {
+            kind: ColType::Tag,
+            name: ident("Account"),
+            properties: vec![
+                PropertyDef {
+                    name: ident("id"),
+                    data_type: DataType::String,
+                    nullable: false,
+                    default: None,
+                    comment: None,
+                },
+                PropertyDef {
+                    name: ident("name"),
+                    data_type: DataType::String,
+                    nullable: true,
+                    default: Some(Literal::from("n\\a")),
+                    comment: Some("it's".to_string()),
+                },
+            ],
+            comment: None,
+        };
+        assert_eq!(
+            "CREATE TAG IF NOT EXISTS `Account`(`id` string NOT NULL, \
+             `name` string NULL DEFAULT \"n\\\\a\" COMMENT \"it's\")",
+            tag.to_string()
+        );
+
+        let index = CreateIndex {
+            kind: ColType::Edge,
+            name: ident("transfer_value"),
+            schema: ident("Transfer"),
+            fields: vec![
+                IndexField {
+                    name: ident("memo"),
+                    length: Some(10),
+                },
+                IndexField {
+                    name: ident("value"),
+                    length: None,
+                },
+            ],
+            comment: None,
+        };
+        assert_eq!(
+            "CREATE EDGE INDEX IF NOT EXISTS `transfer_value` ON `Transfer`(`memo`(10), `value`)",
+            index.to_string()
+        );
+    }
+
+    #[test]
+    fn writes() {
+        let insert = InsertVertices {
+            tag: ident("Account"),
+            properties: vec![ident("id"), ident("name")],
+            rows: vec![
+                VertexRow {
+                    vid: "a\"1".to_string(),
+                    values: vec![Literal::from("a\"1"), Literal::from("x\\")],
+                },
+                VertexRow {
+                    vid: "a2".to_string(),
+                    values: vec![Literal::from("a2"), Literal::Null],
+                },
+            ],
+        };
+        assert_eq!(
+            r#"USE `sgd1`; INSERT VERTEX `Account`(`id`, `name`) VALUES "a\"1":("a\"1", "x\\"), "a2":("a2", NULL);"#,
+            in_space(&ident("sgd1"), &insert)
+        );
+
+        let key = EdgeKey {
+            src: "a\"1".to_string(),
+            dst: "a2".to_string(),
+            rank: -1,
+        };
+        let insert = InsertEdges {
+            edge: ident("Transfer"),
+            properties: vec![ident("value")],
+            rows: vec![EdgeRow {
+                key: key.clone(),
+                values: vec![Literal::Int(5)],
+            }],
+        };
+        assert_eq!(
+            r#"INSERT EDGE `Transfer`(`value`) VALUES "a\"1"->"a2"@-1:(5)"#,
+            insert.to_string()
+        );
+
+        let upsert = UpsertVertex {
+            tag: ident("Account"),
+            vid: "a2".to_string(),
+            set: vec![(ident("name"), Literal::from("`x`"))],
+        };
+        assert_eq!(
+            r#"UPSERT VERTEX ON `Account` "a2" SET `name` = "`x`""#,
+            upsert.to_string()
+        );
+        let upsert = UpsertEdge {
+            edge: ident("Transfer"),
+            key: key.clone(),
+            set: vec![
+                (ident("value"), Literal::Int(6)),
+                (ident("memo"), Literal::Null),
+            ],
+        };
+        assert_eq!(
+            r#"UPSERT EDGE ON `Transfer` "a\"1"->"a2"@-1 SET `value` = 6, `memo` = NULL"#,
+            upsert.to_string()
+        );
+
+        let delete = DeleteVertices {
+            vids: vec!["a\\".to_string(), "b".to_string()],
+        };
+        assert_eq!(r#"DELETE VERTEX "a\\", "b""#, delete.to_string());
+        let delete = DeleteTags {
+            tags: vec![ident("Account")],
+            vids: vec!["a2".to_string()],
+        };
+        assert_eq!(r#"DELETE TAG `Account` FROM "a2""#, delete.to_string());
+        let delete = DeleteEdges {
+            edge: ident("Transfer"),
+            keys: vec![key],
+        };
+        assert_eq!(
+            r#"DELETE EDGE `Transfer` "a\"1"->"a2"@-1"#,
+            delete.to_string()
+        );
+    }
+
+    #[test]
+    fn reads() {
+        let fetch = FetchVertices {
+            tags: vec![],
+            vids: vec!["a\"1".to_string()],
+            yields: vec!["properties(vertex)".to_string()],
+        };
+        assert_eq!(
+            r#"FETCH PROP ON * "a\"1" YIELD properties(vertex)"#,
+            fetch.to_string()
+        );
+        let fetch = FetchEdges {
+            edge: ident("Transfer"),
+            keys: vec![EdgeKey {
+                src: "a".to_string(),
+                dst: "b".to_string(),
+                rank: 0,
+            }],
+            yields: vec![],
+        };
+        assert_eq!(r#"FETCH PROP ON `Transfer` "a"->"b"@0"#, fetch.to_string());
+
+        let go = Go {
+            min_steps: Some(1),
+            max_steps: 3,
+            from: vec!["a".to_string(), "b\n".to_string()],
+            over: vec![ident("Transfer")],
+            direction: Direction::Incoming,
+            filter: Some(format!(
+                "properties(edge).{} > {}",
+                ident("value"),
+                Literal::Int(5)
+            )),
+            yields: vec!["dst(edge) AS id".to_string()],
+        };
+        assert_eq!(
+            r#"GO 1 TO 3 STEPS FROM "a", "b\n" OVER `Transfer` REVERSELY WHERE properties(edge).`value` > 5 YIELD dst(edge) AS id"#,
+            go.to_string()
+        );
+
+        let find = FindPath {
+            kind: PathKind::NoLoop,
+            from: vec!["a".to_string()],
+            to: vec!["b".to_string()],
+            over: vec![ident("Transfer")],
+            direction: Direction::Both,
+            max_steps: 4,
+            alias: "p".to_string(),
+        };
+        assert_eq!(
+            r#"FIND NOLOOP PATH FROM "a" TO "b" OVER `Transfer` BIDIRECT UPTO 4 STEPS YIELD path AS p"#,
+            find.to_string()
+        );
+
+        let m = Match {
+            pattern: format!("(v:{})-[e]->(w)", ident("Account")),
+            filter: Some(format!("id(v) == {}", Literal::from("a\"1"))),
+            returns: vec!["e".to_string(), "w".to_string()],
+        };
+        assert_eq!(
+            r#"MATCH (v:`Account`)-[e]->(w) WHERE id(v) == "a\"1" RETURN e, w"#,
+            m.to_string()
+        );
+        assert_eq!("USE `sgd1`", Use(ident("sgd1")).to_string());
+    }
+
+    #[test]
+    fn idempotent() {
+        assert!(is_idempotent("USE `sgd1`; SHOW TAGS;"));
+        assert!(is_idempotent(
+            "LOOKUP ON `Account` YIELD id(vertex) AS id | DELETE VERTEX $-.id"
+        ));
+        assert!(is_idempotent(
+            "$a = GO FROM \"a\" OVER `Transfer` WHERE $$.x > 1 || $$.y < 2 YIELD dst(edge)"
+        ));
+        assert!(is_idempotent(
+            "CREATE TAG IF NOT EXISTS `Account`(`id` string)"
+        ));
+        assert!(!is_idempotent("CREATE TAG `Account`(`id` string)"));
+        assert!(!is_idempotent(
+            "USE `sgd1`; UPDATE VERTEX ON `Account` \"a\" SET `value` = `value` + 1"
+        ));
+        // A `;` inside a string does not start a new statement
+        assert!(is_idempotent(
+            "INSERT VERTEX `Account`(`memo`) VALUES \"a\":(\"x; UPDATE\")"
+        ));
+        assert!(is_idempotent(
+            "FIND SHORTEST PATH FROM \"a\" TO \"b\" OVER * UPTO 3 STEPS YIELD path AS p \
+             | YIELD [n IN nodes($-.p) | id(n)] AS vids"
+        ));
+    }
+
+    #[test]
+    fn read_only() {
+        assert!(is_read_only(
+            "MATCH (v:`Account`)-[e:`Transfer`]->(w) WHERE id(v) == \"a\" RETURN w LIMIT 10;"
+        ));
+        assert!(is_read_only(
+            "GO FROM \"a\" OVER `Transfer` YIELD dst(edge) AS id | FETCH PROP ON `Account` $-.id \
+             YIELD properties(vertex) | LIMIT 5"
+        ));
+        assert!(is_read_only(
+            "LOOKUP ON `Account` WHERE `Account`.`name` == \"x; DROP SPACE y\" YIELD id(vertex)"
+        ));
+        assert!(is_read_only("MATCH (v)--(w) WHERE id(v) == \"a\" RETURN w"));
+        assert!(is_read_only(
+            "$a = GO FROM \"a\" OVER * YIELD dst(edge) AS d; GO FROM $a.d OVER * YIELD dst(edge)"
+        ));
+
+        for query in [
+            "",
+            " ; ",
+            "USE `other`; MATCH (v) RETURN v LIMIT 1",
+            "SHOW SPACES",
+            "PROFILE GO FROM \"a\" OVER *",
+            "GO FROM \"a\" OVER * YIELD dst(edge) AS d | DELETE VERTEX $-.d",
+            "FETCH PROP ON * \"a\"; DROP SPACE `sgd1`",
+            "INSERT VERTEX `Account`(`id`) VALUES \"a\":(\"a\")",
+            "UPDATE VERTEX ON `Account` \"a\" SET `id` = \"b\"",
+            "$a = DELETE VERTEX \"a\"",
+            // Comments and unclosed strings could hide statements from us
+            "GO FROM \"a\" OVER * /* ' */ ; DROP SPACE `x`; /* ' */",
+            "GO FROM \"a\" OVER * # '\n; DROP SPACE `x`",
+            "GO FROM \"a\" OVER * // '\n; DROP SPACE `x`",
+            "FETCH PROP ON * \"a",
+        ] {
+            assert!(!is_read_only(query), "{}", query);
+        }
+    }
+
+    #[test]
+    fn opencypher() {
+        assert!(is_opencypher("MATCH (v)-[e]->(w) RETURN w LIMIT 10;"));
+        assert!(is_opencypher(
+            "GO FROM \"a\" OVER * YIELD dst(edge); OPTIONAL MATCH (v) RETURN v"
+        ));
+        assert!(!is_opencypher(
+            "MATCH (v) RETURN v; GO FROM \"a\" OVER * YIELD dst(edge) | LIMIT 3"
+        ));
+        assert!(!is_opencypher(
+            "LOOKUP ON `Account` WHERE `Account`.`name` == \"MATCH; x\" YIELD id(vertex)"
+        ));
+        assert!(!is_opencypher(
+            "$a = GO FROM \"a\" OVER * YIELD dst(edge) AS d"
+        ));
+    }
+}
@@ -4,10 +4,17 @@
  * attached with Common Clause Condition 1.0, found in the LICENSES directory.
  */
 
+use crate::graph_client::nebula_url::{NebulaUrl, UrlError};
+use crate::graph_client::ngql::Identifier;
+use crate::graph_client::tls::TlsConfig;
+
 #[derive(Debug, Default, Clone)]
 pub struct PoolConfig {
-    /// connection timeout in ms
+    /// connection timeout in ms, also how long to wait for a connection when
+    /// the pool is exhausted; 0 means no timeout
     pub timeout: u32,
+    /// how long in ms a connection can be idle before it is closed; 0 means
+    /// that idle connections are kept open
     pub idle_time: u32,
     /// max limit count of connections in pool
     pub max_connection_pool_size: u32,
@@ -19,6 +26,18 @@ pub struct PoolConfig {
     pub username: String,
     /// password of user
     pub password: String,
+    /// connect to graph server with TLS if set
+    pub tls: Option<TlsConfig>,
+    /// how long in ms to wait for an answer from graph server; 0 means the
+    /// default of the transport, 5s
+    pub read_timeout: u32,
+    /// size of the buffer that answers are read into; 0 means the default
+    /// of the transport, 1KiB
+    pub buf_size: usize,
+    /// max size of an answer; 0 means the default of the transport, 4KiB
+    pub max_buf_size: usize,
+    /// the space that new sessions use
+    pub space: Option<Identifier>,
 }
 
 impl PoolConfig {
@@ -27,22 +46,11 @@ impl PoolConfig {
         Self::default()
     }
 
+    /// The configuration for a url like `root:nebula@graphd:9669/`; see
+    /// `NebulaUrl` for what the url can contain
     #[inline]
-    pub fn new_conf(nebula_url: &str) -> Self{
-        let v:Vec<&str> = nebula_url.split('@').collect();
-        let v2:Vec<&str> = v[1].split('/').collect();
-        let add = String::from(v2[0]);
-        let v3:Vec<&str> = v[0].split(':').collect();
-        let username = String::from(v3[0]);
-        let password = String::from(v3[1]);
-
-        let mut conf = PoolConfig::new();
-        conf.min_connection_pool_size(2)
-            .max_connection_pool_size(10)
-            .address(add)
-            .set_username(username)
-            .set_password(password);
-        conf
+    pub fn new_conf(nebula_url: &str) -> Result<Self, UrlError> {
+        Ok(nebula_url.parse::<NebulaUrl>()?.into_pool_config())
     }
 
     #[inline]
@@ -93,4 +101,34 @@ impl PoolConfig {
         // println!("{:?}", self.addresses.clone());
         self
     }
+
+    #[inline]
+    pub fn tls(&mut self, tls: TlsConfig) -> &mut Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    #[inline]
+    pub fn read_timeout(&mut self, read_timeout: u32) -> &mut Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    #[inline]
+    pub fn buf_size(&mut self, size: usize) -> &mut Self {
+        self.buf_size = size;
+        self
+    }
+
+    #[inline]
+    pub fn max_buf_size(&mut self, size: usize) -> &mut Self {
+        self.max_buf_size = size;
+        self
+    }
+
+    #[inline]
+    pub fn space(&mut self, space: Identifier) -> &mut Self {
+        self.space = Some(space);
+        self
+    }
 }
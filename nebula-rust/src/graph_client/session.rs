@@ -5,18 +5,134 @@
  */
 
 use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::graph_client::connection::Connection;
+use common::types::ErrorCode;
+use futures::future::{BoxFuture, FutureExt};
+use graph_node::util::backoff::ExponentialBackoff;
+use tokio::sync::Mutex;
+
+use crate::graph_client::connection::{check_json, check_response, json_error_code, Connection, NebulaError, SCHEMA_POLL_INTERVAL, SCHEMA_TIMEOUT};
 use crate::graph_client::connection_pool::ConnectionPool_nebula;
+use crate::graph_client::error::is_session_lost;
 use crate::graph_client::nebula_schema::Tag;
 use crate::graph_client::nebula_schema::ColType;
 use crate::graph_client::nebula_schema::InsertTagQuery;
 use crate::graph_client::nebula_schema::InsertEdgeQueryWithRank;
-
-pub struct Session<'a> {
+use crate::graph_client::nebula_schema::{batch_insert_edges, batch_insert_tags, DEFAULT_MAX_STATEMENT_SIZE};
+use crate::graph_client::ngql::{in_space, is_idempotent, CreateIndex, CreateSchema, CreateSpace, Identifier, IndexField, Literal, NgqlError, Use, VidType};
+use crate::value::ResultSet;
+
+/// How often a statement is sent again after the connection was lost
+const MAX_RETRIES: u64 = 5;
+/// How long to wait before sending a statement again the first time; the
+/// wait doubles with every retry up to `RETRY_CEILING`
+const RETRY_BASE: Duration = Duration::from_millis(100);
+const RETRY_CEILING: Duration = Duration::from_secs(5);
+
+/// The connection of a session and the session in graphd, which are both
+/// replaced when the connection is lost
+struct Link {
     session_id: i64,
     conn: Connection,
-    pool: &'a ConnectionPool_nebula,
+    /// The space that graphd last reported as the current one, to switch
+    /// to it again after reconnecting
+    space: Option<String>,
+}
+
+/// An answer of graphd to a query, in one of the formats it can answer in
+trait Answer: Sized {
+    fn send<'c>(
+        conn: &'c Connection,
+        session_id: i64,
+        query: &'c str,
+    ) -> BoxFuture<'c, std::result::Result<Self, NebulaError>>;
+
+    fn error_code(&self) -> ErrorCode;
+
+    /// The current space of the session after the query
+    fn space_name(&self) -> Option<&[u8]>;
+}
+
+impl Answer for graph::types::ExecutionResponse {
+    fn send<'c>(
+        conn: &'c Connection,
+        session_id: i64,
+        query: &'c str,
+    ) -> BoxFuture<'c, std::result::Result<Self, NebulaError>> {
+        conn.execute(session_id, query).boxed()
+    }
+
+    fn error_code(&self) -> ErrorCode {
+        self.error_code
+    }
+
+    fn space_name(&self) -> Option<&[u8]> {
+        self.space_name.as_deref()
+    }
+}
+
+impl Answer for serde_json::Value {
+    fn send<'c>(
+        conn: &'c Connection,
+        session_id: i64,
+        query: &'c str,
+    ) -> BoxFuture<'c, std::result::Result<Self, NebulaError>> {
+        conn.execute_json(session_id, query).boxed()
+    }
+
+    fn error_code(&self) -> ErrorCode {
+        json_error_code(self)
+    }
+
+    fn space_name(&self) -> Option<&[u8]> {
+        self.pointer("/results/0/spaceName")
+            .and_then(serde_json::Value::as_str)
+            .map(str::as_bytes)
+    }
+}
+
+/// The pool that a session gives its connection back to
+pub enum PoolRef<'a> {
+    Borrowed(&'a ConnectionPool_nebula),
+    Shared(Arc<ConnectionPool_nebula>),
+}
+
+impl Deref for PoolRef<'_> {
+    type Target = ConnectionPool_nebula;
+
+    fn deref(&self) -> &ConnectionPool_nebula {
+        match self {
+            PoolRef::Borrowed(pool) => pool,
+            PoolRef::Shared(pool) => pool,
+        }
+    }
+}
+
+impl<'a> From<&'a ConnectionPool_nebula> for PoolRef<'a> {
+    fn from(pool: &'a ConnectionPool_nebula) -> Self {
+        PoolRef::Borrowed(pool)
+    }
+}
+
+impl From<Arc<ConnectionPool_nebula>> for PoolRef<'static> {
+    fn from(pool: Arc<ConnectionPool_nebula>) -> Self {
+        PoolRef::Shared(pool)
+    }
+}
+
+/// A session that keeps its pool alive, so that it can be moved into
+/// spawned tasks. See `ConnectionPool_nebula::get_owned_session`
+pub type OwnedSession = Session<'static>;
+
+/// A session in graphd on a connection of the pool. Dropping the session
+/// gives the connection back to the pool, which signs the session out
+/// before it uses the connection again
+pub struct Session<'a> {
+    link: Mutex<Link>,
+    pool: PoolRef<'a>,
     username: String,
     password: String,
     // empty means not a named timezone
@@ -25,13 +141,16 @@ pub struct Session<'a> {
     offset_secs: i32,
     // Keep connection if true
     retry_connect: bool,
+    /// Close the connection when the session is dropped instead of giving
+    /// it back to the pool
+    discarded: bool,
 }
 
 impl<'a> Session<'a> {
     pub fn new(
         session_id: i64,
         conn: Connection,
-        pool: &'a ConnectionPool_nebula,
+        pool: impl Into<PoolRef<'a>>,
         username: String,
         password: String,
         time_zone_name: String,
@@ -39,33 +158,133 @@ impl<'a> Session<'a> {
         retry_connect: bool,
     ) -> Self {
         Session {
-            session_id: session_id,
-            conn: conn,
-            pool: pool,
+            link: Mutex::new(Link {
+                session_id,
+                conn,
+                space: None,
+            }),
+            pool: pool.into(),
             username: username,
             password: password,
             time_zone_name: time_zone_name,
             offset_secs: offset_secs,
             retry_connect: retry_connect,
+            discarded: false,
         }
     }
 
+    /// Close the connection of the session instead of giving it back to the
+    /// pool. Use this when a query was abandoned while graphd still works
+    /// on it, since graphd would answer it on the connection later
+    pub fn discard(mut self) {
+        self.discarded = true;
+    }
+
     /// sign out the session
     #[inline]
-    pub async fn signout(&self) -> std::result::Result<(), common::types::ErrorCode> {
-        self.conn.signout(self.session_id).await
+    pub async fn signout(&self) -> std::result::Result<(), NebulaError> {
+        let link = self.link.lock().await;
+        link.conn.signout(link.session_id).await
     }
 
     /// Execute the query in current session
     /// The returned error of `Result` only means the request/response status
     /// The error from Nebula Graph is still in `error_code` field in response, so you need check it
     /// to known wether the query execute succeeded
-    #[inline]
+    ///
+    /// When the connection broke or graphd forgot the session, and the
+    /// session was created with `retry_connect`, it logs in again on
+    /// another connection of the pool and switches back to its space. The
+    /// query is then sent again with backoff if graphd did not run it or if
+    /// it is idempotent (see `ngql::is_idempotent`)
     pub async fn execute(
         &self,
         query: &str,
-    ) -> std::result::Result<graph::types::ExecutionResponse, common::types::ErrorCode> {
-        self.conn.execute(self.session_id, query).await
+    ) -> std::result::Result<graph::types::ExecutionResponse, NebulaError> {
+        self.send(query).await
+    }
+
+    /// Execute the query in current session with Nebula Graph answering in
+    /// JSON. Like with `execute`, the error from Nebula Graph is still in
+    /// the `errors` of the answer, and broken connections are replaced
+    #[inline]
+    pub async fn execute_json(
+        &self,
+        query: &str,
+    ) -> std::result::Result<serde_json::Value, NebulaError> {
+        self.send(query).await
+    }
+
+    /// Execute the query like `execute_json`, but turn the errors from
+    /// Nebula Graph into `NebulaError::Server`
+    #[inline]
+    pub async fn execute_json_checked(
+        &self,
+        query: &str,
+    ) -> std::result::Result<serde_json::Value, NebulaError> {
+        let json = self.execute_json(query).await?;
+        check_json(json, query)
+    }
+
+    async fn send<T: Answer>(&self, query: &str) -> std::result::Result<T, NebulaError> {
+        let mut link = self.link.lock().await;
+        let mut backoff = ExponentialBackoff::new(RETRY_BASE, RETRY_CEILING);
+        loop {
+            let result = T::send(&link.conn, link.session_id, query).await;
+            let lost = match &result {
+                Ok(answer) => is_session_lost(answer.error_code()),
+                Err(e) => e.is_connection_lost(),
+            };
+            if !lost || !self.retry_connect {
+                let space = result.as_ref().ok().and_then(|answer| answer.space_name());
+                if let Some(space) = space.filter(|space| !space.is_empty()) {
+                    link.space = Some(String::from_utf8_lossy(space).into_owned());
+                }
+                return result;
+            }
+
+            // An answer means graphd rejected the query without running it
+            let resend = result.is_ok() || is_idempotent(query);
+            if !resend || backoff.attempt >= MAX_RETRIES {
+                // Leave a working session for the next query
+                self.reconnect(&mut link).await.ok();
+                return result;
+            }
+            backoff.sleep_async().await;
+            if let Err(e) = self.reconnect(&mut link).await {
+                if !e.is_retryable() {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Log in again on another connection of the pool, close the broken
+    /// connection, and switch to the space that the session was using
+    async fn reconnect(&self, link: &mut Link) -> std::result::Result<(), NebulaError> {
+        let (conn, session_id, _) = self.pool.login(&self.username, &self.password).await?;
+        let broken = std::mem::replace(&mut link.conn, conn);
+        self.pool.close(broken);
+        let old_session_id = std::mem::replace(&mut link.session_id, session_id);
+        // graphd still knows the old session if only the connection broke
+        link.conn.signout(old_session_id).await.ok();
+        if let Some(space) = &link.space {
+            if let Ok(query) = Self::use_space(space) {
+                link.conn.execute_checked(link.session_id, &query).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute the query in current session and read its result. Errors
+    /// from Nebula Graph are returned as `NebulaError::Server`
+    #[inline]
+    pub async fn execute_checked(
+        &self,
+        query: &str,
+    ) -> std::result::Result<ResultSet, NebulaError> {
+        let resp = self.execute(query).await?;
+        check_response(resp, query)
     }
 
     /// Get the time zone name
@@ -80,293 +299,188 @@ impl<'a> Session<'a> {
         self.offset_secs
     }
     #[inline]
-    pub async fn show_spaces(&self){
+    pub async fn show_spaces(&self) -> std::result::Result<(), NebulaError>{
         let query = "show spaces;";
-        let resp = self.execute(query).await.unwrap();
+        let resp = self.execute(query).await?;
         resp.show_data();
+        Ok(())
     }
 
     #[inline]
-    pub fn get_create_space_query(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str) -> String{
-        let mut query = String::from("CREATE SPACE IF NOT EXISTS `");
-        query += space_name;
-        query += "` (partition_num = ";
-        query += partition_num.to_string().as_str();
-        query += ", replica_factor = ";
-        query += replica_factor.to_string().as_str();
-        query += ", vid_type = ";
-        if is_fixed_string{
-            query += "FIXED_STRING(";
-            query += fixed_string_len.to_string().as_str();
-            query += "))";
-        }else{
-            query += "INT64)";
-        }
-        if comment!=""{
-            query += " COMMENT = \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-        query
+    pub fn get_create_space_query(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str) -> std::result::Result<String, NgqlError>{
+        let query = CreateSpace{
+            name: Identifier::new(space_name)?,
+            partition_num: partition_num as u32,
+            replica_factor: replica_factor as u32,
+            vid_type: if is_fixed_string { VidType::FixedString(fixed_string_len as u32) } else { VidType::Int64 },
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        Ok(format!("{};", query))
     }
     #[inline]
     // CREATE SPACE `testGraph` (partition_num = 15, replica_factor = 1, vid_type = FIXED_STRING(50)) COMMENT = "this is a graph for test"
-    pub async fn create_space(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str){
-        let query = self.get_create_space_query(space_name, partition_num, replica_factor, is_fixed_string, fixed_string_len, comment);
-        // println!("{}", query);
-        let _resp = self.execute(query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn create_space(&self, space_name: &str, partition_num: u8, replica_factor: u8, is_fixed_string: bool, fixed_string_len: u8, comment: &str) -> std::result::Result<(), NebulaError>{
+        let query = self.get_create_space_query(space_name, partition_num, replica_factor, is_fixed_string, fixed_string_len, comment)?;
+        self.execute_checked(query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub fn get_create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>) -> String{
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; CREATE ";
-        query += col_type.to_string().as_str();
-        query += " IF NOT EXISTS `";
-        query += tag_name;
-        query += "` (";
-        for i in 0..tags.len() {
-            query += tags[i].to_string().as_str();
-            if i!=tags.len()-1{
-                query += ",";
-            }
-        }
-        query += ")";
-        if comment!="".to_string(){
-            query += " COMMENT = \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-        query
+    pub fn get_create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>) -> std::result::Result<String, NgqlError>{
+        let query = CreateSchema{
+            kind: col_type,
+            name: Identifier::new(tag_name)?,
+            properties: tags.iter().map(Tag::to_property_def).collect::<std::result::Result<_, _>>()?,
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        Ok(in_space(&Identifier::new(space_name)?, &query))
     }
 
     #[inline]
-    pub async fn create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>){
-
-        let query = self.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags);
-        //println!("{}", query);
-
-        let _resp = self.execute(query.as_str()).await.unwrap();
-
-        //println!("{:?}", _resp);
+    pub async fn create_tag_or_edge(&self, space_name: &str, col_type: ColType, tag_name: &str, comment: &str, tags: Vec<Tag>) -> std::result::Result<(), NebulaError>{
+        let query = self.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags)?;
+        self.execute_checked(query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
     // INSERT VERTEX t2 (name, age) VALUES "11":("n1", 12);
-    pub async fn insert_tag(&self, space_name: &str, tag_name: &str, kv: HashMap<String, String>, vid: &str){
-
-        if self.find_tag_or_edge(space_name, tag_name, ColType::Tag).await == false{
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-        }
-
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT VERTEX IF NOT EXISTS ";
-        query += tag_name;
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            keys += k.as_str();
-            values += v.as_str();
-        }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += vid;
-        query += "\":";
-        query += values.as_str();
-        query += ";";
-
-        // println!("{}", query);
-        let _resp = self.execute(query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn insert_tag(&self, space_name: &str, tag_name: &str, kv: HashMap<Identifier, Literal>, vid: &str) -> std::result::Result<(), NebulaError>{
+
+        self.wait_for_tag_or_edge(space_name, tag_name, ColType::Tag, SCHEMA_TIMEOUT).await?;
+
+        let query = InsertTagQuery::new(
+            Identifier::new(space_name)?,
+            Identifier::new(tag_name)?,
+            kv,
+            vid.to_string(),
+        );
+        self.execute_checked(query.to_string().as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub async fn insert_tags(&self, insert_tag_queries: Vec<InsertTagQuery>){
-        for query in insert_tag_queries{
-            self.insert_tag(query.space_name.as_str(), query.tag_name.as_str(), query.kv, query.vid.as_str()).await;
+    pub async fn insert_tags(&self, insert_tag_queries: Vec<InsertTagQuery>) -> std::result::Result<(), NebulaError>{
+        for query in batch_insert_tags(&insert_tag_queries, DEFAULT_MAX_STATEMENT_SIZE){
+            self.execute_checked(query.as_str()).await?;
         }
+        Ok(())
     }
 
     #[inline]
-    pub async fn insert_edges(&self, insert_edge_queries: Vec<InsertEdgeQueryWithRank>){
-        for query in insert_edge_queries{
-            self.insert_edge_with_rank(query.space_name.as_str(), query.edge_name.as_str(), query.kv, query.from_vertex.as_str(), query.to_vertex.as_str(), query.rank).await;
+    pub async fn insert_edges(&self, insert_edge_queries: Vec<InsertEdgeQueryWithRank>) -> std::result::Result<(), NebulaError>{
+        for query in batch_insert_edges(&insert_edge_queries, DEFAULT_MAX_STATEMENT_SIZE){
+            self.execute_checked(query.as_str()).await?;
         }
+        Ok(())
     }
 
     #[inline]
     // INSERT EDGE e2 (name, age) VALUES "11"->"13":("n1", 12);
-    pub async fn insert_edge(&self, space_name: &str, edge_name: &str, kv: HashMap<String, String>, from_vertex: &str, to_vertex: &str){
-
-        if self.find_tag_or_edge(space_name, edge_name, ColType::Edge).await == false{
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-        }
+    pub async fn insert_edge(&self, space_name: &str, edge_name: &str, kv: HashMap<Identifier, Literal>, from_vertex: &str, to_vertex: &str) -> std::result::Result<(), NebulaError>{
 
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT EDGE IF NOT EXISTS ";
-        query += edge_name;
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            keys += k.as_str();
-            values += v.as_str();
-        }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += from_vertex;
-        query += "\" -> \"";
-        query += to_vertex;
-        query += "\":";
-        query += values.as_str();
-        query += ";";
+        self.wait_for_tag_or_edge(space_name, edge_name, ColType::Edge, SCHEMA_TIMEOUT).await?;
 
-        // println!("{}", query);
-        let _resp = self.execute(query.as_str()).await.unwrap();
+        self.insert_edge_with_rank(space_name, edge_name, kv, from_vertex, to_vertex, 0).await
     }
 
 
     #[inline]
     // INSERT EDGE e2 (name, age) VALUES "11"->"13"@1:("n1", 12);
-    pub async fn insert_edge_with_rank(&self, space_name: &str, edge_name: &str, kv: HashMap<String, String>, from_vertex: &str, to_vertex: &str, rank: i32){
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "INSERT EDGE IF NOT EXISTS ";
-        query += edge_name;
-        query += " ";
-        let mut keys = String::from("(");
-        let mut values = String::from("(");
-        for (k,v) in kv{
-            if keys.len()!=1{
-                keys += ",";
-                values += ",";
-            }
-            keys += k.as_str();
-            values += v.as_str();
-        }
-        keys += ")";
-        values += ")";
-        query += keys.as_str();
-        query += " VALUES \"";
-        query += from_vertex;
-        query += "\" -> \"";
-        query += to_vertex;
-        query += "\"@";
-        query += rank.to_string().as_str();
-        query += ":";
-        query += values.as_str();
-        query += ";";
-
-        // println!("{}", query);
-        let _resp = self.execute(query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn insert_edge_with_rank(&self, space_name: &str, edge_name: &str, kv: HashMap<Identifier, Literal>, from_vertex: &str, to_vertex: &str, rank: i64) -> std::result::Result<(), NebulaError>{
+        let query = InsertEdgeQueryWithRank::new(
+            Identifier::new(space_name)?,
+            Identifier::new(edge_name)?,
+            kv,
+            from_vertex.to_string(),
+            to_vertex.to_string(),
+            rank,
+        );
+        self.execute_checked(query.to_string().as_str()).await?;
+        Ok(())
     }
 
     #[inline]
     // CREATE TAG INDEX `index_tag` on `stu`      (`name`(10), `age`) COMMENT "this is an index for tag"
-    pub async fn create_index(&self, space_name: &str, index_type: ColType, tag_or_edge_name: &str, index_name: &str, comment: &str, indexed_properties: HashMap<String, u8>){
-        let mut query = String::from("use ");
-        query += space_name;
-        query += "; ";
-        query += "CREATE ";
-        query += index_type.to_string().as_str();
-        query += " INDEX `";
-        query += index_name;
-        query += "` on `";
-        query += tag_or_edge_name;
-        query += "`(";
-
-        let mut properties = String::from("");
-        for (k,v) in indexed_properties {
-            if properties.len()!=0{
-                properties += ",";
-            }
-            let mut property = String::from("`");
-            property += k.as_str();
-            property += "`";
-            if v==0 {
-            }else{
-                property += "(";
-                property += v.to_string().as_str();
-                property += ")"
-            }
-            properties += property.as_str();
-        }
-
-        query += properties.as_str();
-        query += ") ";
-        if comment!=""{
-            query += "COMMENT \"";
-            query += comment;
-            query += "\"";
-        }
-        query += ";";
-
-        // println!("{}", query);
-        let _resp = self.execute(query.as_str()).await.unwrap();
-        // println!("{:?}", _resp);
+    pub async fn create_index(&self, space_name: &str, index_type: ColType, tag_or_edge_name: &str, index_name: &str, comment: &str, indexed_properties: HashMap<String, u8>) -> std::result::Result<(), NebulaError>{
+        let mut fields: Vec<IndexField> = indexed_properties
+            .iter()
+            .map(|(name, len)| Ok(IndexField{
+                name: Identifier::new(name)?,
+                // a length of 0 means that the property is not a string
+                length: if *len == 0 { None } else { Some(*len as u16) },
+            }))
+            .collect::<std::result::Result<_, NgqlError>>()?;
+        fields.sort_by(|a, b| a.name.cmp(&b.name));
+        let query = CreateIndex{
+            kind: index_type,
+            name: Identifier::new(index_name)?,
+            schema: Identifier::new(tag_or_edge_name)?,
+            fields,
+            comment: if comment.is_empty() { None } else { Some(comment.to_string()) },
+        };
+        let query = in_space(&Identifier::new(space_name)?, &query);
+        self.execute_checked(query.as_str()).await?;
+        Ok(())
     }
 
     #[inline]
-    pub async fn find_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType) -> bool{
-        let mut query = Self::use_space(space_name);
+    pub async fn find_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType) -> std::result::Result<bool, NebulaError>{
+        let mut query = Self::use_space(space_name)?;
         match col_type {
-            ColType::Edge => query += "show edges;",
-            ColType::Tag => query += "show tags;",
+            ColType::Edge => query += " SHOW EDGES;",
+            ColType::Tag => query += " SHOW TAGS;",
         }
-        let resp = self.execute(query.as_str()).await.unwrap();
-        // print!("{:?}", resp);
+        let resp = self.execute(query.as_str()).await?;
         let res = resp.get_sVal();
         match res {
             Some(tags) => {
                 for tag in tags{
                     if tag == tag_or_edge_name.to_string(){
-                        return true;
+                        return Ok(true);
                     }
                 }
             }
-            None => return false
+            None => return Ok(false)
+        }
+        Ok(false)
+    }
+
+    /// Wait until the tag or edge type is visible in the space since nebula
+    /// creates them asynchronously. Returns false if it is still not visible
+    /// after `timeout`, and an error if checking whether it is visible fails
+    pub async fn wait_for_tag_or_edge(&self, space_name: &str, tag_or_edge_name: &str, col_type: ColType, timeout: Duration) -> std::result::Result<bool, NebulaError>{
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.find_tag_or_edge(space_name, tag_or_edge_name, col_type).await? {
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            tokio::time::sleep(SCHEMA_POLL_INTERVAL).await;
         }
-        return false;
     }
 
     #[inline]
-    pub fn use_space(space_name: &str) -> String{
-        let mut line = String::from("use ");
-        line += space_name;
-        line += ";";
-        line
+    pub fn use_space(space_name: &str) -> std::result::Result<String, NgqlError>{
+        Ok(format!("{};", Use(Identifier::new(space_name)?)))
     }
 
 }
 
 impl<'a> Drop for Session<'a> {
-    /// Drop session will sign out the session in server
-    /// and give back connection to pool
+    /// Drop session will give back connection to pool without waiting for
+    /// the server; the pool signs out the session in server before the
+    /// connection is used again, and closes it if it is broken. The
+    /// connection of a discarded session is closed right away
     fn drop(&mut self) {
-        futures::executor::block_on(self.signout());
-        self.pool.give_back(std::mem::take(&mut self.conn));
+        let link = self.link.get_mut();
+        let conn = std::mem::take(&mut link.conn);
+        if self.discarded {
+            self.pool.discard(conn);
+        } else {
+            self.pool.give_back_session(conn, link.session_id);
+        }
     }
 }
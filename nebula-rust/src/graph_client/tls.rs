@@ -0,0 +1,289 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! TLS for the connections to graphd
+//!
+//! Connections talk to graphd through a `GraphStream`, which is either a
+//! plain TCP stream or a TLS stream on top of one, so that the same
+//! `AsyncTransport` works for both
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use rustls_pemfile::Item;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+
+/// How to secure the connections to graphd. The files are read for every
+/// new connection, so that renewed certificates are picked up without a
+/// restart
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// PEM file with the certificates of the CAs that graphd's certificate
+    /// must be signed by
+    pub ca_file: PathBuf,
+    /// PEM file with the client certificate chain, for graphd setups that
+    /// require clients to authenticate; needs `key_file`
+    #[serde(default)]
+    pub cert_file: Option<PathBuf>,
+    /// PEM file with the private key of the client certificate
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    /// The name that graphd's certificate must be issued for, if that is
+    /// not the host of the address that is connected to
+    #[serde(default)]
+    pub server_name: Option<String>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn certs(path: &Path) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(invalid(format!("no certificates in {}", path.display())));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn private_key(path: &Path) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::RSAKey(key)) | Some(Item::PKCS8Key(key)) | Some(Item::ECKey(key)) => {
+                return Ok(PrivateKey(key))
+            }
+            Some(_) => continue,
+            None => return Err(invalid(format!("no private key in {}", path.display()))),
+        }
+    }
+}
+
+/// The host of a `host:port` address
+fn host(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+impl TlsConfig {
+    /// The rustls configuration with the CAs and the client certificate.
+    /// Fails if a file can not be read or has no certificate or key
+    pub fn client_config(&self) -> io::Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        for cert in certs(&self.ca_file)? {
+            roots
+                .add(&cert)
+                .map_err(|e| invalid(format!("invalid CA in {}: {}", self.ca_file.display(), e)))?;
+        }
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        match (&self.cert_file, &self.key_file) {
+            (Some(cert_file), Some(key_file)) => builder
+                .with_single_cert(certs(cert_file)?, private_key(key_file)?)
+                .map_err(|e| invalid(format!("invalid client certificate: {}", e))),
+            (None, None) => Ok(builder.with_no_client_auth()),
+            _ => Err(invalid(
+                "cert_file and key_file must be set together".to_string(),
+            )),
+        }
+    }
+
+    fn server_name(&self, address: &str) -> io::Result<ServerName> {
+        let name = self.server_name.as_deref().unwrap_or_else(|| host(address));
+        ServerName::try_from(name)
+            .map_err(|_| invalid(format!("invalid TLS server name `{}`", name)))
+    }
+}
+
+/// A connection to graphd, with or without TLS
+pub enum GraphStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl GraphStream {
+    /// Connect to `address`, and secure the connection if `tls` is set
+    pub async fn connect(address: &str, tls: Option<&TlsConfig>) -> io::Result<GraphStream> {
+        match tls {
+            None => Ok(GraphStream::Plain(TcpStream::connect(address).await?)),
+            Some(tls) => {
+                let connector = TlsConnector::from(Arc::new(tls.client_config()?));
+                let server_name = tls.server_name(address)?;
+                let stream = TcpStream::connect(address).await?;
+                let stream = connector.connect(server_name, stream).await?;
+                Ok(GraphStream::Tls(Box::new(stream)))
+            }
+        }
+    }
+}
+
+impl AsyncRead for GraphStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GraphStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            GraphStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for GraphStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            GraphStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            GraphStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GraphStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            GraphStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GraphStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            GraphStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use common::types::ErrorCode;
+    use fbthrift::binary_protocol::{BinaryProtocolDeserializer, BinaryProtocolSerializer};
+    use fbthrift::{MessageType, ProtocolReader, ProtocolWriter, Serialize};
+    use graph::services::graph_service::ExecuteExn;
+    use graph::types::ExecutionResponse;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    use super::*;
+    use crate::graph_client::connection::Connection;
+
+    /// The certificates in `tests/tls` were made with `openssl`; the server
+    /// certificate is issued for `localhost` by the CA in `ca.pem`
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/tls")
+            .join(name)
+    }
+
+    fn tls(server_name: &str) -> TlsConfig {
+        TlsConfig {
+            ca_file: fixture("ca.pem"),
+            server_name: Some(server_name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// A graphd that only speaks TLS and answers every request with an
+    /// empty, successful `ExecutionResponse`
+    async fn mock_graphd() -> String {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                certs(&fixture("server.pem")).unwrap(),
+                private_key(&fixture("server.key")).unwrap(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut stream = match acceptor.accept(stream).await {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let mut buf = vec![0; 4096];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let mut des =
+                        BinaryProtocolDeserializer::<Bytes>::new(Bytes::from(buf[..n].to_vec()));
+                    let (_, _, seqid) = des.read_message_begin(|v| v.to_vec()).unwrap();
+
+                    let mut ser =
+                        BinaryProtocolSerializer::<BytesMut>::with_buffer(BytesMut::new());
+                    ser.write_message_begin("execute", MessageType::Reply, seqid);
+                    ExecuteExn::Success(ExecutionResponse {
+                        error_code: ErrorCode::SUCCEEDED,
+                        ..Default::default()
+                    })
+                    .write(&mut ser);
+                    ser.write_message_end();
+                    stream.write_all(&ser.finish()[..]).await.unwrap();
+                    stream.flush().await.unwrap();
+                }
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn execute_over_tls() {
+        let address = mock_graphd().await;
+
+        let conn = Connection::new_from_address_with_tls(&address, Some(&tls("localhost")))
+            .await
+            .unwrap();
+        let resp = conn.execute(1, "YIELD 1").await.unwrap();
+        assert_eq!(ErrorCode::SUCCEEDED, resp.error_code);
+
+        // The certificate is not issued for that name
+        assert!(
+            Connection::new_from_address_with_tls(&address, Some(&tls("graphd.example")))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn config() {
+        assert_eq!("graphd", host("graphd:9669"));
+        assert_eq!("::1", host("[::1]:9669"));
+
+        assert!(tls("localhost").client_config().is_ok());
+        let half = TlsConfig {
+            cert_file: Some(fixture("server.pem")),
+            ..tls("localhost")
+        };
+        assert!(half.client_config().is_err());
+        let missing = TlsConfig {
+            ca_file: fixture("missing.pem"),
+            ..tls("localhost")
+        };
+        assert!(missing.client_config().is_err());
+    }
+}
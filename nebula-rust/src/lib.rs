@@ -5,4 +5,6 @@
  */
 
 pub mod graph_client;
+pub mod meta_client;
+pub mod storage_client;
 pub mod value;
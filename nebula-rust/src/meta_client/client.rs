@@ -0,0 +1,497 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use common::types::{ErrorCode, HostAddr};
+use fbthrift::{BinaryProtocol, NonthrowingFunctionError};
+use fbthrift_transport::{tokio_io::transport::AsyncTransport, AsyncTransportConfiguration};
+use meta::client::{MetaService, MetaServiceImpl};
+use meta::types::{
+    AlterEdgeReq, AlterSchemaItem, AlterSchemaOp, AlterTagReq, ColumnDef, CreateEdgeReq,
+    CreateSpaceReq, CreateTagReq, DropEdgeReq, DropSpaceReq, DropTagReq, ExecResp,
+    GetPartsAllocReq, GetPartsAllocResp, GetSpaceReq, GetSpaceResp, HostStatus, ListEdgesReq,
+    ListEdgesResp, ListHostType, ListHostsReq, ListHostsResp, ListSpacesReq, ListSpacesResp,
+    ListTagsReq, ListTagsResp, SchemaProp, SpaceDesc, ID,
+};
+use tokio::net::TcpStream;
+
+use crate::graph_client::nebula_schema::ColType;
+use crate::graph_client::ngql::{CreateSchema, CreateSpace, Identifier};
+use crate::meta_client::transport_response_handler::MetaTransportResponseHandler;
+use crate::meta_client::types::{
+    column_def, property_infos, schema, string, vid_type, vid_type_def, HostInfo, HostRole,
+    MetaError, PartitionId, SchemaChange, SchemaInfo, SchemaVersion, SpaceId, SpaceInfo,
+};
+
+/// How often the meta service may tell us that another host became its
+/// leader before a request is given up
+const MAX_REDIRECTS: usize = 3;
+
+/// How often `wait_for_schema` asks for the schema
+const SCHEMA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type Client =
+    MetaServiceImpl<BinaryProtocol, AsyncTransport<TcpStream, MetaTransportResponseHandler>>;
+
+type Call<R> = Pin<Box<dyn Future<Output = Result<R, NonthrowingFunctionError>> + Send>>;
+
+/// The parts that all answers of the meta service have
+trait MetaResponse {
+    fn code(&self) -> ErrorCode;
+    fn leader(&self) -> &HostAddr;
+}
+
+macro_rules! meta_response {
+    ($($resp:ty),*) => {
+        $(impl MetaResponse for $resp {
+            fn code(&self) -> ErrorCode {
+                self.code
+            }
+            fn leader(&self) -> &HostAddr {
+                &self.leader
+            }
+        })*
+    };
+}
+
+meta_response!(
+    ExecResp,
+    GetPartsAllocResp,
+    GetSpaceResp,
+    ListSpacesResp,
+    ListTagsResp,
+    ListEdgesResp,
+    ListHostsResp
+);
+
+/// A client for the meta service that manages spaces, tags and edge types
+/// without going through nGQL. It connects to the first of its addresses
+/// that can be reached and follows the meta service when it names another
+/// host as its leader
+///
+/// Changes made here become visible to graphd only after it refreshed its
+/// schema cache, which happens with its heartbeat; readiness for nGQL
+/// still has to be checked through graphd
+pub struct MetaClient {
+    addresses: Vec<String>,
+    /// The address `client` is connected to
+    address: String,
+    client: Client,
+}
+
+impl MetaClient {
+    /// Connect to one of `addresses`, which are `host:port` of metad
+    pub async fn new(addresses: Vec<String>) -> Result<MetaClient, MetaError> {
+        let mut errors = vec![];
+        for address in &addresses {
+            match Self::connect(address).await {
+                Ok(client) => {
+                    return Ok(MetaClient {
+                        address: address.clone(),
+                        addresses,
+                        client,
+                    })
+                }
+                Err(e) => errors.push(format!("{}: {}", address, e)),
+            }
+        }
+        Err(MetaError::Connect(errors.join(", ")))
+    }
+
+    async fn connect(address: &str) -> std::io::Result<Client> {
+        let stream = TcpStream::connect(address).await?;
+        let transport = AsyncTransport::new(
+            stream,
+            AsyncTransportConfiguration::new(MetaTransportResponseHandler),
+        );
+        Ok(MetaServiceImpl::new(transport))
+    }
+
+    /// Connect to `leader`, or to the address after the current one if the
+    /// leader is not known
+    async fn reconnect(&mut self, leader: Option<&HostAddr>) -> Result<(), MetaError> {
+        let address = match leader {
+            Some(leader) if !leader.host.is_empty() => format!("{}:{}", leader.host, leader.port),
+            _ => {
+                let current = self
+                    .addresses
+                    .iter()
+                    .position(|address| address == &self.address)
+                    .unwrap_or(0);
+                self.addresses[(current + 1) % self.addresses.len()].clone()
+            }
+        };
+        self.client = Self::connect(&address)
+            .await
+            .map_err(|e| MetaError::Connect(format!("{}: {}", address, e)))?;
+        self.address = address;
+        Ok(())
+    }
+
+    /// Send the request that `call` makes until a host that leads the
+    /// meta service answers it
+    async fn call<R, F>(&mut self, call: F) -> Result<R, MetaError>
+    where
+        R: MetaResponse,
+        F: Fn(&Client) -> Call<R>,
+    {
+        let mut error = MetaError::Code(ErrorCode::E_LEADER_CHANGED);
+        for _ in 0..=MAX_REDIRECTS {
+            match call(&self.client).await {
+                Ok(resp) if resp.code() == ErrorCode::SUCCEEDED => return Ok(resp),
+                Ok(resp) if resp.code() == ErrorCode::E_LEADER_CHANGED => {
+                    let leader = resp.leader().clone();
+                    self.reconnect(Some(&leader)).await?;
+                }
+                Ok(resp) => return Err(MetaError::Code(resp.code())),
+                Err(e) => {
+                    error = MetaError::Rpc(e.to_string());
+                    self.reconnect(None).await?;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Create the space and return its id
+    pub async fn create_space(
+        &mut self,
+        space: &CreateSpace,
+        if_not_exists: bool,
+    ) -> Result<SpaceId, MetaError> {
+        let req = CreateSpaceReq {
+            properties: SpaceDesc {
+                space_name: space.name.as_str().as_bytes().to_vec(),
+                partition_num: space.partition_num as i32,
+                replica_factor: space.replica_factor as i32,
+                charset_name: b"utf8".to_vec(),
+                collate_name: b"utf8_bin".to_vec(),
+                vid_type: vid_type_def(space.vid_type),
+                comment: space.comment.as_ref().map(|c| c.as_bytes().to_vec()),
+                ..Default::default()
+            },
+            if_not_exists,
+        };
+        let resp = self.call(|client| client.createSpace(&req)).await?;
+        match resp.id {
+            ID::space_id(id) => Ok(id),
+            id => Err(MetaError::Rpc(format!(
+                "expected a space id but got {:?}",
+                id
+            ))),
+        }
+    }
+
+    pub async fn drop_space(
+        &mut self,
+        name: &Identifier,
+        if_exists: bool,
+    ) -> Result<(), MetaError> {
+        let req = DropSpaceReq {
+            space_name: name.as_str().as_bytes().to_vec(),
+            if_exists,
+        };
+        self.call(|client| client.dropSpace(&req)).await?;
+        Ok(())
+    }
+
+    /// The space called `name`; fails with `E_SPACE_NOT_FOUND` if there is
+    /// none
+    pub async fn get_space(&mut self, name: &Identifier) -> Result<SpaceInfo, MetaError> {
+        let req = GetSpaceReq {
+            space_name: name.as_str().as_bytes().to_vec(),
+        };
+        let item = self.call(|client| client.getSpace(&req)).await?.item;
+        let desc = item.properties;
+        Ok(SpaceInfo {
+            id: item.space_id,
+            vid_type: vid_type(&desc.vid_type),
+            name: string(desc.space_name),
+            partition_num: desc.partition_num,
+            replica_factor: desc.replica_factor,
+            comment: desc.comment.map(string),
+        })
+    }
+
+    /// The ids and names of all spaces
+    pub async fn list_spaces(&mut self) -> Result<Vec<(SpaceId, String)>, MetaError> {
+        let req = ListSpacesReq {};
+        let spaces = self.call(|client| client.listSpaces(&req)).await?.spaces;
+        Ok(spaces
+            .into_iter()
+            .filter_map(|space| match space.id {
+                ID::space_id(id) => Some((id, string(space.name))),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Create the tag or edge type and return its id. Properties with a
+    /// default value are not supported
+    pub async fn create_schema(
+        &mut self,
+        space: SpaceId,
+        create: &CreateSchema,
+        if_not_exists: bool,
+    ) -> Result<i32, MetaError> {
+        let name = create.name.as_str().as_bytes().to_vec();
+        let schema = schema(&create.properties, create.comment.as_ref())?;
+        let resp = match create.kind {
+            ColType::Tag => {
+                let req = CreateTagReq {
+                    space_id: space,
+                    tag_name: name,
+                    schema,
+                    if_not_exists,
+                };
+                self.call(|client| client.createTag(&req)).await?
+            }
+            ColType::Edge => {
+                let req = CreateEdgeReq {
+                    space_id: space,
+                    edge_name: name,
+                    schema,
+                    if_not_exists,
+                };
+                self.call(|client| client.createEdge(&req)).await?
+            }
+        };
+        match resp.id {
+            ID::tag_id(id) | ID::edge_type(id) => Ok(id),
+            id => Err(MetaError::Rpc(format!(
+                "expected a schema id but got {:?}",
+                id
+            ))),
+        }
+    }
+
+    /// Apply `changes` to the tag or edge type `name`. Every successful
+    /// change increases the version of the schema
+    pub async fn alter_schema(
+        &mut self,
+        space: SpaceId,
+        kind: ColType,
+        name: &Identifier,
+        changes: &[SchemaChange],
+    ) -> Result<(), MetaError> {
+        let mut items: Vec<AlterSchemaItem> = vec![];
+        for change in changes {
+            let (op, column) = match change {
+                SchemaChange::Add(property) => (AlterSchemaOp::ADD, column_def(property)?),
+                SchemaChange::Change(property) => (AlterSchemaOp::CHANGE, column_def(property)?),
+                SchemaChange::Drop(name) => (
+                    AlterSchemaOp::DROP,
+                    ColumnDef {
+                        name: name.as_str().as_bytes().to_vec(),
+                        ..Default::default()
+                    },
+                ),
+            };
+            // Consecutive changes of the same kind go into one item
+            match items.last_mut() {
+                Some(item) if item.op == op => item.schema.columns.push(column),
+                _ => {
+                    let mut item = AlterSchemaItem {
+                        op,
+                        ..Default::default()
+                    };
+                    item.schema.columns.push(column);
+                    items.push(item);
+                }
+            }
+        }
+
+        let name = name.as_str().as_bytes().to_vec();
+        match kind {
+            ColType::Tag => {
+                let req = AlterTagReq {
+                    space_id: space,
+                    tag_name: name,
+                    tag_items: items,
+                    schema_prop: SchemaProp::default(),
+                };
+                self.call(|client| client.alterTag(&req)).await?;
+            }
+            ColType::Edge => {
+                let req = AlterEdgeReq {
+                    space_id: space,
+                    edge_name: name,
+                    edge_items: items,
+                    schema_prop: SchemaProp::default(),
+                };
+                self.call(|client| client.alterEdge(&req)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn drop_schema(
+        &mut self,
+        space: SpaceId,
+        kind: ColType,
+        name: &Identifier,
+        if_exists: bool,
+    ) -> Result<(), MetaError> {
+        let name = name.as_str().as_bytes().to_vec();
+        match kind {
+            ColType::Tag => {
+                let req = DropTagReq {
+                    space_id: space,
+                    tag_name: name,
+                    if_exists,
+                };
+                self.call(|client| client.dropTag(&req)).await?;
+            }
+            ColType::Edge => {
+                let req = DropEdgeReq {
+                    space_id: space,
+                    edge_name: name,
+                    if_exists,
+                };
+                self.call(|client| client.dropEdge(&req)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The latest version of all tags or all edge types of the space
+    pub async fn list_schemas(
+        &mut self,
+        space: SpaceId,
+        kind: ColType,
+    ) -> Result<Vec<SchemaInfo>, MetaError> {
+        let schemas = match kind {
+            ColType::Tag => {
+                let req = ListTagsReq { space_id: space };
+                self.call(|client| client.listTags(&req))
+                    .await?
+                    .tags
+                    .into_iter()
+                    .map(|tag| (tag.tag_id, tag.tag_name, tag.version, tag.schema))
+                    .collect::<Vec<_>>()
+            }
+            ColType::Edge => {
+                let req = ListEdgesReq { space_id: space };
+                self.call(|client| client.listEdges(&req))
+                    .await?
+                    .edges
+                    .into_iter()
+                    .map(|edge| (edge.edge_type, edge.edge_name, edge.version, edge.schema))
+                    .collect::<Vec<_>>()
+            }
+        };
+        // The meta service lists every version of a schema; only keep the
+        // latest one
+        let mut infos: Vec<SchemaInfo> = vec![];
+        for (id, name, version, schema) in schemas {
+            let name = string(name);
+            if let Some(info) = infos.iter().find(|info| info.name == name) {
+                if info.version >= version {
+                    continue;
+                }
+            }
+            infos.retain(|info| info.name != name);
+            infos.push(SchemaInfo {
+                kind,
+                id,
+                name,
+                version,
+                comment: schema.schema_prop.comment.clone().map(string),
+                properties: property_infos(schema),
+            });
+        }
+        Ok(infos)
+    }
+
+    /// The latest version of the tag or edge type `name`, if there is one
+    pub async fn get_schema(
+        &mut self,
+        space: SpaceId,
+        kind: ColType,
+        name: &Identifier,
+    ) -> Result<Option<SchemaInfo>, MetaError> {
+        Ok(self
+            .list_schemas(space, kind)
+            .await?
+            .into_iter()
+            .find(|info| info.name == name.as_str()))
+    }
+
+    /// Wait until the tag or edge type `name` exists with at least
+    /// `version`, which is 0 for a newly created schema, and return it
+    pub async fn wait_for_schema(
+        &mut self,
+        space: SpaceId,
+        kind: ColType,
+        name: &Identifier,
+        version: SchemaVersion,
+        timeout: Duration,
+    ) -> Result<SchemaInfo, MetaError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.get_schema(space, kind, name).await? {
+                Some(info) if info.version >= version => return Ok(info),
+                _ if Instant::now() >= deadline => {
+                    return Err(MetaError::Timeout(format!(
+                        "version {} of {} {}",
+                        version,
+                        kind.to_string(),
+                        name
+                    )))
+                }
+                _ => tokio::time::sleep(SCHEMA_POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// The addresses of the storage hosts that hold a replica of each
+    /// partition of the space
+    pub async fn get_parts_alloc(
+        &mut self,
+        space: SpaceId,
+    ) -> Result<BTreeMap<PartitionId, Vec<String>>, MetaError> {
+        let req = GetPartsAllocReq { space_id: space };
+        let parts = self.call(|client| client.getPartsAlloc(&req)).await?.parts;
+        Ok(parts
+            .into_iter()
+            .map(|(part, hosts)| {
+                let hosts = hosts
+                    .into_iter()
+                    .map(|host| format!("{}:{}", host.host, host.port))
+                    .collect();
+                (part, hosts)
+            })
+            .collect())
+    }
+
+    /// The hosts that play `role` in the cluster
+    pub async fn list_hosts(&mut self, role: HostRole) -> Result<Vec<HostInfo>, MetaError> {
+        let req = ListHostsReq {
+            type_: match role {
+                HostRole::Graph => ListHostType::GRAPH,
+                HostRole::Meta => ListHostType::META,
+                HostRole::Storage => ListHostType::STORAGE,
+            },
+        };
+        let hosts = self.call(|client| client.listHosts(&req)).await?.hosts;
+        Ok(hosts
+            .into_iter()
+            .map(|host| HostInfo {
+                address: format!("{}:{}", host.hostAddr.host, host.hostAddr.port),
+                online: host.status == HostStatus::ONLINE,
+                leader_parts: host
+                    .leader_parts
+                    .into_iter()
+                    .map(|(space, parts)| (string(space), parts))
+                    .collect(),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,164 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+// Follows graph_client/transport_response_handler.rs for the methods of the
+// meta service that `MetaClient` uses
+
+use std::io;
+
+use bytes::Bytes;
+use fbthrift::{
+    binary_protocol::BinaryProtocolDeserializer, ApplicationException, Deserialize, MessageType,
+    ProtocolReader,
+};
+use fbthrift_transport::fbthrift_transport_response_handler::ResponseHandler;
+use meta::services::meta_service::{
+    AlterEdgeExn, AlterTagExn, CreateEdgeExn, CreateSpaceExn, CreateTagExn, DropEdgeExn,
+    DropSpaceExn, DropTagExn, GetPartsAllocExn, GetSpaceExn, ListEdgesExn, ListHostsExn,
+    ListSpacesExn, ListTagsExn,
+};
+
+#[derive(Clone)]
+pub struct MetaTransportResponseHandler;
+
+impl ResponseHandler for MetaTransportResponseHandler {
+    fn try_make_static_response_bytes(
+        &mut self,
+        request_bytes: &[u8],
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut des = BinaryProtocolDeserializer::<Bytes>::new(Bytes::from(request_bytes.to_vec()));
+        let (name, _, _) = des
+            .read_message_begin(|v| v.to_vec())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+        match &name[..] {
+            b"createSpace" | b"dropSpace" | b"getSpace" | b"listSpaces" | b"createTag"
+            | b"alterTag" | b"dropTag" | b"listTags" | b"createEdge" | b"alterEdge"
+            | b"dropEdge" | b"listEdges" | b"listHosts" | b"getPartsAlloc" => Ok(None),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Unknown method {:?}", name),
+            )),
+        }
+    }
+
+    fn parse_response_bytes(&mut self, response_bytes: &[u8]) -> io::Result<Option<usize>> {
+        let n = response_bytes.len();
+
+        let mut des =
+            BinaryProtocolDeserializer::<Bytes>::new(Bytes::from(response_bytes.to_vec()));
+        let (name, message_type, _) = match des.read_message_begin(|v| v.to_vec()) {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+
+        match message_type {
+            MessageType::Reply => {
+                match &name[..] {
+                    b"createSpace" => {
+                        let _: CreateSpaceExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"dropSpace" => {
+                        let _: DropSpaceExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"getSpace" => {
+                        let _: GetSpaceExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"listSpaces" => {
+                        let _: ListSpacesExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"createTag" => {
+                        let _: CreateTagExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"alterTag" => {
+                        let _: AlterTagExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"dropTag" => {
+                        let _: DropTagExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"listTags" => {
+                        let _: ListTagsExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"createEdge" => {
+                        let _: CreateEdgeExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"alterEdge" => {
+                        let _: AlterEdgeExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"dropEdge" => {
+                        let _: DropEdgeExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"listEdges" => {
+                        let _: ListEdgesExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"listHosts" => {
+                        let _: ListHostsExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    b"getPartsAlloc" => {
+                        let _: GetPartsAllocExn = match Deserialize::read(&mut des) {
+                            Ok(v) => v,
+                            Err(_) => return Ok(None),
+                        };
+                    }
+                    _ => return Ok(None),
+                };
+            }
+            MessageType::Exception => {
+                let _: ApplicationException = match Deserialize::read(&mut des) {
+                    Ok(v) => v,
+                    Err(_) => return Ok(None),
+                };
+            }
+            MessageType::Call | MessageType::Oneway | MessageType::InvalidMessageType => {}
+        }
+
+        match des.read_message_end() {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(n - des.into_inner().len()))
+    }
+}
@@ -0,0 +1,257 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! The typed requests and answers of `MetaClient`, and how they map to the
+//! thrift types of the meta service
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use meta::types::{ColumnDef, ColumnTypeDef, PropertyType, Schema, SchemaProp};
+
+use crate::graph_client::nebula_schema::{ColType, DataType};
+use crate::graph_client::ngql::{Identifier, PropertyDef, VidType};
+
+pub type SpaceId = common::types::GraphSpaceID;
+pub type SchemaVersion = meta::types::SchemaVer;
+pub type PartitionId = common::types::PartitionID;
+
+#[derive(Debug)]
+pub enum MetaError {
+    /// None of the addresses of the meta service could be reached
+    Connect(String),
+    /// Sending the request or receiving the answer failed
+    Rpc(String),
+    /// The meta service answered with an error
+    Code(common::types::ErrorCode),
+    /// The request can not be expressed through the meta service
+    Unsupported(String),
+    /// Waiting for the schema took too long
+    Timeout(String),
+}
+
+impl fmt::Display for MetaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetaError::Connect(msg) => write!(f, "can not connect to the meta service: {}", msg),
+            MetaError::Rpc(msg) => write!(f, "meta service request failed: {}", msg),
+            MetaError::Code(code) => write!(f, "meta service returned {}", code),
+            MetaError::Unsupported(msg) => write!(f, "unsupported by the meta service: {}", msg),
+            MetaError::Timeout(msg) => write!(f, "timed out waiting for {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MetaError {}
+
+/// A space as the meta service describes it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpaceInfo {
+    pub id: SpaceId,
+    pub name: String,
+    pub partition_num: i32,
+    pub replica_factor: i32,
+    /// `None` if the space uses a vid type that `VidType` can't express
+    pub vid_type: Option<VidType>,
+    pub comment: Option<String>,
+}
+
+/// A property of a tag or edge type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PropertyInfo {
+    pub name: String,
+    /// `None` for types that `DataType` can't express, like `datetime`
+    pub data_type: Option<DataType>,
+    pub nullable: bool,
+    pub comment: Option<String>,
+}
+
+/// The latest version of a tag or edge type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaInfo {
+    pub kind: ColType,
+    /// The tag id or the edge type
+    pub id: i32,
+    pub name: String,
+    /// Starts at 0 and increases with every change of the schema
+    pub version: SchemaVersion,
+    pub properties: Vec<PropertyInfo>,
+    pub comment: Option<String>,
+}
+
+/// One change of an `ALTER TAG` or `ALTER EDGE`
+#[derive(Clone, Debug)]
+pub enum SchemaChange {
+    Add(PropertyDef),
+    Change(PropertyDef),
+    Drop(Identifier),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostRole {
+    Graph,
+    Meta,
+    Storage,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostInfo {
+    /// `host:port`
+    pub address: String,
+    pub online: bool,
+    /// The partitions the host leads, by space name
+    pub leader_parts: BTreeMap<String, Vec<PartitionId>>,
+}
+
+pub(crate) fn string(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+pub(crate) fn property_type(data_type: DataType) -> Result<PropertyType, MetaError> {
+    let property_type = match data_type {
+        DataType::Bool => PropertyType::BOOL,
+        DataType::Int | DataType::Int64 => PropertyType::INT64,
+        DataType::Int32 => PropertyType::INT32,
+        DataType::Int16 => PropertyType::INT16,
+        DataType::Int8 => PropertyType::INT8,
+        DataType::Float => PropertyType::FLOAT,
+        DataType::Double => PropertyType::DOUBLE,
+        DataType::String => PropertyType::STRING,
+        DataType::Date => PropertyType::DATE,
+        DataType::Time => PropertyType::TIME,
+        DataType::FixedString => {
+            return Err(MetaError::Unsupported(
+                "fixed_string properties need a length".to_string(),
+            ))
+        }
+    };
+    Ok(property_type)
+}
+
+pub(crate) fn data_type(property_type: PropertyType) -> Option<DataType> {
+    let data_type = match property_type {
+        PropertyType::BOOL => DataType::Bool,
+        PropertyType::INT64 => DataType::Int64,
+        PropertyType::INT32 => DataType::Int32,
+        PropertyType::INT16 => DataType::Int16,
+        PropertyType::INT8 => DataType::Int8,
+        PropertyType::FLOAT => DataType::Float,
+        PropertyType::DOUBLE => DataType::Double,
+        PropertyType::STRING => DataType::String,
+        PropertyType::FIXED_STRING => DataType::FixedString,
+        PropertyType::DATE => DataType::Date,
+        PropertyType::TIME => DataType::Time,
+        _ => return None,
+    };
+    Some(data_type)
+}
+
+pub(crate) fn vid_type_def(vid_type: VidType) -> ColumnTypeDef {
+    match vid_type {
+        VidType::Int64 => ColumnTypeDef {
+            type_: PropertyType::INT64,
+            type_length: None,
+        },
+        VidType::FixedString(length) => ColumnTypeDef {
+            type_: PropertyType::FIXED_STRING,
+            type_length: Some(length as i16),
+        },
+    }
+}
+
+pub(crate) fn vid_type(def: &ColumnTypeDef) -> Option<VidType> {
+    match (def.type_, def.type_length) {
+        (PropertyType::INT64, _) => Some(VidType::Int64),
+        (PropertyType::FIXED_STRING, Some(length)) if length > 0 => {
+            Some(VidType::FixedString(length as u32))
+        }
+        _ => None,
+    }
+}
+
+/// The meta service expects defaults as serialized expressions, which this
+/// client can't produce; properties with a default have to be created
+/// with nGQL instead
+pub(crate) fn column_def(property: &PropertyDef) -> Result<ColumnDef, MetaError> {
+    if property.default.is_some() {
+        return Err(MetaError::Unsupported(format!(
+            "the default value of property {}",
+            property.name
+        )));
+    }
+    Ok(ColumnDef {
+        name: property.name.as_str().as_bytes().to_vec(),
+        type_: ColumnTypeDef {
+            type_: property_type(property.data_type)?,
+            type_length: None,
+        },
+        default_value: None,
+        nullable: Some(property.nullable),
+        comment: property.comment.as_ref().map(|c| c.as_bytes().to_vec()),
+    })
+}
+
+pub(crate) fn schema(
+    properties: &[PropertyDef],
+    comment: Option<&String>,
+) -> Result<Schema, MetaError> {
+    Ok(Schema {
+        columns: properties
+            .iter()
+            .map(column_def)
+            .collect::<Result<_, _>>()?,
+        schema_prop: SchemaProp {
+            comment: comment.map(|c| c.as_bytes().to_vec()),
+            ..Default::default()
+        },
+    })
+}
+
+pub(crate) fn property_infos(schema: Schema) -> Vec<PropertyInfo> {
+    schema
+        .columns
+        .into_iter()
+        .map(|column| PropertyInfo {
+            name: string(column.name),
+            data_type: data_type(column.type_.type_),
+            nullable: column.nullable.unwrap_or(false),
+            comment: column.comment.map(string),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_types() {
+        for data_type in [
+            DataType::Bool,
+            DataType::Int64,
+            DataType::Int32,
+            DataType::Int16,
+            DataType::Int8,
+            DataType::Float,
+            DataType::Double,
+            DataType::String,
+            DataType::Date,
+            DataType::Time,
+        ]
+        .iter()
+        {
+            let property_type = property_type(*data_type).unwrap();
+            assert_eq!(Some(*data_type), super::data_type(property_type));
+        }
+        assert_eq!(PropertyType::INT64, property_type(DataType::Int).unwrap());
+        assert!(property_type(DataType::FixedString).is_err());
+        assert_eq!(None, super::data_type(PropertyType::DATETIME));
+
+        for vid in [VidType::Int64, VidType::FixedString(64)].iter() {
+            assert_eq!(Some(*vid), vid_type(&vid_type_def(*vid)));
+        }
+    }
+}
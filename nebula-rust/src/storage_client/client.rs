@@ -0,0 +1,343 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+use std::collections::{HashMap, VecDeque};
+
+use common::types::{ErrorCode, Row};
+use fbthrift::BinaryProtocol;
+use fbthrift_transport::{tokio_io::transport::AsyncTransport, AsyncTransportConfiguration};
+use futures::stream::{self, BoxStream, StreamExt};
+use storage::client::{GraphStorageService, GraphStorageServiceImpl};
+use storage::types::{EdgeProp, ScanEdgeRequest, ScanVertexRequest, VertexProp};
+use tokio::net::TcpStream;
+
+use crate::graph_client::nebula_schema::ColType;
+use crate::graph_client::ngql::Identifier;
+use crate::meta_client::client::MetaClient;
+use crate::meta_client::types::{HostRole, PartitionId, SpaceId};
+use crate::storage_client::transport_response_handler::StorageTransportResponseHandler;
+use crate::storage_client::types::{
+    edge, vertex, ScannedEdge, ScannedVertex, StorageError, EDGE_COLUMNS, VERTEX_COLUMNS,
+};
+
+/// How many rows one request reads from a partition unless
+/// `with_batch_size` says otherwise
+const DEFAULT_BATCH_SIZE: i64 = 1000;
+
+/// How often a request for a partition is sent again because its leader
+/// changed or its host could not be reached
+const MAX_REDIRECTS: usize = 3;
+
+type Client = GraphStorageServiceImpl<
+    BinaryProtocol,
+    AsyncTransport<TcpStream, StorageTransportResponseHandler>,
+>;
+
+/// What a scan asks the storage service for
+enum Columns {
+    Vertex(VertexProp),
+    Edge(EdgeProp),
+}
+
+/// The state of a scan between two requests
+struct Scan {
+    space: SpaceId,
+    space_name: String,
+    columns: Columns,
+    /// The partitions that were not read completely yet, starting with
+    /// the one that is being read
+    parts: VecDeque<PartitionId>,
+    /// Where to continue reading the first of `parts`
+    cursor: Option<Vec<u8>>,
+    /// The rows that were read but not returned yet
+    rows: VecDeque<Row>,
+}
+
+/// Reads vertices and edges directly from the storage service, bypassing
+/// graphd. Each partition is read from its leader, which is looked up
+/// through the meta service and updated when the storage service names
+/// another host as the leader
+///
+/// Scans read one partition after the other in batches, so that only one
+/// batch is held in memory at a time
+pub struct StorageClient {
+    meta: MetaClient,
+    batch_size: i64,
+    /// The address of the leader of each partition, as far as it is known
+    leaders: HashMap<(SpaceId, PartitionId), String>,
+    /// The connections to storage hosts by address
+    clients: HashMap<String, Client>,
+}
+
+impl StorageClient {
+    pub fn new(meta: MetaClient) -> Self {
+        StorageClient {
+            meta,
+            batch_size: DEFAULT_BATCH_SIZE,
+            leaders: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Read at most `batch_size` rows with each request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0);
+        self.batch_size = batch_size as i64;
+        self
+    }
+
+    /// Stream all vertices of the space that have the tag, with all
+    /// properties of the latest version of the tag
+    pub async fn scan_vertices<'a>(
+        &'a mut self,
+        space: &Identifier,
+        tag: &Identifier,
+    ) -> Result<BoxStream<'a, Result<ScannedVertex, StorageError>>, StorageError> {
+        let (scan, names) = self.start(space, ColType::Tag, tag).await?;
+        Ok(self
+            .rows(scan)
+            .map(move |row| row.and_then(|row| vertex(row, &names)))
+            .boxed())
+    }
+
+    /// Stream all edges of the space of the edge type, with all properties
+    /// of the latest version of the edge type
+    pub async fn scan_edges<'a>(
+        &'a mut self,
+        space: &Identifier,
+        edge_type: &Identifier,
+    ) -> Result<BoxStream<'a, Result<ScannedEdge, StorageError>>, StorageError> {
+        let (scan, names) = self.start(space, ColType::Edge, edge_type).await?;
+        Ok(self
+            .rows(scan)
+            .map(move |row| row.and_then(|row| edge(row, &names)))
+            .boxed())
+    }
+
+    /// Look up what a scan of the tag or edge type `name` needs, and the
+    /// names of the properties it returns
+    async fn start(
+        &mut self,
+        space: &Identifier,
+        kind: ColType,
+        name: &Identifier,
+    ) -> Result<(Scan, Vec<String>), StorageError> {
+        let space = self.meta.get_space(space).await?;
+        let schema = self
+            .meta
+            .get_schema(space.id, kind, name)
+            .await?
+            .ok_or_else(|| {
+                StorageError::NotFound(format!(
+                    "{} {} in space {}",
+                    kind.to_string(),
+                    name,
+                    space.name
+                ))
+            })?;
+        let names: Vec<String> = schema.properties.into_iter().map(|p| p.name).collect();
+
+        let fixed: &[&str] = match kind {
+            ColType::Tag => &VERTEX_COLUMNS,
+            ColType::Edge => &EDGE_COLUMNS,
+        };
+        let props = fixed
+            .iter()
+            .map(|column| column.as_bytes().to_vec())
+            .chain(names.iter().map(|name| name.as_bytes().to_vec()))
+            .collect();
+        let columns = match kind {
+            ColType::Tag => Columns::Vertex(VertexProp {
+                tag: schema.id,
+                props,
+            }),
+            ColType::Edge => Columns::Edge(EdgeProp {
+                type_: schema.id,
+                props,
+            }),
+        };
+
+        let parts = self
+            .meta
+            .get_parts_alloc(space.id)
+            .await?
+            .keys()
+            .copied()
+            .collect();
+        let scan = Scan {
+            space: space.id,
+            space_name: space.name,
+            columns,
+            parts,
+            cursor: None,
+            rows: VecDeque::new(),
+        };
+        Ok((scan, names))
+    }
+
+    /// Stream the rows of all partitions of the scan. The stream ends after
+    /// the first error
+    fn rows(&mut self, scan: Scan) -> BoxStream<'_, Result<Row, StorageError>> {
+        stream::unfold((self, scan), |(client, mut scan)| async move {
+            loop {
+                if let Some(row) = scan.rows.pop_front() {
+                    return Some((Ok(row), (client, scan)));
+                }
+                let part = *scan.parts.front()?;
+                match client.scan_part(&scan, part).await {
+                    Ok((rows, cursor)) => {
+                        scan.rows.extend(rows);
+                        if cursor.is_none() {
+                            scan.parts.pop_front();
+                        }
+                        scan.cursor = cursor;
+                    }
+                    Err(e) => {
+                        scan.parts.clear();
+                        return Some((Err(e), (client, scan)));
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Read the next batch of `part` from its leader. Returns the rows and
+    /// where to continue, or `None` if the partition was read completely
+    async fn scan_part(
+        &mut self,
+        scan: &Scan,
+        part: PartitionId,
+    ) -> Result<(Vec<Row>, Option<Vec<u8>>), StorageError> {
+        let limit = self.batch_size;
+        let mut error = StorageError::Code(ErrorCode::E_LEADER_CHANGED);
+        for _ in 0..=MAX_REDIRECTS {
+            let address = self.leader(scan.space, &scan.space_name, part).await?;
+            let client = self.client(&address).await?;
+            let resp = match &scan.columns {
+                Columns::Vertex(props) => {
+                    let req = ScanVertexRequest {
+                        space_id: scan.space,
+                        part_id: part,
+                        cursor: scan.cursor.clone(),
+                        return_columns: props.clone(),
+                        limit,
+                        only_latest_version: true,
+                        ..Default::default()
+                    };
+                    client.scanVertex(&req).await.map(|resp| {
+                        (
+                            resp.result,
+                            resp.vertex_data,
+                            resp.has_next,
+                            resp.next_cursor,
+                        )
+                    })
+                }
+                Columns::Edge(props) => {
+                    let req = ScanEdgeRequest {
+                        space_id: scan.space,
+                        part_id: part,
+                        cursor: scan.cursor.clone(),
+                        return_columns: props.clone(),
+                        limit,
+                        only_latest_version: true,
+                        ..Default::default()
+                    };
+                    client
+                        .scanEdge(&req)
+                        .await
+                        .map(|resp| (resp.result, resp.edge_data, resp.has_next, resp.next_cursor))
+                }
+            };
+
+            match resp {
+                Ok((result, data, has_next, cursor)) => {
+                    let failed = result.failed_parts.into_iter().find(|p| p.part_id == part);
+                    match failed {
+                        None => {
+                            let cursor = if has_next { cursor } else { None };
+                            return Ok((data.rows, cursor));
+                        }
+                        Some(failed) if failed.code == ErrorCode::E_LEADER_CHANGED => {
+                            match failed.leader {
+                                Some(leader) if !leader.host.is_empty() => {
+                                    let leader = format!("{}:{}", leader.host, leader.port);
+                                    self.leaders.insert((scan.space, part), leader);
+                                }
+                                _ => {
+                                    self.leaders.remove(&(scan.space, part));
+                                }
+                            }
+                        }
+                        Some(failed) => return Err(StorageError::Code(failed.code)),
+                    }
+                }
+                Err(e) => {
+                    error = StorageError::Rpc(format!("{}: {}", address, e));
+                    self.clients.remove(&address);
+                    self.leaders.remove(&(scan.space, part));
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// The address of the leader of the partition. Asks the meta service
+    /// for the leaders of the space when it is not known
+    async fn leader(
+        &mut self,
+        space: SpaceId,
+        space_name: &str,
+        part: PartitionId,
+    ) -> Result<String, StorageError> {
+        if let Some(leader) = self.leaders.get(&(space, part)) {
+            return Ok(leader.clone());
+        }
+
+        for host in self.meta.list_hosts(HostRole::Storage).await? {
+            if !host.online {
+                continue;
+            }
+            if let Some(parts) = host.leader_parts.get(space_name) {
+                for part in parts {
+                    self.leaders.insert((space, *part), host.address.clone());
+                }
+            }
+        }
+        // Partitions without a leader, e.g. right after the space was
+        // created, are tried on their first replica, which redirects to
+        // the leader once there is one
+        if !self.leaders.contains_key(&(space, part)) {
+            let replica = self
+                .meta
+                .get_parts_alloc(space)
+                .await?
+                .remove(&part)
+                .and_then(|hosts| hosts.into_iter().next())
+                .ok_or_else(|| {
+                    StorageError::NotFound(format!("partition {} of space {}", part, space_name))
+                })?;
+            self.leaders.insert((space, part), replica);
+        }
+        Ok(self.leaders[&(space, part)].clone())
+    }
+
+    async fn client(&mut self, address: &str) -> Result<&Client, StorageError> {
+        if !self.clients.contains_key(address) {
+            let stream = TcpStream::connect(address)
+                .await
+                .map_err(|e| StorageError::Connect(format!("{}: {}", address, e)))?;
+            let transport = AsyncTransport::new(
+                stream,
+                AsyncTransportConfiguration::new(StorageTransportResponseHandler),
+            );
+            self.clients
+                .insert(address.to_string(), GraphStorageServiceImpl::new(transport));
+        }
+        Ok(&self.clients[address])
+    }
+}
@@ -0,0 +1,178 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! What `StorageClient` returns, and how it is read from the rows of the
+//! storage service
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use common::types::{ErrorCode, Row, Value};
+
+use crate::meta_client::types::MetaError;
+
+/// The columns that a scan of vertices asks for before the properties
+pub(crate) const VERTEX_COLUMNS: [&str; 1] = ["_vid"];
+
+/// The columns that a scan of edges asks for before the properties
+pub(crate) const EDGE_COLUMNS: [&str; 3] = ["_src", "_dst", "_rank"];
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Finding the space, the schema, or the partition leaders failed
+    Meta(MetaError),
+    /// The space has no tag or edge type of that name
+    NotFound(String),
+    /// A storage host could not be reached
+    Connect(String),
+    /// Sending the request or receiving the answer failed
+    Rpc(String),
+    /// The storage service answered with an error for a partition
+    Code(ErrorCode),
+    /// The storage service answered with rows of an unexpected shape
+    Data(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Meta(e) => write!(f, "{}", e),
+            StorageError::NotFound(msg) => write!(f, "{} does not exist", msg),
+            StorageError::Connect(msg) => {
+                write!(f, "can not connect to the storage service: {}", msg)
+            }
+            StorageError::Rpc(msg) => write!(f, "storage service request failed: {}", msg),
+            StorageError::Code(code) => write!(f, "storage service returned {}", code),
+            StorageError::Data(msg) => write!(f, "unexpected answer from storage: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<MetaError> for StorageError {
+    fn from(e: MetaError) -> Self {
+        StorageError::Meta(e)
+    }
+}
+
+/// A vertex with the properties of one of its tags
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannedVertex {
+    pub vid: Value,
+    pub props: BTreeMap<String, Value>,
+}
+
+/// An edge with its properties
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannedEdge {
+    pub src: Value,
+    pub dst: Value,
+    pub rank: i64,
+    pub props: BTreeMap<String, Value>,
+}
+
+/// Split off the first `columns` values of `row` and pair the rest with
+/// the property `names`
+fn split(
+    row: Row,
+    columns: usize,
+    names: &[String],
+) -> Result<(Vec<Value>, BTreeMap<String, Value>), StorageError> {
+    let mut values = row.values;
+    if values.len() != columns + names.len() {
+        return Err(StorageError::Data(format!(
+            "expected {} columns but got {}",
+            columns + names.len(),
+            values.len()
+        )));
+    }
+    let props = values.split_off(columns);
+    Ok((values, names.iter().cloned().zip(props).collect()))
+}
+
+pub(crate) fn vertex(row: Row, names: &[String]) -> Result<ScannedVertex, StorageError> {
+    let (mut columns, props) = split(row, VERTEX_COLUMNS.len(), names)?;
+    Ok(ScannedVertex {
+        vid: columns.remove(0),
+        props,
+    })
+}
+
+pub(crate) fn edge(row: Row, names: &[String]) -> Result<ScannedEdge, StorageError> {
+    let (columns, props) = split(row, EDGE_COLUMNS.len(), names)?;
+    let mut columns = columns.into_iter();
+    let src = columns.next().unwrap();
+    let dst = columns.next().unwrap();
+    let rank = match columns.next().unwrap() {
+        Value::iVal(rank) => rank,
+        value => {
+            return Err(StorageError::Data(format!(
+                "expected an integer rank but got {:?}",
+                value
+            )))
+        }
+    };
+    Ok(ScannedEdge {
+        src,
+        dst,
+        rank,
+        props,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::sVal(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn rows() {
+        let names = vec!["name".to_string(), "age".to_string()];
+
+        let row = Row {
+            values: vec![string("v1"), string("alice"), Value::iVal(42)],
+        };
+        let vertex = vertex(row, &names).unwrap();
+        assert_eq!(string("v1"), vertex.vid);
+        assert_eq!(Some(&string("alice")), vertex.props.get("name"));
+        assert_eq!(Some(&Value::iVal(42)), vertex.props.get("age"));
+
+        let row = Row {
+            values: vec![
+                string("v1"),
+                string("v2"),
+                Value::iVal(7),
+                string("bob"),
+                Value::iVal(1),
+            ],
+        };
+        let edge = edge(row, &names).unwrap();
+        assert_eq!(
+            (string("v1"), string("v2"), 7),
+            (edge.src, edge.dst, edge.rank)
+        );
+        assert_eq!(2, edge.props.len());
+
+        let short = Row {
+            values: vec![string("v1")],
+        };
+        assert!(super::vertex(short, &names).is_err());
+        let bad_rank = Row {
+            values: vec![
+                string("v1"),
+                string("v2"),
+                string("0"),
+                string("bob"),
+                Value::iVal(1),
+            ],
+        };
+        assert!(super::edge(bad_rank, &names).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! Conversion of the values in a result into Rust types
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+
+use common::types::{Date, DateTime, Edge, Path, Time, Value, Vertex};
+
+use crate::value::error::ResultError;
+
+/// A Rust type that a value of a result can be read as
+///
+/// `null` can only be read as `Option`, `Value`, or `()`. Lists and sets
+/// are read as `Vec`, and maps as maps with `String` keys
+pub trait FromNebulaValue: Sized {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError>;
+}
+
+/// The name of the type of `value` as nGQL calls it
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::nVal(_) => "null",
+        Value::bVal(_) => "bool",
+        Value::iVal(_) => "int",
+        Value::fVal(_) => "float",
+        Value::sVal(_) => "string",
+        Value::dVal(_) => "date",
+        Value::tVal(_) => "time",
+        Value::dtVal(_) => "datetime",
+        Value::vVal(_) => "vertex",
+        Value::eVal(_) => "edge",
+        Value::pVal(_) => "path",
+        Value::lVal(_) => "list",
+        Value::mVal(_) => "map",
+        Value::uVal(_) => "set",
+        Value::gVal(_) => "dataset",
+        Value::UnknownField(_) => "unknown",
+    }
+}
+
+fn mismatch(expected: &'static str, value: &Value) -> ResultError {
+    ResultError::Type {
+        expected,
+        found: type_name(value),
+    }
+}
+
+impl FromNebulaValue for Value {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromNebulaValue for () {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::nVal(_) => Ok(()),
+            value => Err(mismatch("null", value)),
+        }
+    }
+}
+
+impl<T: FromNebulaValue> FromNebulaValue for Option<T> {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::nVal(_) => Ok(None),
+            value => T::from_nebula_value(value).map(Some),
+        }
+    }
+}
+
+impl FromNebulaValue for bool {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::bVal(b) => Ok(*b),
+            value => Err(mismatch("bool", value)),
+        }
+    }
+}
+
+impl FromNebulaValue for i64 {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::iVal(i) => Ok(*i),
+            value => Err(mismatch("int", value)),
+        }
+    }
+}
+
+macro_rules! from_int {
+    ($($ty:ty),*) => {
+        $(impl FromNebulaValue for $ty {
+            fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+                let i = i64::from_nebula_value(value)?;
+                <$ty>::try_from(i).map_err(|_| ResultError::OutOfRange {
+                    expected: stringify!($ty),
+                    value: i.to_string(),
+                })
+            }
+        })*
+    };
+}
+
+from_int!(i32, i16, i8, u64, u32, u16, u8, usize);
+
+impl FromNebulaValue for f64 {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::fVal(f) => Ok(f.0),
+            value => Err(mismatch("float", value)),
+        }
+    }
+}
+
+impl FromNebulaValue for f32 {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        f64::from_nebula_value(value).map(|f| f as f32)
+    }
+}
+
+impl FromNebulaValue for String {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::sVal(s) => String::from_utf8(s.clone()).map_err(|_| ResultError::OutOfRange {
+                expected: "String",
+                value: String::from_utf8_lossy(s).into_owned(),
+            }),
+            value => Err(mismatch("string", value)),
+        }
+    }
+}
+
+impl<T: FromNebulaValue> FromNebulaValue for Vec<T> {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::lVal(list) => list.values.iter().map(T::from_nebula_value).collect(),
+            Value::uVal(set) => set.values.iter().map(T::from_nebula_value).collect(),
+            value => Err(mismatch("list", value)),
+        }
+    }
+}
+
+fn map_entries<T: FromNebulaValue>(
+    value: &Value,
+) -> Result<impl Iterator<Item = Result<(String, T), ResultError>> + '_, ResultError> {
+    match value {
+        Value::mVal(map) => Ok(map.kvs.iter().map(|(key, value)| {
+            let key = String::from_utf8_lossy(key).into_owned();
+            T::from_nebula_value(value).map(|value| (key, value))
+        })),
+        value => Err(mismatch("map", value)),
+    }
+}
+
+impl<T: FromNebulaValue> FromNebulaValue for BTreeMap<String, T> {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        map_entries(value)?.collect()
+    }
+}
+
+impl<T: FromNebulaValue> FromNebulaValue for HashMap<String, T> {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        map_entries(value)?.collect()
+    }
+}
+
+macro_rules! from_variant {
+    ($($ty:ty => $variant:ident, $name:expr);*) => {
+        $(impl FromNebulaValue for $ty {
+            fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+                match value {
+                    Value::$variant(v) => Ok((**v).clone()),
+                    value => Err(mismatch($name, value)),
+                }
+            }
+        })*
+    };
+}
+
+from_variant!(
+    Vertex => vVal, "vertex";
+    Edge => eVal, "edge";
+    Path => pVal, "path"
+);
+
+impl FromNebulaValue for Date {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::dVal(d) => Ok(d.clone()),
+            value => Err(mismatch("date", value)),
+        }
+    }
+}
+
+impl FromNebulaValue for Time {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::tVal(t) => Ok(t.clone()),
+            value => Err(mismatch("time", value)),
+        }
+    }
+}
+
+impl FromNebulaValue for DateTime {
+    fn from_nebula_value(value: &Value) -> Result<Self, ResultError> {
+        match value {
+            Value::dtVal(dt) => Ok(dt.clone()),
+            value => Err(mismatch("datetime", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::double::Double;
+    use common::types::{NList, NMap, NullType};
+
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::sVal(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(Ok(true), bool::from_nebula_value(&Value::bVal(true)));
+        assert_eq!(Ok(-7), i64::from_nebula_value(&Value::iVal(-7)));
+        assert_eq!(Ok(7u8), u8::from_nebula_value(&Value::iVal(7)));
+        assert_eq!(Ok(1.5), f64::from_nebula_value(&Value::fVal(Double(1.5))));
+        assert_eq!(Ok("a".to_string()), String::from_nebula_value(&string("a")));
+        assert_eq!(
+            Ok(None),
+            Option::<i64>::from_nebula_value(&Value::nVal(NullType::__NULL__))
+        );
+
+        assert_eq!(
+            Err(ResultError::Type {
+                expected: "int",
+                found: "string"
+            }),
+            i64::from_nebula_value(&string("1"))
+        );
+        assert!(matches!(
+            i32::from_nebula_value(&Value::iVal(i64::MAX)),
+            Err(ResultError::OutOfRange { .. })
+        ));
+        assert!(u32::from_nebula_value(&Value::iVal(-1)).is_err());
+        assert!(i64::from_nebula_value(&Value::nVal(NullType::__NULL__)).is_err());
+    }
+
+    #[test]
+    fn collections() {
+        let list = Value::lVal(Box::new(NList {
+            values: vec![Value::iVal(1), Value::iVal(2)],
+        }));
+        assert_eq!(Ok(vec![1, 2]), Vec::<i64>::from_nebula_value(&list));
+        assert!(Vec::<String>::from_nebula_value(&list).is_err());
+
+        let mut kvs = BTreeMap::new();
+        kvs.insert(b"a".to_vec(), Value::iVal(1));
+        kvs.insert(b"b".to_vec(), Value::nVal(NullType::__NULL__));
+        let map = Value::mVal(Box::new(NMap { kvs }));
+        let map = HashMap::<String, Option<i64>>::from_nebula_value(&map).unwrap();
+        assert_eq!(Some(&Some(1)), map.get("a"));
+        assert_eq!(Some(&None), map.get("b"));
+    }
+}
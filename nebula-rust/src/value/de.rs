@@ -0,0 +1,327 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+//! Deserialization of values and rows into serde types
+//!
+//! Scalars, lists, sets, and maps deserialize as their serde counterparts;
+//! `null` deserializes as `None` or `()`. Strings that are not UTF-8
+//! deserialize as bytes. Dates and times deserialize as ISO 8601 strings.
+//! Graph elements deserialize as maps:
+//!
+//! - a vertex as `{ vid, tags: { <tag>: { <prop>: .. } } }`
+//! - an edge as `{ src, dst, name, ranking, props: { <prop>: .. } }`
+//! - a path as `{ src: <vertex>, steps: [{ dst: <vertex>, name, ranking,
+//!   props }] }`
+//! - a dataset as a list of rows that map column names to values
+
+use std::collections::BTreeMap;
+
+use common::types::{DataSet, Date, DateTime, NList, NMap, Time, Value, Vertex};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer, Visitor};
+use serde::{forward_to_deserialize_any, Deserializer};
+
+use crate::value::error::ResultError;
+
+/// Deserialize `value` into `T`
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ResultError> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+/// Deserialize a row into `T`, which is usually a struct whose fields are
+/// named like the columns
+pub fn from_row<T: DeserializeOwned>(
+    column_names: &[String],
+    values: Vec<Value>,
+) -> Result<T, ResultError> {
+    T::deserialize(MapDeserializer::new(
+        column_names
+            .iter()
+            .cloned()
+            .zip(values.into_iter().map(ValueDeserializer)),
+    ))
+}
+
+pub struct ValueDeserializer(pub Value);
+
+impl<'de> IntoDeserializer<'de, ResultError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn string(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn date(d: &Date) -> String {
+    format!("{:04}-{:02}-{:02}", d.year, d.month, d.day)
+}
+
+fn time(hour: i8, minute: i8, sec: i8, microsec: i32) -> String {
+    format!("{:02}:{:02}:{:02}.{:06}", hour, minute, sec, microsec)
+}
+
+fn map<K: Into<Vec<u8>>>(entries: Vec<(K, Value)>) -> Value {
+    let kvs = entries
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect();
+    Value::mVal(Box::new(NMap { kvs }))
+}
+
+fn props(props: BTreeMap<Vec<u8>, Value>) -> Value {
+    Value::mVal(Box::new(NMap { kvs: props }))
+}
+
+fn vertex(vertex: Vertex) -> Value {
+    let tags = vertex
+        .tags
+        .into_iter()
+        .map(|tag| (tag.name, props(tag.props)))
+        .collect();
+    map(vec![("vid", vertex.vid), ("tags", map(tags))])
+}
+
+fn data_set(data_set: DataSet) -> Value {
+    let DataSet { column_names, rows } = data_set;
+    let rows = rows
+        .into_iter()
+        .map(|row| map(column_names.iter().cloned().zip(row.values).collect()))
+        .collect();
+    Value::lVal(Box::new(NList { values: rows }))
+}
+
+fn seq<'de, V: Visitor<'de>>(values: Vec<Value>, visitor: V) -> Result<V::Value, ResultError> {
+    let mut seq = SeqDeserializer::<_, ResultError>::new(values.into_iter().map(ValueDeserializer));
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer {
+    type Error = ResultError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ResultError> {
+        match self.0 {
+            Value::nVal(_) => visitor.visit_unit(),
+            Value::bVal(b) => visitor.visit_bool(b),
+            Value::iVal(i) => visitor.visit_i64(i),
+            Value::fVal(f) => visitor.visit_f64(f.0),
+            Value::sVal(s) => match String::from_utf8(s) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Value::dVal(d) => visitor.visit_string(date(&d)),
+            Value::tVal(Time {
+                hour,
+                minute,
+                sec,
+                microsec,
+            }) => visitor.visit_string(time(hour, minute, sec, microsec)),
+            Value::dtVal(dt) => {
+                let DateTime {
+                    year,
+                    month,
+                    day,
+                    hour,
+                    minute,
+                    sec,
+                    microsec,
+                } = dt;
+                let d = date(&Date { year, month, day });
+                visitor.visit_string(format!("{}T{}", d, time(hour, minute, sec, microsec)))
+            }
+            Value::vVal(v) => ValueDeserializer(vertex(*v)).deserialize_any(visitor),
+            Value::eVal(e) => {
+                let e = *e;
+                let value = map(vec![
+                    ("src", e.src),
+                    ("dst", e.dst),
+                    ("name", Value::sVal(e.name)),
+                    ("ranking", Value::iVal(e.ranking)),
+                    ("props", props(e.props)),
+                ]);
+                ValueDeserializer(value).deserialize_any(visitor)
+            }
+            Value::pVal(p) => {
+                let p = *p;
+                let steps = p
+                    .steps
+                    .into_iter()
+                    .map(|step| {
+                        map(vec![
+                            ("dst", vertex(step.dst)),
+                            ("name", Value::sVal(step.name)),
+                            ("ranking", Value::iVal(step.ranking)),
+                            ("props", props(step.props)),
+                        ])
+                    })
+                    .collect();
+                let value = map(vec![
+                    ("src", vertex(p.src)),
+                    ("steps", Value::lVal(Box::new(NList { values: steps }))),
+                ]);
+                ValueDeserializer(value).deserialize_any(visitor)
+            }
+            Value::lVal(l) => seq(l.values, visitor),
+            Value::uVal(u) => seq(u.values.into_iter().collect(), visitor),
+            Value::mVal(m) => {
+                let mut map = MapDeserializer::<_, ResultError>::new(
+                    m.kvs
+                        .into_iter()
+                        .map(|(key, value)| (string(key), ValueDeserializer(value))),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::gVal(g) => ValueDeserializer(data_set(*g)).deserialize_any(visitor),
+            Value::UnknownField(field) => Err(ResultError::Deserialize(format!(
+                "unknown value type {}",
+                field
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ResultError> {
+        match self.0 {
+            Value::nVal(_) => visitor.visit_none(),
+            value => visitor.visit_some(ValueDeserializer(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ResultError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ResultError> {
+        // Only unit variants, which are stored as their name
+        match self.0 {
+            Value::sVal(s) => visitor.visit_enum(string(s).into_deserializer()),
+            value => Err(ResultError::Type {
+                expected: "string",
+                found: crate::value::convert::type_name(&value),
+            }),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::{Edge, NullType, Tag};
+    use serde::de::IgnoredAny;
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn s(s: &str) -> Value {
+        Value::sVal(s.as_bytes().to_vec())
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Player {
+        name: String,
+        age: u8,
+        nickname: Option<String>,
+        teams: Vec<String>,
+        born: String,
+    }
+
+    #[test]
+    fn row() {
+        let columns = vec![
+            "name".to_string(),
+            "age".to_string(),
+            "nickname".to_string(),
+            "teams".to_string(),
+            "born".to_string(),
+        ];
+        let values = vec![
+            s("Tim"),
+            Value::iVal(42),
+            Value::nVal(NullType::__NULL__),
+            Value::lVal(Box::new(NList {
+                values: vec![s("Spurs")],
+            })),
+            Value::dVal(Date {
+                year: 1976,
+                month: 4,
+                day: 25,
+            }),
+        ];
+        let player: Player = from_row(&columns, values.clone()).unwrap();
+        assert_eq!(
+            Player {
+                name: "Tim".to_string(),
+                age: 42,
+                nickname: None,
+                teams: vec!["Spurs".to_string()],
+                born: "1976-04-25".to_string(),
+            },
+            player
+        );
+
+        let mut values = values;
+        values[1] = s("42");
+        assert!(from_row::<Player>(&columns, values).is_err());
+    }
+
+    #[test]
+    fn graph_elements() {
+        #[derive(Deserialize)]
+        struct E {
+            src: String,
+            dst: String,
+            ranking: i64,
+            props: BTreeMap<String, i64>,
+        }
+
+        let mut props = BTreeMap::new();
+        props.insert(b"since".to_vec(), Value::iVal(1997));
+        let edge = Value::eVal(Box::new(Edge {
+            src: s("a"),
+            dst: s("b"),
+            type_: 1,
+            name: b"serve".to_vec(),
+            ranking: 3,
+            props: props.clone(),
+        }));
+        let edge: E = from_value(edge).unwrap();
+        assert_eq!(("a", "b", 3), (&*edge.src, &*edge.dst, edge.ranking));
+        assert_eq!(Some(&1997), edge.props.get("since"));
+
+        let vertex = Value::vVal(Box::new(Vertex {
+            vid: s("a"),
+            tags: vec![Tag {
+                name: b"player".to_vec(),
+                props,
+            }],
+        }));
+        let vertex: BTreeMap<String, IgnoredAny> = from_value(vertex).unwrap();
+        assert_eq!(
+            vec!["tags", "vid"],
+            vertex.keys().map(String::as_str).collect::<Vec<_>>()
+        );
+    }
+}
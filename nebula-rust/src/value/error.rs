@@ -0,0 +1,64 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+use std::fmt;
+
+use common::types::ErrorCode;
+
+/// Why the result of a statement can not be read
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResultError {
+    /// The statement failed, with the code and message of graphd
+    Execution { code: ErrorCode, msg: String },
+    /// The result has no column of that name
+    NoSuchColumn(String),
+    /// A row has no value at that index
+    NoSuchIndex(usize),
+    /// The value has another type than the one it is read as
+    Type {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The value has the right type but does not fit the Rust type, e.g.,
+    /// an `int` that is too big for an `i32`
+    OutOfRange {
+        expected: &'static str,
+        value: String,
+    },
+    /// Deserializing a row or a value into a serde type failed
+    Deserialize(String),
+}
+
+impl fmt::Display for ResultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResultError::Execution { code, msg } => {
+                write!(f, "statement failed with {}: {}", code, msg)
+            }
+            ResultError::NoSuchColumn(name) => write!(f, "the result has no column `{}`", name),
+            ResultError::NoSuchIndex(index) => write!(f, "the row has no value at {}", index),
+            ResultError::Type { expected, found } => {
+                write!(
+                    f,
+                    "expected a value of type {} but found {}",
+                    expected, found
+                )
+            }
+            ResultError::OutOfRange { expected, value } => {
+                write!(f, "the value {} does not fit into {}", value, expected)
+            }
+            ResultError::Deserialize(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ResultError {}
+
+impl serde::de::Error for ResultError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ResultError::Deserialize(msg.to_string())
+    }
+}
@@ -7,3 +7,13 @@
 /// Some extension of the thrift value
 pub mod data_set;
 pub mod row;
+
+/// Reading the results of statements
+pub mod convert;
+pub mod de;
+pub mod error;
+pub mod result_set;
+
+pub use convert::FromNebulaValue;
+pub use error::ResultError;
+pub use result_set::{Record, ResultSet};
@@ -0,0 +1,214 @@
+/* Copyright (c) 2021 vesoft inc. All rights reserved.
+ *
+ * This source code is licensed under Apache 2.0 License,
+ * attached with Common Clause Condition 1.0, found in the LICENSES directory.
+ */
+
+use common::types::{DataSet, ErrorCode, Value};
+use graph::types::ExecutionResponse;
+use serde::de::DeserializeOwned;
+
+use crate::value::convert::FromNebulaValue;
+use crate::value::de::from_row;
+use crate::value::error::ResultError;
+
+fn string(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The rows that a statement returned, with their values addressable by
+/// column name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResultSet {
+    column_names: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl ResultSet {
+    /// The result of the response; fails with `ResultError::Execution` if
+    /// the statement failed. Statements that return nothing, like DDL,
+    /// have an empty result
+    pub fn from_response(resp: ExecutionResponse) -> Result<ResultSet, ResultError> {
+        if resp.error_code != ErrorCode::SUCCEEDED {
+            return Err(ResultError::Execution {
+                code: resp.error_code,
+                msg: resp.error_msg.map(string).unwrap_or_default(),
+            });
+        }
+        Ok(resp.data.map(ResultSet::from).unwrap_or_default())
+    }
+
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    /// The number of rows
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Record<'_>> {
+        let column_names = &self.column_names;
+        self.rows.iter().map(move |values| Record {
+            column_names,
+            values,
+        })
+    }
+
+    /// The values of the column `name` in all rows
+    pub fn column<T: FromNebulaValue>(&self, name: &str) -> Result<Vec<T>, ResultError> {
+        let index = self.index(name)?;
+        self.rows()
+            .map(|record| record.get_by_index(index))
+            .collect()
+    }
+
+    /// Deserialize each row into `T`; see `value::de` for how values map
+    /// to serde
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<Vec<T>, ResultError> {
+        self.rows().map(|record| record.deserialize()).collect()
+    }
+
+    fn index(&self, name: &str) -> Result<usize, ResultError> {
+        self.column_names
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| ResultError::NoSuchColumn(name.to_string()))
+    }
+}
+
+impl From<DataSet> for ResultSet {
+    fn from(data: DataSet) -> Self {
+        ResultSet {
+            column_names: data.column_names.into_iter().map(string).collect(),
+            rows: data.rows.into_iter().map(|row| row.values).collect(),
+        }
+    }
+}
+
+/// One row of a `ResultSet`
+#[derive(Clone, Copy, Debug)]
+pub struct Record<'a> {
+    column_names: &'a [String],
+    values: &'a [Value],
+}
+
+impl<'a> Record<'a> {
+    pub fn values(&self) -> &'a [Value] {
+        self.values
+    }
+
+    /// The raw value of the column `name`
+    pub fn value(&self, name: &str) -> Result<&'a Value, ResultError> {
+        let index = self
+            .column_names
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| ResultError::NoSuchColumn(name.to_string()))?;
+        self.value_by_index(index)
+    }
+
+    pub fn value_by_index(&self, index: usize) -> Result<&'a Value, ResultError> {
+        self.values
+            .get(index)
+            .ok_or(ResultError::NoSuchIndex(index))
+    }
+
+    /// The value of the column `name` as a `T`
+    pub fn get<T: FromNebulaValue>(&self, name: &str) -> Result<T, ResultError> {
+        T::from_nebula_value(self.value(name)?)
+    }
+
+    pub fn get_by_index<T: FromNebulaValue>(&self, index: usize) -> Result<T, ResultError> {
+        T::from_nebula_value(self.value_by_index(index)?)
+    }
+
+    /// Deserialize the row into `T`, usually a struct whose fields are
+    /// named like the columns
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, ResultError> {
+        from_row(self.column_names, self.values.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::Row;
+    use serde::Deserialize;
+
+    use super::*;
+
+    fn s(s: &str) -> Value {
+        Value::sVal(s.as_bytes().to_vec())
+    }
+
+    fn response() -> ExecutionResponse {
+        ExecutionResponse {
+            error_code: ErrorCode::SUCCEEDED,
+            data: Some(DataSet {
+                column_names: vec![b"name".to_vec(), b"age".to_vec()],
+                rows: vec![
+                    Row {
+                        values: vec![s("Tim"), Value::iVal(42)],
+                    },
+                    Row {
+                        values: vec![s("Tony"), Value::iVal(36)],
+                    },
+                ],
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn records() {
+        let result = ResultSet::from_response(response()).unwrap();
+        assert_eq!(2, result.len());
+        assert_eq!(
+            &["name".to_string(), "age".to_string()],
+            result.column_names()
+        );
+
+        let first = result.rows().next().unwrap();
+        assert_eq!(Ok("Tim".to_string()), first.get::<String>("name"));
+        assert_eq!(Ok(42), first.get::<i32>("age"));
+        assert_eq!(
+            Err(ResultError::NoSuchColumn("team".to_string())),
+            first.get::<String>("team")
+        );
+        assert_eq!(Ok(vec![42, 36]), result.column::<i64>("age"));
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Player {
+            name: String,
+            age: i64,
+        }
+        let players: Vec<Player> = result.deserialize().unwrap();
+        assert_eq!("Tony", players[1].name);
+    }
+
+    #[test]
+    fn errors() {
+        let resp = ExecutionResponse {
+            error_code: ErrorCode::E_SEMANTIC_ERROR,
+            error_msg: Some(b"TagNotFound: Tag `team' not exist".to_vec()),
+            ..response()
+        };
+        assert_eq!(
+            Err(ResultError::Execution {
+                code: ErrorCode::E_SEMANTIC_ERROR,
+                msg: "TagNotFound: Tag `team' not exist".to_string()
+            }),
+            ResultSet::from_response(resp)
+        );
+
+        let resp = ExecutionResponse {
+            data: None,
+            ..response()
+        };
+        assert!(ResultSet::from_response(resp).unwrap().is_empty());
+    }
+}
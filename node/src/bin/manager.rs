@@ -232,6 +232,10 @@ pub enum Command {
         #[clap(long, short, default_value = "10000")]
         history: usize,
     },
+
+    /// Manage the NebulaGraph spaces of deployments
+    #[clap(subcommand)]
+    Nebula(NebulaCommand),
 }
 
 impl Command {
@@ -494,6 +498,53 @@ pub enum IndexCommand {
     },
 }
 
+#[derive(Clone, Debug, Subcommand)]
+pub enum NebulaCommand {
+    /// Load the entities of a deployment into its NebulaGraph space.
+    ///
+    /// The space is dropped and created again, and then filled with the
+    /// entities as of one block, reading them from the entity tables in
+    /// batches. The deployment must be unassigned while the backfill runs.
+    ///
+    /// A backfill that was interrupted resumes where it left off when the
+    /// command is run again, or when the job that writes to NebulaGraph
+    /// picks it up a few minutes later.
+    ///
+    /// This command may be time-consuming.
+    Backfill {
+        /// The deployment (see `help info`).
+        #[clap(empty_values = false)]
+        deployment: DeploymentSearch,
+        /// The block as of which to load entities. Defaults to the
+        /// latest block of the deployment
+        #[clap(long)]
+        at_block: Option<i32>,
+    },
+    /// Compare the NebulaGraph space of a deployment with its entities.
+    ///
+    /// Entities are compared as of the block up to which changes have been
+    /// written to the space. Every vertex and edge that an entity is
+    /// written as is fetched and compared with the entity, and the number
+    /// of vertices and edges of each type is compared with the number of
+    /// entities. Vertices and edges that should not be there can only be
+    /// noticed through those numbers; `nebula backfill` removes them.
+    ///
+    /// The result is shown in the indexing status of the deployment.
+    Verify {
+        /// The deployment (see `help info`).
+        #[clap(empty_values = false)]
+        deployment: DeploymentSearch,
+        /// Only compare this many entities of each type, taken from
+        /// random positions, instead of all of them
+        #[clap(long)]
+        sample: Option<usize>,
+        /// Write vertices and edges that are missing or differ again. The
+        /// deployment must be unassigned
+        #[clap(long)]
+        fix: bool,
+    },
+}
+
 #[derive(Clone, Debug, Subcommand)]
 pub enum CheckBlockMethod {
     /// The number of the target block
@@ -751,7 +802,7 @@ async fn main() -> anyhow::Result<()> {
 
     if opt.pool_size > 0 && !opt.cmd.use_configured_pool_size() {
         // Override pool size from configuration
-        for shard in config.stores.values_mut() {
+        for shard in config.stores.shards.values_mut() {
             shard.pool_size = PoolSize::Fixed(opt.pool_size);
             for replica in shard.replicas.values_mut() {
                 replica.pool_size = PoolSize::Fixed(opt.pool_size);
@@ -935,7 +986,7 @@ async fn main() -> anyhow::Result<()> {
                     node,
                     offset,
                 } => {
-                    let shards: Vec<_> = ctx.config.stores.keys().cloned().collect();
+                    let shards: Vec<_> = ctx.config.stores.shards.keys().cloned().collect();
                     let (store, primary) = ctx.store_and_primary();
                     commands::copy::create(store, primary, src, shard, shards, node, offset).await
                 }
@@ -1065,6 +1116,26 @@ async fn main() -> anyhow::Result<()> {
             let (store, primary_pool) = ctx.store_and_primary();
             commands::prune::run(store, primary_pool, deployment, history, prune_ratio).await
         }
+        Nebula(cmd) => {
+            use NebulaCommand::*;
+            match cmd {
+                Backfill {
+                    deployment,
+                    at_block,
+                } => {
+                    let (store, primary_pool) = ctx.store_and_primary();
+                    commands::nebula::backfill(store, primary_pool, deployment, at_block).await
+                }
+                Verify {
+                    deployment,
+                    sample,
+                    fix,
+                } => {
+                    let (store, primary_pool) = ctx.store_and_primary();
+                    commands::nebula::verify(store, primary_pool, deployment, sample, fix).await
+                }
+            }
+        }
     }
 }
 
@@ -62,7 +62,7 @@ pub fn pools(config: &Config, nodes: Vec<String>, shard: bool) -> Result<(), Err
     let mut sizes = BTreeMap::new();
     for node in &nodes {
         let mut shard_sizes = BTreeMap::new();
-        for (name, shard) in &config.stores {
+        for (name, shard) in &config.stores.shards {
             let size = shard.pool_size.size_for(node, name)?;
             shard_sizes.insert(name.to_string(), size);
             for (replica_name, replica) in &shard.replicas {
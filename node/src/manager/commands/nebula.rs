@@ -0,0 +1,238 @@
+use std::{
+    io::Write,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use graph::{
+    components::store::{BackfillReporter, DeploymentLocator, NebulaDifference, VerifyReporter},
+    data::subgraph::status::NebulaConsistency,
+    prelude::{anyhow, BlockNumber},
+};
+use graph_store_postgres::{command_support::catalog, connection_pool::ConnectionPool, Store};
+
+use crate::manager::{commands::stats::abbreviate_table_name, deployment::DeploymentSearch};
+
+struct Progress {
+    start: Instant,
+    table_start: Instant,
+    header: bool,
+}
+
+impl Progress {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            table_start: Instant::now(),
+            header: false,
+        }
+    }
+}
+
+fn print_row(entity_type: &str, total_rows: usize, elapsed: Duration) {
+    print!(
+        "\r{:<30} | {:>10} | {:>9}s",
+        abbreviate_table_name(entity_type, 30),
+        total_rows,
+        elapsed.as_secs()
+    );
+    std::io::stdout().flush().ok();
+}
+
+impl BackfillReporter for Progress {
+    fn start(&mut self, block: BlockNumber, resumed: bool) {
+        if resumed {
+            println!("Resume loading entities as of block {block}");
+        } else {
+            println!("Load entities as of block {block}");
+        }
+    }
+
+    fn recreate_space(&mut self, space: &str) {
+        println!("Drop and create space {space}");
+    }
+
+    fn start_entity_type(&mut self, entity_type: &str, total_rows: usize) {
+        if !self.header {
+            println!(
+                "\n{:^30} | {:^10} | {:^11}",
+                "entity type", "entities", "time"
+            );
+            println!("{:-^30}-+-{:-^10}-+-{:-^11}", "", "", "");
+            self.header = true;
+        }
+        print_row(entity_type, total_rows, Duration::from_secs(0));
+        self.table_start = Instant::now();
+    }
+
+    fn batch(&mut self, entity_type: &str, _rows: usize, total_rows: usize, finished: bool) {
+        print_row(entity_type, total_rows, self.table_start.elapsed());
+        if finished {
+            println!();
+        }
+        std::io::stdout().flush().ok();
+    }
+
+    fn finish(&mut self, total_rows: usize) {
+        println!(
+            "Finished loading {total_rows} entities in {}s",
+            self.start.elapsed().as_secs()
+        );
+    }
+}
+
+struct VerifyProgress {
+    start: Instant,
+    table_start: Instant,
+    header: bool,
+    /// The progress row of the current entity type still has to be ended
+    /// before anything else is printed
+    in_row: bool,
+}
+
+impl VerifyProgress {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            table_start: Instant::now(),
+            header: false,
+            in_row: false,
+        }
+    }
+
+    fn end_row(&mut self) {
+        if self.in_row {
+            println!();
+            self.in_row = false;
+        }
+    }
+}
+
+impl VerifyReporter for VerifyProgress {
+    fn start(&mut self, block: BlockNumber, sample: Option<usize>) {
+        match sample {
+            Some(sample) => {
+                println!("Compare up to {sample} entities of each type as of block {block}")
+            }
+            None => println!("Compare all entities as of block {block}"),
+        }
+    }
+
+    fn start_entity_type(&mut self, entity_type: &str) {
+        self.end_row();
+        if !self.header {
+            println!(
+                "\n{:^30} | {:^10} | {:^11}",
+                "entity type", "entities", "time"
+            );
+            println!("{:-^30}-+-{:-^10}-+-{:-^11}", "", "", "");
+            self.header = true;
+        }
+        print_row(entity_type, 0, Duration::from_secs(0));
+        self.table_start = Instant::now();
+        self.in_row = true;
+    }
+
+    fn batch(&mut self, entity_type: &str, _rows: usize, total_rows: usize) {
+        print_row(entity_type, total_rows, self.table_start.elapsed());
+        self.in_row = true;
+    }
+
+    fn difference(&mut self, difference: &NebulaDifference) {
+        self.end_row();
+        println!("  {difference}");
+    }
+
+    fn repair(&mut self, statements: usize) {
+        self.end_row();
+        println!("  repaired with {statements} statements");
+    }
+
+    fn start_count(&mut self) {
+        self.end_row();
+        println!("\nCount vertices and edges");
+    }
+
+    fn finish(&mut self, result: &NebulaConsistency) {
+        self.end_row();
+        println!(
+            "\nCompared {} entities in {}s",
+            result.entities,
+            self.start.elapsed().as_secs()
+        );
+        println!("  missing:            {}", result.missing);
+        println!("  different:          {}", result.different);
+        println!("  repaired:           {}", result.repaired);
+        println!("  count mismatches:   {}", result.count_mismatches);
+        if result.is_consistent() {
+            println!("The space is consistent with the entities");
+        } else if result.count_mismatches > 0 {
+            println!(
+                "The space has vertices or edges that should not be there; \
+                 run `graphman nebula backfill` to rebuild it"
+            );
+        } else {
+            println!("Run with `--fix` to repair the differences");
+        }
+    }
+}
+
+/// Fail if `deployment` is assigned to a node, since changes that it
+/// writes would race with ours
+fn check_unassigned(
+    primary_pool: &ConnectionPool,
+    deployment: &DeploymentLocator,
+) -> Result<(), anyhow::Error> {
+    let conn = catalog::Connection::new(primary_pool.get()?);
+    let site = conn
+        .locate_site(deployment.clone())?
+        .ok_or_else(|| anyhow!("failed to locate site for {deployment}"))?;
+    if let Some(node) = conn.assigned_node(&site)? {
+        return Err(anyhow!(
+            "deployment {deployment} is assigned to {node}; run `graphman unassign` first"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn backfill(
+    store: Arc<Store>,
+    primary_pool: ConnectionPool,
+    search: DeploymentSearch,
+    block: Option<BlockNumber>,
+) -> Result<(), anyhow::Error> {
+    let deployment = search.locate_unique(&primary_pool)?;
+
+    check_unassigned(&primary_pool, &deployment)?;
+
+    println!("backfill {deployment}");
+    let reporter = Box::new(Progress::new());
+    store
+        .subgraph_store()
+        .nebula_backfill(reporter, &deployment, block)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn verify(
+    store: Arc<Store>,
+    primary_pool: ConnectionPool,
+    search: DeploymentSearch,
+    sample: Option<usize>,
+    fix: bool,
+) -> Result<(), anyhow::Error> {
+    let deployment = search.locate_unique(&primary_pool)?;
+    if fix {
+        check_unassigned(&primary_pool, &deployment)?;
+    }
+
+    println!("verify {deployment}");
+    let reporter = Box::new(VerifyProgress::new());
+    store
+        .subgraph_store()
+        .nebula_verify(reporter, &deployment, sample, fix)
+        .await?;
+
+    Ok(())
+}
@@ -15,7 +15,7 @@ use graph_store_postgres::{
     NotificationSender, Shard as ShardName, Store as DieselStore, SubgraphStore,
     SubscriptionManager, PRIMARY_SHARD,
 };
-use nebula_rust::graph_client::{pool_config, connection_pool, session};
+use nebula_rust::graph_client::{connection_pool, session};
 use crate::config::{Config, Shard};
 
 pub struct StoreBuilder {
@@ -95,18 +95,10 @@ impl StoreBuilder {
     ) -> (Arc<SubgraphStore>, HashMap<ShardName, ConnectionPool>) {
         let notification_sender = Arc::new(NotificationSender::new(registry.cheap_clone()));
 
-        fn is_postgres_url(s:&str)->bool{
-            // println!("======={:?}========", s);
-            if s.contains("postgres"){
-                return true;
-            }
-            return false;
-        }
-
         let servers = config
             .stores
+            .shards
             .iter()
-            .filter(|(_, shard)| is_postgres_url(&shard.connection))
             .map(|(name, shard)| ForeignServer::new_from_raw(name.to_string(), &shard.connection))
             .collect::<Result<Vec<_>, _>>()
             .expect("connection url's contain enough detail");
@@ -114,8 +106,8 @@ impl StoreBuilder {
 
         let shards: Vec<_> = config
             .stores
+            .shards
             .iter()
-            .filter(|(_, shard)| is_postgres_url(&shard.connection))
             .map(|(name, shard)| {
                 let logger = logger.new(o!("shard" => name.to_string()));
                 let conn_pool = Self::main_pool(
@@ -142,23 +134,16 @@ impl StoreBuilder {
             })
             .collect();
 
-        // filter out nebula_url
-        let shard_nebula: Vec<_> = config
-            .stores
-            .iter()
-            .filter(|(_, shard)| !is_postgres_url(&shard.connection))
-            .map(|(_, shard)| {
-                &shard.connection
-            })
-            .collect();
-        // println!("================shard_nebula:{:?}==================", shard_nebula.len());
-        let nebula_url = shard_nebula[0].clone();
-        // let nebula_connection = connection_pool::ConnectionPool_nebula::new_pool(nebula_url.as_str());
-        // println!("===============conf===============");
-        // nebula_connection.get_config();
-
-
-
+        let nebula_conf = config
+            .nebula_store()
+            .nebula_config(node)
+            .expect("cannot determine the pool size for the NebulaGraph store");
+        info!(
+            logger,
+            "Connecting to NebulaGraph";
+            "addresses" => nebula_conf.pool.addresses.join(","),
+            "conn_pool_size" => nebula_conf.pool.max_connection_pool_size
+        );
 
         let pools: HashMap<_, _> = HashMap::from_iter(
             shards
@@ -173,7 +158,7 @@ impl StoreBuilder {
             notification_sender,
             fork_base,
             registry,
-            nebula_url,
+            nebula_conf,
         ));
 
         (store, pools)
@@ -15,7 +15,7 @@ use graph_store_postgres::{
     NotificationSender, Shard as ShardName, Store as DieselStore, SubgraphStore,
     SubscriptionManager, PRIMARY_SHARD,
 };
-use nebula_rust::graph_client::{pool_config, connection_pool, session};
+use nebula_rust::graph_client::{connection_pool, session};
 
 use crate::config::{Config, Shard};
 use tokio::*;
@@ -31,28 +31,12 @@ impl StoreBuilder_nebula{
         logger: &Logger,
         config: &Config,
     ) -> Self{
-        // 
-        let primary_shard = config.primary_store_nebula().clone();
-        // root:root@localhost:9669/basketballplayer
-        let conn_info = primary_shard.connection.as_str();
-        let v:Vec<&str> = conn_info.split('@').collect();
-        let v2:Vec<&str> = v[1].split('/').collect();
-        let add = String::from(v2[0]);
-        let v3:Vec<&str> = v[0].split(':').collect();
-        let username = String::from(v3[0]);
-        let password = String::from(v3[1]);
+        let conf = config
+            .nebula_store()
+            .nebula_config(&config.node)
+            .expect("cannot determine the pool size for the NebulaGraph store");
 
-        let mut conf = pool_config::PoolConfig::new();
-        conf.min_connection_pool_size(2)
-            .max_connection_pool_size(10)
-            .address(add)
-            .set_username(username)
-            .set_password(password);
-    
-        // println!("===============PoolConfig============");
-        // println!("{:?}", conf);
-
-        let pool = connection_pool::ConnectionPool_nebula::new(&conf);
+        let pool = connection_pool::ConnectionPool_nebula::new(&conf.pool);
         pool.create_new_connection().await;
         info!(logger, "Successfully connecting to NebulaGraph!");
         // let session = pool.get_session("root", "nebula", true).await.unwrap();
@@ -48,6 +48,29 @@ pub fn parse_graphql_request(body: &Bytes) -> Result<Query, GraphQLServerError>
     Ok(Query::new(document, variables))
 }
 
+/// The nGQL statement in the `query` field of the body of a request to the
+/// `/nebula` route of a deployment
+pub fn parse_nebula_request(body: &Bytes) -> Result<String, GraphQLServerError> {
+    let json: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| GraphQLServerError::ClientError(format!("{}", e)))?;
+
+    json.as_object()
+        .ok_or_else(|| {
+            GraphQLServerError::ClientError(String::from("Request data is not an object"))
+        })?
+        .get("query")
+        .ok_or_else(|| {
+            GraphQLServerError::ClientError(String::from(
+                "The \"query\" field is missing in request data",
+            ))
+        })?
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            GraphQLServerError::ClientError(String::from("The \"query\" field is not a string"))
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use graphql_parser;
@@ -59,7 +82,7 @@ mod tests {
         prelude::*,
     };
 
-    use super::parse_graphql_request;
+    use super::{parse_graphql_request, parse_nebula_request};
 
     lazy_static! {
         static ref TARGET: QueryTarget = QueryTarget::Name(
@@ -106,6 +129,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn accepts_nebula_statements() {
+        let request = parse_nebula_request(&hyper::body::Bytes::from(
+            "{\"query\": \"GO FROM \\\"a\\\" OVER `Transfer` YIELD dst(edge)\"}",
+        ));
+        assert_eq!(
+            "GO FROM \"a\" OVER `Transfer` YIELD dst(edge)",
+            request.expect("Should accept nGQL statements")
+        );
+
+        let request = parse_nebula_request(&hyper::body::Bytes::from("{\"query\": 5}"));
+        request.expect_err("Should reject a non-string query field");
+    }
+
     #[test]
     fn accepts_null_variables() {
         let request = parse_graphql_request(&hyper::body::Bytes::from(
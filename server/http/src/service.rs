@@ -15,7 +15,7 @@ use http::header::{
 use hyper::service::Service;
 use hyper::{Body, Method, Request, Response, StatusCode};
 
-use crate::request::parse_graphql_request;
+use crate::request::{parse_graphql_request, parse_nebula_request};
 
 pub type GraphQLServiceResult = Result<Response<Body>, GraphQLServerError>;
 /// An asynchronous response to a GraphQL request.
@@ -185,6 +185,57 @@ where
         Ok(result.as_http_response())
     }
 
+    async fn handle_nebula_query_by_name(
+        self,
+        subgraph_name: String,
+        request: Request<Body>,
+    ) -> GraphQLServiceResult {
+        let version = self.resolve_api_version(&request)?;
+        let subgraph_name = SubgraphName::new(subgraph_name.as_str()).map_err(|()| {
+            GraphQLServerError::ClientError(format!("Invalid subgraph name {:?}", subgraph_name))
+        })?;
+
+        self.handle_nebula_query(
+            QueryTarget::Name(subgraph_name, version),
+            request.into_body(),
+        )
+        .await
+    }
+
+    async fn handle_nebula_query_by_id(
+        self,
+        id: String,
+        request: Request<Body>,
+    ) -> GraphQLServiceResult {
+        let version = self.resolve_api_version(&request)?;
+        let id = DeploymentHash::new(id).map_err(|id| {
+            GraphQLServerError::ClientError(format!("Invalid subgraph id `{}`", id))
+        })?;
+
+        self.handle_nebula_query(QueryTarget::Deployment(id, version), request.into_body())
+            .await
+    }
+
+    /// Runs the nGQL statement in `request_body` against the NebulaGraph
+    /// space of the deployment `target`
+    async fn handle_nebula_query(
+        self,
+        target: QueryTarget,
+        request_body: Body,
+    ) -> GraphQLServiceResult {
+        let body = hyper::body::to_bytes(request_body)
+            .map_err(|_| GraphQLServerError::InternalError("Failed to read request body".into()))
+            .await?;
+        let statement = parse_nebula_request(&body)?;
+
+        let result = self
+            .graphql_runner
+            .clone()
+            .run_nebula_query(statement, target)
+            .await;
+        Ok(result.as_http_response())
+    }
+
     // Handles OPTIONS requests
     fn handle_graphql_options(&self, _request: Request<Body>) -> GraphQLServiceResponse {
         async {
@@ -260,6 +311,29 @@ where
                 self.handle_temp_redirect(dest).boxed()
             }
 
+            // Before the routes for GraphQL, which would take
+            // `<name>/nebula` for the name of a subgraph
+            (Method::POST, &["subgraphs", "id", subgraph_id, "nebula"]) => self
+                .handle_nebula_query_by_id(subgraph_id.to_owned(), req)
+                .boxed(),
+            (Method::POST, &["subgraphs", "name", subgraph_name, "nebula"]) => self
+                .handle_nebula_query_by_name(subgraph_name.to_owned(), req)
+                .boxed(),
+            (Method::POST, ["subgraphs", "name", part1, part2, "nebula"]) => {
+                let subgraph_name = format!("{}/{}", part1, part2);
+                self.handle_nebula_query_by_name(subgraph_name, req).boxed()
+            }
+            (Method::POST, ["subgraphs", "network", part1, part2, "nebula"]) => {
+                let subgraph_name = format!("network/{}/{}", part1, part2);
+                self.handle_nebula_query_by_name(subgraph_name, req).boxed()
+            }
+            (Method::OPTIONS, ["subgraphs", "id", _, "nebula"])
+            | (Method::OPTIONS, ["subgraphs", "name", _, "nebula"])
+            | (Method::OPTIONS, ["subgraphs", "name", _, _, "nebula"])
+            | (Method::OPTIONS, ["subgraphs", "network", _, _, "nebula"]) => {
+                self.handle_graphql_options(req)
+            }
+
             (Method::POST, &["subgraphs", "id", subgraph_id]) => {
                 self.handle_graphql_query_by_id(subgraph_id.to_owned(), req)
             }
@@ -405,6 +479,16 @@ mod tests {
             unreachable!();
         }
 
+        async fn run_nebula_query(
+            self: Arc<Self>,
+            statement: String,
+            _target: QueryTarget,
+        ) -> QueryResults {
+            QueryResults::from(Object::from_iter(
+                vec![(String::from("statement"), r::Value::String(statement))].into_iter(),
+            ))
+        }
+
         fn load_manager(&self) -> Arc<LoadManager> {
             unimplemented!()
         }
@@ -477,4 +561,31 @@ mod tests {
             .expect("Query result field \"name\" is not a string");
         assert_eq!(name, "Jordi".to_string());
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn posting_nebula_statements_runs_them() {
+        let logger = Logger::root(slog::Discard, o!());
+        let graphql_runner = Arc::new(TestGraphQlRunner);
+
+        let node_id = NodeId::new("test").unwrap();
+        let mut service = GraphQLService::new(logger, graphql_runner, 8001, node_id);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("http://localhost:8000/subgraphs/name/user/accounts/nebula")
+            .body(Body::from("{\"query\": \"MATCH (v) RETURN v LIMIT 1\"}"))
+            .unwrap();
+
+        let response = tokio::spawn(service.call(request))
+            .await
+            .unwrap()
+            .expect("Should return a response");
+        let data = test_utils::assert_successful_response(response);
+        let statement = data
+            .get("statement")
+            .expect("Query result data has no \"statement\" field")
+            .as_str()
+            .expect("Query result field \"statement\" is not a string");
+        assert_eq!(statement, "MATCH (v) RETURN v LIMIT 1");
+    }
 }
@@ -77,6 +77,14 @@ impl GraphQlRunner for TestGraphQlRunner {
         unreachable!();
     }
 
+    async fn run_nebula_query(
+        self: Arc<Self>,
+        _statement: String,
+        _target: QueryTarget,
+    ) -> QueryResults {
+        unimplemented!();
+    }
+
     fn load_manager(&self) -> Arc<LoadManager> {
         unimplemented!()
     }
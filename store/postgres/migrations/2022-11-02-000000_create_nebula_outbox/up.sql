@@ -0,0 +1,10 @@
+create table subgraphs.nebula_outbox (
+       id           bigserial primary key,
+       deployment   int not null
+                    references subgraphs.subgraph_deployment(id) on delete cascade,
+       block_number int not null,
+       statements   text[] not null
+);
+
+create index nebula_outbox_deployment_id
+    on subgraphs.nebula_outbox(deployment, id);
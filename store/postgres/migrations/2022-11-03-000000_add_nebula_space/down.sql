@@ -0,0 +1,3 @@
+drop table subgraphs.nebula_dropped_space;
+alter table subgraphs.subgraph_deployment
+      drop column nebula_space;
@@ -0,0 +1,10 @@
+-- The NebulaGraph space of a deployment; null for deployments whose
+-- space is named after their deployment hash
+alter table subgraphs.subgraph_deployment
+      add column nebula_space text;
+
+-- Spaces of removed deployments that still have to be dropped
+create table subgraphs.nebula_dropped_space (
+       space      text primary key,
+       dropped_at timestamptz not null default now()
+);
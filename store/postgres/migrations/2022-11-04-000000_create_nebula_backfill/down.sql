@@ -0,0 +1 @@
+drop table subgraphs.nebula_backfill;
@@ -0,0 +1,13 @@
+-- The progress of `graphman nebula backfill`, one row per entity type
+create table subgraphs.nebula_backfill (
+       deployment   int not null
+                    references subgraphs.subgraph_deployment(id) on delete cascade,
+       entity_type  text not null,
+       block_number int not null,
+       -- The id of the last entity that was loaded; null if loading has
+       -- not started yet
+       last_id      text,
+       entity_count bigint not null default 0,
+       finished_at  timestamptz,
+       primary key (deployment, entity_type)
+);
@@ -0,0 +1,13 @@
+-- The result of the last `graphman nebula verify` of each deployment
+create table subgraphs.nebula_verification (
+       deployment       int primary key
+                        references subgraphs.subgraph_deployment(id) on delete cascade,
+       block_number     int not null,
+       complete         boolean not null,
+       entities         bigint not null,
+       count_mismatches int not null,
+       missing          bigint not null,
+       different        bigint not null,
+       repaired         bigint not null,
+       verified_at      timestamptz not null default now()
+);
@@ -0,0 +1,6 @@
+-- Deployments whose NebulaGraph space still has to be created
+create table subgraphs.nebula_new_space (
+       deployment int primary key
+                  references subgraphs.subgraph_deployment(id) on delete cascade,
+       created_at timestamptz not null default now()
+);
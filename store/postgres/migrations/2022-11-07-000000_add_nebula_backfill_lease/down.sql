@@ -0,0 +1 @@
+alter table subgraphs.nebula_backfill drop column locked_until;
@@ -0,0 +1,3 @@
+-- Until when the process that runs a backfill holds on to it; null if no
+-- process runs it. See `nebula::backfill::claim`
+alter table subgraphs.nebula_backfill add column locked_until timestamptz;
@@ -15,7 +15,7 @@ use diesel::{
 };
 use graph::data::subgraph::{
     schema::{DeploymentCreate, SubgraphManifestEntity},
-    SubgraphFeature,
+    GraphMapping, SubgraphFeature,
 };
 use graph::prelude::{
     anyhow, bigdecimal::ToPrimitive, hex, web3::types::H256, BigDecimal, BlockNumber, BlockPtr,
@@ -84,6 +84,8 @@ table! {
         current_reorg_depth -> Integer,
         max_reorg_depth -> Integer,
         firehose_cursor -> Nullable<Text>,
+        /// The NebulaGraph space that mirrors the deployment
+        nebula_space -> Nullable<Text>,
     }
 }
 
@@ -113,6 +115,8 @@ table! {
         /// Parent of the smallest start block from the manifest
         start_block_number -> Nullable<Integer>,
         start_block_hash -> Nullable<Binary>,
+        /// The `graph` section of the manifest as JSON
+        graph_mapping -> Nullable<Text>,
     }
 }
 
@@ -260,6 +264,40 @@ pub fn manifest_info(
         .map(|schema| (schema, description, repository, spec_version))
 }
 
+/// Return the mapping from the `graph` section of the manifest, or `None`
+/// if the manifest does not have one
+pub fn graph_mapping(conn: &PgConnection, site: &Site) -> Result<Option<GraphMapping>, StoreError> {
+    use subgraph_manifest as sm;
+
+    let mapping: Option<String> = sm::table
+        .select(sm::graph_mapping)
+        .filter(sm::id.eq(site.id))
+        .first(conn)?;
+    mapping
+        .map(|mapping| {
+            GraphMapping::from_json(&mapping).map_err(|e| {
+                constraint_violation!(
+                    "invalid graph mapping for deployment {}: {}",
+                    site.deployment,
+                    e
+                )
+            })
+        })
+        .transpose()
+}
+
+/// Return the name of the NebulaGraph space of the deployment, or `None`
+/// if the deployment was created before spaces were recorded
+pub fn nebula_space(conn: &PgConnection, site: &Site) -> Result<Option<String>, StoreError> {
+    use subgraph_deployment as d;
+
+    d::table
+        .select(d::nebula_space)
+        .filter(d::id.eq(site.id))
+        .first(conn)
+        .map_err(StoreError::from)
+}
+
 #[allow(dead_code)]
 pub fn features(conn: &PgConnection, site: &Site) -> Result<BTreeSet<SubgraphFeature>, StoreError> {
     use subgraph_manifest as sm;
@@ -889,6 +927,7 @@ pub fn create_deployment(
                 repository,
                 features,
                 schema,
+                graph_mapping,
             },
         earliest_block,
         graft_base,
@@ -915,6 +954,9 @@ pub fn create_deployment(
         d::graft_block_hash.eq(b(&graft_block)),
         d::graft_block_number.eq(n(&graft_block)),
         d::debug_fork.eq(debug_fork.as_ref().map(|s| s.as_str())),
+        // Every deployment, and in particular every copy of a deployment,
+        // gets a space of its own
+        d::nebula_space.eq(site.namespace.as_str()),
     );
 
     let graph_node_version_id = GraphNodeVersion::create_or_get(conn)?;
@@ -932,6 +974,7 @@ pub fn create_deployment(
         m::use_bytea_prefix.eq(true),
         m::start_block_hash.eq(b(&earliest_block)),
         m::start_block_number.eq(earliest_block_number),
+        m::graph_mapping.eq(graph_mapping),
     );
 
     if exists && replace {
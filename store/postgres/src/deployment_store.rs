@@ -8,6 +8,7 @@ use graph::blockchain::block_stream::FirehoseCursor;
 use graph::components::store::{EntityKey, EntityType, PruneReporter, StoredDynamicDataSource};
 use graph::components::versions::VERSIONS;
 use graph::data::query::Trace;
+use graph::data::subgraph::status::{NebulaConsistency, NebulaSync};
 use graph::data::subgraph::{status, SPEC_VERSION_0_0_6};
 use graph::prelude::{
     tokio, ApiVersion, CancelHandle, CancelToken, CancelableError, EntityOperation, PoolWaitStats,
@@ -26,26 +27,31 @@ use std::str::FromStr;
 use std::sync::{atomic::AtomicUsize, Arc, Mutex};
 use std::time::Instant;
 
-use graph::components::store::EntityCollection;
+use graph::components::store::{
+    BackfillReporter, EntityCollection, NebulaDifference, NebulaRows, NeighborsQuery, PathsQuery,
+    VerifyReporter,
+};
 use graph::components::subgraph::{ProofOfIndexingFinisher, ProofOfIndexingVersion};
 use graph::constraint_violation;
 use graph::data::subgraph::schema::{DeploymentCreate, SubgraphError, POI_OBJECT};
+use graph::data::subgraph::GraphMapping;
 use graph::prelude::{
     anyhow, debug, info, o, warn, web3, ApiSchema, AttributeNames, BlockNumber, BlockPtr,
-    CheapClone, DeploymentHash, DeploymentState, Entity, EntityModification, EntityQuery, Error,
-    Logger, QueryExecutionError, Schema, StopwatchMetrics, StoreError, StoreEvent, UnfailOutcome,
-    Value, ENV_VARS,
+    CheapClone, DeploymentHash, DeploymentState, Entity, EntityFilter, EntityModification,
+    EntityOrder, EntityQuery, EntityRange, Error, Logger, MetricsRegistry, QueryExecutionError,
+    Schema, StopwatchMetrics, StoreError, StoreEvent, UnfailOutcome, Value, BLOCK_NUMBER_MAX,
+    ENV_VARS,
 };
-use graph_graphql::prelude::api_schema;
+use graph_graphql::prelude::{add_traversal_fields, api_schema};
 use web3::types::Address;
-use nebula_rust::graph_client::{pool_config, connection_pool, connection::Connection as Connection_nebula,session, nebula_schema::{ColType, Tag, DataType, InsertTagQuery, InsertEdgeQueryWithRank}};
-use rand::Rng;
 
 use crate::block_range::block_number;
 use crate::catalog;
 use crate::deployment;
 use crate::detail::ErrorDetail;
 use crate::dynds::DataSourcesTable;
+use crate::nebula::{self, GraphLayout, GraphWrites, NebulaConfig, NebulaSink};
+use crate::primary::DeploymentId;
 use crate::relational::{Layout, LayoutCache, SqlName, Table};
 use crate::relational_queries::FromEntityData;
 use crate::{connection_pool::ConnectionPool, detail};
@@ -103,10 +109,14 @@ pub struct StoreInner {
     /// the entities module
     pub(crate) layout_cache: LayoutCache,
 
-    // pool_nebula: connection_test::ConnectionPool,
-    conf_nebula: pool_config::PoolConfig,
-
+    /// A cache for the layout of the NebulaGraph space of a deployment.
+    /// Since it is derived from the subgraph schema alone, it never changes.
+    /// It is keyed by the id of the deployment rather than its hash, since
+    /// copies of a deployment share the hash but not the space
+    graph_layout_cache: Mutex<LruCache<DeploymentId, Arc<GraphLayout>>>,
 
+    /// The task that all writes to NebulaGraph go through
+    nebula_sink: NebulaSink,
 }
 
 /// Storage of the data for individual deployments. Each `DeploymentStore`
@@ -129,8 +139,8 @@ impl DeploymentStore {
         pool: ConnectionPool,
         read_only_pools: Vec<ConnectionPool>,
         mut pool_weights: Vec<usize>,
-        nebula_url: String,
-        // pool_nebula: connection_test::ConnectionPool
+        conf_nebula: NebulaConfig,
+        registry: Arc<dyn MetricsRegistry>,
     ) -> Self {
         // Create a store-specific logger
         let logger = logger.new(o!("component" => "Store"));
@@ -159,8 +169,7 @@ impl DeploymentStore {
 
 
         // init nebula connection configuration
-        // let pool_nebula = connection_pool::ConnectionPool_nebula::new_pool(nebula_url.as_str());
-        let conf_nebula = pool_config::PoolConfig::new_conf(nebula_url.as_str());
+        let nebula_sink = NebulaSink::new(&logger, conf_nebula, registry, pool.shard.clone());
 
 
         // Create the store
@@ -172,7 +181,8 @@ impl DeploymentStore {
             conn_round_robin_counter: AtomicUsize::new(0),
             subgraph_cache: Mutex::new(LruCache::with_capacity(100)),
             layout_cache: LayoutCache::new(ENV_VARS.store.query_stats_refresh_interval),
-            conf_nebula,
+            graph_layout_cache: Mutex::new(LruCache::with_capacity(100)),
+            nebula_sink,
         };
 
         DeploymentStore(Arc::new(store))
@@ -186,22 +196,16 @@ impl DeploymentStore {
         graft_base: Option<Arc<Layout>>,
         replace: bool,
     ) -> Result<(), StoreError> {
+        let mapping = deployment
+            .manifest
+            .graph_mapping
+            .as_deref()
+            .map(GraphMapping::from_json)
+            .transpose()
+            .map_err(|e| StoreError::Unknown(anyhow!("invalid graph mapping: {}", e)))?;
 
         let conn = self.get_conn()?;
-
-        let conf_nebula = &self.conf_nebula;
-        // get nebula session
-        let conn_nebula = Connection_nebula::new_from_conf(conf_nebula).await.unwrap();
-
-        let resp = conn_nebula.authenticate(conf_nebula.username.clone().as_str(), conf_nebula.password.clone().as_str()).await.unwrap();
-
-        let session_id = resp.session_id.unwrap();
-
-        // CREATE SPACE `token_transfer` (partition_num = 1, replica_factor = 1, vid_type = FIXED_STRING(50))
-
-        let mut tables: Vec<Arc<Table>> = Vec::new();
-
-        let res:Result<(), StoreError> = conn.transaction(|| -> Result<_, StoreError> {
+        conn.transaction(|| -> Result<_, StoreError> {
             let exists = deployment::exists(&conn, &site)?;
 
             // Create (or update) the metadata. Update only happens in tests
@@ -215,8 +219,6 @@ impl DeploymentStore {
                 conn.batch_execute(&query)?;
 
                 let layout = Layout::create_relational_schema(&conn, site.clone(), schema)?;
-
-
                 // See if we are grafting and check that the graft is permissible
                 if let Some(base) = graft_base {
                     let errors = layout.can_copy_from(&base);
@@ -236,73 +238,36 @@ impl DeploymentStore {
                     conn.batch_execute(&DataSourcesTable::new(site.namespace.clone()).as_ddl())?;
                 }
 
-
-                for (_, v) in layout.tables{
-                    tables.push(v);
-                }
+                // The space that mirrors the deployment in NebulaGraph is
+                // created by `replay_nebula_outbox`, so that a failure to
+                // create it is retried rather than leaving the deployment
+                // without a space. Building the layout here still rejects
+                // graph mappings that do not fit the schema
+                GraphLayout::new(&layout, schema, mapping.as_ref(), site.namespace.as_str())?;
+                nebula::outbox::new_space(&conn, &site)?;
             }
             Ok(())
-        });
-
-        for table in tables{
-            
-            if table.object.to_string() == String::from("Poi$"){
-                continue;
-            }
-
-            let mut all_queries = String::from("");
-
-            all_queries += conn_nebula.get_create_space_query(table.object.as_str(), 1, 1, true, 50, "").as_str();
-
-            // create tag
-            // only create one property (id)
-            let space_name = table.object.as_str();
-            let col_type = ColType::Tag;
-            let mut tag_name = String::from(table.object.as_str()) + "_";
-            tag_name += ColType::Tag.to_string().as_str();
-            let tag_name = tag_name.as_str();
-            let comment = "";
-            let mut tags: Vec<Tag> = Vec::new();
-            for column in &table.columns{
-                if column.name.as_str()!="id"{
-                    continue;
-                }
-                let property_name = column.name.as_str();
-                let data_type = column.column_type.to_nebula_type();
-                let allow_null = false;
-                let defaults = "";
-                let comment = "";
-                let tag_id = Tag::new(property_name, data_type, allow_null, defaults, comment);
-                let tag_value = Tag::new("value", DataType::Int64, allow_null, "", comment);
-                tags.push(tag_id);
-                tags.push(tag_value);
-            }
-
-            all_queries += conn_nebula.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags).as_str();
-
-            // create edge (custom)
-            if table.object.as_str() == "TokenTransfer"{
-                let space_name = table.object.as_str();
-                let col_type = ColType::Edge;
-                let tag_name = "tx";
-                let comment = "";
-                let mut tags: Vec<Tag> = Vec::new();
-                tags.push(Tag::new("from_account", DataType::String, false, "", ""));
-                tags.push(Tag::new("to_account", DataType::String, false, "", ""));
-                tags.push(Tag::new("transactions", DataType::String, false, "", ""));
-                all_queries += conn_nebula.get_create_tag_or_edge(space_name, col_type, tag_name, comment, tags).as_str();
-            }
+        })
+    }
 
-            println!("create table:{:?}",all_queries);
-            let _resp = conn_nebula.execute(session_id, all_queries.as_str(), ).await.unwrap();
-            println!("create table:{:?}",_resp);
-            conn_nebula.signout(session_id).await;
-            std::thread::sleep(std::time::Duration::from_millis(5000));
-        }
-        let res = Ok(());
-        res
+    /// Create the space for `graph` with all its tags and edge types.
+    /// Since all of them are created only if they do not exist yet, this
+    /// can be repeated
+    async fn create_nebula_space(&self, graph: &GraphLayout) -> Result<(), StoreError> {
+        let space = graph.space.clone();
+        // NebulaGraph creates spaces, tags and edge types asynchronously,
+        // and we have to wait for them before we can use them
+        self.nebula_sink
+            .execute(vec![graph.create_space_query(self.nebula_sink.space())])
+            .await?;
+        self.nebula_sink
+            .wait_for_schema(space.clone(), vec![], vec![])
+            .await?;
+        self.nebula_sink.execute(graph.as_ddl()).await?;
+        let tags = graph.tags.values().map(|tag| tag.name.clone()).collect();
+        let edges = graph.edges.iter().map(|edge| edge.name.clone()).collect();
+        self.nebula_sink.wait_for_schema(space, tags, edges).await
     }
-        
 
     pub(crate) fn load_deployment(
         &self,
@@ -316,7 +281,14 @@ impl DeploymentStore {
     // is not reversible
     pub(crate) fn drop_deployment(&self, site: &Site) -> Result<(), StoreError> {
         let conn = self.get_conn()?;
+        self.graph_layout_cache.lock().unwrap().remove(&site.id);
         conn.transaction(|| {
+            // The space is dropped by `replay_nebula_outbox`. Spaces that
+            // are named after the deployment hash might be shared with
+            // copies of the deployment and are left alone
+            if let Some(space) = crate::deployment::nebula_space(&conn, site)? {
+                nebula::outbox::drop_space(&conn, &space)?;
+            }
             crate::deployment::drop_schema(&conn, &site.namespace)?;
             if !site.schema_version.private_data_sources() {
                 crate::dynds::shared::drop(&conn, &site.deployment)?;
@@ -518,7 +490,7 @@ impl DeploymentStore {
     fn subgraph_info_with_conn(
         &self,
         conn: &PgConnection,
-        site: &Site,
+        site: &Arc<Site>,
     ) -> Result<SubgraphInfo, StoreError> {
         if let Some(info) = self.subgraph_cache.lock().unwrap().get(&site.deployment) {
             return Ok(info.clone());
@@ -532,6 +504,12 @@ impl DeploymentStore {
 
         let debug_fork = deployment::debug_fork(conn, &site.deployment)?;
 
+        // The edge types that can be traversed with the `neighbors` and
+        // `paths` fields of the API schema
+        let traversal_edges = self
+            .graph_layout_for_schema(conn, site.clone(), &input_schema)?
+            .traversal_edges();
+
         // Generate an API schema for the subgraph and make sure all types in the
         // API schema have a @subgraphId directive as well
         let mut api: HashMap<ApiVersion, Arc<ApiSchema>> = HashMap::new();
@@ -541,6 +519,7 @@ impl DeploymentStore {
             let mut schema = input_schema.clone();
             schema.document =
                 api_schema(&schema.document).map_err(|e| StoreError::Unknown(e.into()))?;
+            add_traversal_fields(&mut schema.document, &traversal_edges);
             schema.add_subgraph_id_directives(site.deployment.clone());
             api.insert(api_version, Arc::new(ApiSchema::from_api_schema(schema)?));
         }
@@ -569,7 +548,57 @@ impl DeploymentStore {
         Ok(cache.get(&site.deployment).unwrap().clone())
     }
 
-    pub(crate) fn subgraph_info(&self, site: &Site) -> Result<SubgraphInfo, StoreError> {
+    /// The layout of the NebulaGraph space for a deployment if it has been
+    /// loaded already
+    pub(crate) fn cached_graph_layout(&self, site: &Site) -> Option<Arc<GraphLayout>> {
+        self.graph_layout_cache
+            .lock()
+            .unwrap()
+            .get(&site.id)
+            .cloned()
+    }
+
+    /// Return the layout of the NebulaGraph space for a deployment
+    pub(crate) fn graph_layout(
+        &self,
+        conn: &PgConnection,
+        site: Arc<Site>,
+    ) -> Result<Arc<GraphLayout>, StoreError> {
+        if let Some(graph) = self.cached_graph_layout(&site) {
+            return Ok(graph);
+        }
+
+        let schema = self.subgraph_info_with_conn(conn, &site)?.input;
+        self.graph_layout_for_schema(conn, site, &schema)
+    }
+
+    /// Like `graph_layout`, but for callers that already have the input
+    /// schema of the deployment; `subgraph_info_with_conn` needs the graph
+    /// layout and can therefore not be used to get the schema
+    fn graph_layout_for_schema(
+        &self,
+        conn: &PgConnection,
+        site: Arc<Site>,
+        schema: &Schema,
+    ) -> Result<Arc<GraphLayout>, StoreError> {
+        if let Some(graph) = self.cached_graph_layout(&site) {
+            return Ok(graph);
+        }
+
+        let mapping = deployment::graph_mapping(conn, &site)?;
+        let layout = self.layout(conn, site.clone())?;
+        // Deployments from before spaces were recorded use their hash
+        let space =
+            deployment::nebula_space(conn, &site)?.unwrap_or_else(|| site.deployment.to_string());
+        let graph = Arc::new(GraphLayout::new(&layout, schema, mapping.as_ref(), &space)?);
+        self.graph_layout_cache
+            .lock()
+            .unwrap()
+            .insert(site.id, graph.clone());
+        Ok(graph)
+    }
+
+    pub(crate) fn subgraph_info(&self, site: &Arc<Site>) -> Result<SubgraphInfo, StoreError> {
         if let Some(info) = self.subgraph_cache.lock().unwrap().get(&site.deployment) {
             return Ok(info.clone());
         }
@@ -641,6 +670,7 @@ impl DeploymentStore {
         delete from subgraphs.subgraph_manifest;
         delete from subgraphs.copy_table_state;
         delete from subgraphs.copy_state;
+        delete from subgraphs.nebula_dropped_space;
         delete from active_copies;
     ";
 
@@ -650,6 +680,606 @@ impl DeploymentStore {
         Ok(())
     }
 
+    /// The deployments whose NebulaGraph space has not been created yet
+    pub(crate) async fn new_nebula_spaces(&self) -> Result<Vec<DeploymentId>, StoreError> {
+        self.with_conn(|conn, _| nebula::outbox::new_spaces(conn).map_err(Into::into))
+            .await
+    }
+
+    /// Drop the spaces of removed deployments and create the spaces of
+    /// `new_spaces`, the deployments returned by `new_nebula_spaces`,
+    /// then write the mutations waiting in the NebulaGraph outbox, one
+    /// deployment at a time and in the order in which they were recorded.
+    /// When writing fails for a deployment, its remaining mutations stay
+    /// in the outbox and are retried the next time this runs; the same
+    /// goes for spaces. Deployments that are being backfilled are skipped
+    /// until the backfill has finished. No new work is started after
+    /// `deadline`. Returns the number of blocks written
+    pub(crate) async fn replay_nebula_outbox(
+        &self,
+        logger: &Logger,
+        new_spaces: Vec<Site>,
+        deadline: Instant,
+    ) -> Result<usize, StoreError> {
+        const BATCH_SIZE: i64 = 100;
+
+        let spaces = self
+            .with_conn(|conn, _| nebula::outbox::dropped_spaces(conn).map_err(Into::into))
+            .await?;
+        for space in spaces {
+            let query = nebula::drop_space_query(&space)?;
+            if let Err(e) = self.nebula_sink.execute(vec![query]).await {
+                warn!(logger, "Dropping NebulaGraph space failed, will retry";
+                      "space" => space.as_str(),
+                      "error" => e.to_string());
+                continue;
+            }
+            info!(logger, "Dropped NebulaGraph space"; "space" => space.as_str());
+            self.with_conn(move |conn, _| {
+                nebula::outbox::space_dropped(conn, &space).map_err(Into::into)
+            })
+            .await?;
+        }
+
+        // The mutations of deployments whose space could not be created
+        // are held back by `nebula::outbox::pending`
+        for site in new_spaces {
+            let site = Arc::new(site);
+            let store = self.clone();
+            let site2 = site.clone();
+            let graph = self
+                .with_conn(move |conn, _| store.graph_layout(conn, site2).map_err(Into::into))
+                .await?;
+            if let Err(e) = self.create_nebula_space(&graph).await {
+                warn!(logger, "Creating NebulaGraph space failed, will retry";
+                      "sgd" => site.namespace.as_str(),
+                      "space" => graph.space.as_str(),
+                      "error" => e.to_string());
+                continue;
+            }
+            info!(logger, "Created NebulaGraph space";
+                  "sgd" => site.namespace.as_str(),
+                  "space" => graph.space.as_str());
+            self.with_conn(move |conn, _| {
+                nebula::outbox::space_created(conn, &site).map_err(Into::into)
+            })
+            .await?;
+        }
+
+        let deployments = self
+            .with_conn(|conn, _| nebula::outbox::deployments(conn).map_err(Into::into))
+            .await?;
+
+        let mut written = 0;
+        'deployments: for deployment in deployments {
+            while Instant::now() < deadline {
+                let entries = self
+                    .with_conn(move |conn, _| {
+                        nebula::outbox::pending(conn, deployment, BATCH_SIZE).map_err(Into::into)
+                    })
+                    .await?;
+                if entries.is_empty() {
+                    continue 'deployments;
+                }
+
+                for entry in entries {
+                    if let Err(e) = self.nebula_sink.execute(entry.statements).await {
+                        warn!(logger, "Writing to NebulaGraph failed, will retry";
+                              "sgd" => deployment.to_string(),
+                              "block" => entry.block_number,
+                              "error" => e.to_string());
+                        continue 'deployments;
+                    }
+                    let id = entry.id;
+                    self.with_conn(move |conn, _| {
+                        nebula::outbox::acknowledge(conn, id).map_err(Into::into)
+                    })
+                    .await?;
+                    written += 1;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    pub(crate) fn nebula_sync_status(&self, site: &Site) -> Result<NebulaSync, StoreError> {
+        let conn = self.get_conn()?;
+        nebula::outbox::sync_status(&conn, site)
+    }
+
+    /// The entities of type `entity_type` as of `block` in the order of
+    /// their ids and limited to `range`; only the ones whose id is greater
+    /// than `after` if it is given
+    async fn entities_by_id(
+        self: &Arc<Self>,
+        site: Arc<Site>,
+        entity_type: EntityType,
+        after: Option<String>,
+        range: EntityRange,
+        block: BlockNumber,
+    ) -> Result<Vec<Entity>, StoreError> {
+        let store = self.clone();
+        let collection = EntityCollection::All(vec![(entity_type, AttributeNames::All)]);
+        let filter = after.map(|id| EntityFilter::GreaterThan("id".to_string(), Value::String(id)));
+        self.with_conn(move |conn, _| {
+            let layout = store.layout(conn, site)?;
+            let (entities, _) = layout
+                .query(
+                    &store.logger,
+                    conn,
+                    collection,
+                    filter,
+                    EntityOrder::Default,
+                    range,
+                    block,
+                    None,
+                )
+                .map_err(StoreError::from)?;
+            Ok(entities)
+        })
+        .await
+    }
+
+    /// Load the entities of the deployment as of `block`, or as of its
+    /// head if `block` is `None`, into its NebulaGraph space; see
+    /// `nebula::backfill`. If a backfill was interrupted, it is resumed
+    /// instead, and `block` must be the block of that backfill if it is
+    /// given. Fails if another process runs the backfill
+    pub(crate) async fn nebula_backfill(
+        self: &Arc<Self>,
+        mut reporter: Box<dyn BackfillReporter>,
+        site: Arc<Site>,
+        block: Option<BlockNumber>,
+    ) -> Result<Box<dyn BackfillReporter>, StoreError> {
+        let store = self.clone();
+        let site2 = site.clone();
+        let (graph, tables, state) = self
+            .with_conn(move |conn, _| {
+                let graph = store.graph_layout(conn, site2.clone())?;
+                let tables = nebula::backfill::state(conn, &site2)?;
+                let state = deployment::state(conn, site2.deployment.clone())?;
+                Ok((graph, tables, state))
+            })
+            .await?;
+
+        let (block, tables) = match tables.first() {
+            Some(table) => {
+                let started = table.block_number;
+                if block.map_or(false, |block| block != started) {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "a backfill of {} as of block {} has not finished yet",
+                        site.namespace,
+                        started
+                    )));
+                }
+                let site2 = site.clone();
+                let claimed = self
+                    .with_conn(move |conn, _| {
+                        nebula::backfill::claim(conn, &site2).map_err(Into::into)
+                    })
+                    .await?;
+                if !claimed {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "the backfill of {} is run by another process, for example by \
+                         the job that writes to NebulaGraph",
+                        site.namespace
+                    )));
+                }
+                reporter.start(started, true);
+                (started, tables)
+            }
+            None => {
+                let head = state.latest_block.number;
+                let block = block.unwrap_or(head);
+                if block > head || block < state.earliest_block_number {
+                    return Err(StoreError::Unknown(anyhow!(
+                        "can not backfill {} as of block {} since it only has entities \
+                         for blocks {} to {}",
+                        site.namespace,
+                        block,
+                        state.earliest_block_number,
+                        head
+                    )));
+                }
+                reporter.start(block, false);
+
+                // The outbox entries up to `block` are part of what we
+                // load, and writing them afterwards would undo later
+                // changes. Recording the backfill also stops the outbox
+                // job from replaying the later entries until we are done;
+                // that has to happen before the space is recreated
+                let site2 = site.clone();
+                let entity_types = graph.entity_types();
+                let tables = self
+                    .with_conn(move |conn, _| {
+                        conn.transaction(|| {
+                            nebula::outbox::drop_until(conn, &site2, block)?;
+                            nebula::backfill::start(conn, &site2, block, &entity_types)?;
+                            nebula::backfill::claim(conn, &site2)?;
+                            nebula::backfill::state(conn, &site2)
+                        })
+                        .map_err(Into::into)
+                    })
+                    .await?;
+                (block, tables)
+            }
+        };
+
+        self.run_nebula_backfill(reporter, site, &graph, block, tables)
+            .await
+    }
+
+    /// The deployments with a backfill that no process runs, e.g., because
+    /// it was recorded for a graft or copy, or because the process that
+    /// ran it was interrupted
+    pub(crate) async fn unclaimed_nebula_backfills(&self) -> Result<Vec<DeploymentId>, StoreError> {
+        self.with_conn(|conn, _| nebula::backfill::unclaimed(conn).map_err(Into::into))
+            .await
+    }
+
+    /// Resume the backfill of the NebulaGraph space of `site` unless
+    /// another process runs it. `start_subgraph` records one for grafts
+    /// and copies, and the job that writes to NebulaGraph uses this to
+    /// perform those and to finish backfills that were interrupted
+    pub(crate) async fn resume_nebula_backfill(
+        self: &Arc<Self>,
+        logger: &Logger,
+        site: Arc<Site>,
+    ) -> Result<(), StoreError> {
+        let store = self.clone();
+        let site2 = site.clone();
+        let claimed = self
+            .with_conn(move |conn, _| {
+                if !nebula::backfill::claim(conn, &site2)? {
+                    return Ok(None);
+                }
+                let graph = store.graph_layout(conn, site2.clone())?;
+                let tables = nebula::backfill::state(conn, &site2)?;
+                Ok(Some((graph, tables)))
+            })
+            .await?;
+        let (graph, tables) = match claimed {
+            Some((graph, tables)) => (graph, tables),
+            None => return Ok(()),
+        };
+        let block = match tables.first() {
+            Some(table) => table.block_number,
+            None => return Ok(()),
+        };
+
+        let mut reporter = Box::new(nebula::backfill::LogReporter::new(logger));
+        reporter.start(block, true);
+        self.run_nebula_backfill(reporter, site, &graph, block, tables)
+            .await?;
+        Ok(())
+    }
+
+    /// Load the entities of `tables` as of `block` into the space of
+    /// `graph`, and forget the backfill once that is done. The caller must
+    /// have claimed the backfill; the claim is given up if loading fails
+    async fn run_nebula_backfill(
+        self: &Arc<Self>,
+        mut reporter: Box<dyn BackfillReporter>,
+        site: Arc<Site>,
+        graph: &GraphLayout,
+        block: BlockNumber,
+        tables: Vec<nebula::backfill::TableState>,
+    ) -> Result<Box<dyn BackfillReporter>, StoreError> {
+        let total = match self
+            .load_nebula_backfill(reporter.as_mut(), &site, graph, block, tables)
+            .await
+        {
+            Ok(total) => total,
+            Err(e) => {
+                let site = site.clone();
+                self.with_conn(move |conn, _| {
+                    nebula::backfill::release(conn, &site).map_err(Into::into)
+                })
+                .await
+                .ok();
+                return Err(e);
+            }
+        };
+
+        self.with_conn(move |conn, _| nebula::backfill::finish(conn, &site).map_err(Into::into))
+            .await?;
+        reporter.finish(total);
+        Ok(reporter)
+    }
+
+    /// Load the entities of the `tables` that have not been finished yet
+    /// and return how many entities the backfill loaded in total
+    async fn load_nebula_backfill(
+        self: &Arc<Self>,
+        reporter: &mut dyn BackfillReporter,
+        site: &Arc<Site>,
+        graph: &GraphLayout,
+        block: BlockNumber,
+        tables: Vec<nebula::backfill::TableState>,
+    ) -> Result<usize, StoreError> {
+        const BATCH_SIZE: u32 = 1_000;
+
+        // A backfill that was interrupted before it loaded anything might
+        // not have recreated the space yet
+        if tables
+            .iter()
+            .all(|table| table.last_id.is_none() && !table.finished)
+        {
+            reporter.recreate_space(graph.space.as_str());
+            self.nebula_sink
+                .execute(vec![nebula::drop_space_query(graph.space.as_str())?])
+                .await?;
+            self.create_nebula_space(graph).await?;
+        }
+
+        let mut total = 0;
+        for table in tables {
+            let entity_type = EntityType::new(table.entity_type);
+            let mut count = table.entity_count as usize;
+            let mut last_id = table.last_id;
+            if table.finished {
+                total += count;
+                continue;
+            }
+            reporter.start_entity_type(entity_type.as_str(), count);
+
+            loop {
+                let entities = self
+                    .entities_by_id(
+                        site.clone(),
+                        entity_type.clone(),
+                        last_id.clone(),
+                        EntityRange::first(BATCH_SIZE),
+                        block,
+                    )
+                    .await?;
+                let finished = entities.len() < BATCH_SIZE as usize;
+
+                let mut writes = GraphWrites::new(block);
+                for entity in &entities {
+                    graph.add_entity(&entity_type, entity, &mut writes)?;
+                }
+                if !writes.is_empty() {
+                    self.nebula_sink
+                        .execute(writes.into_statements(ENV_VARS.store.nebula_max_statement_size))
+                        .await?;
+                }
+
+                if let Some(entity) = entities.last() {
+                    last_id = Some(entity.id()?);
+                }
+                count += entities.len();
+                let site2 = site.clone();
+                let entity_type2 = entity_type.clone();
+                let last_id2 = last_id.clone();
+                self.with_conn(move |conn, _| {
+                    nebula::backfill::record_batch(
+                        conn,
+                        &site2,
+                        &entity_type2,
+                        last_id2.as_deref(),
+                        count,
+                        finished,
+                    )
+                    .map_err(Into::into)
+                })
+                .await?;
+                reporter.batch(entity_type.as_str(), entities.len(), count, finished);
+                if finished {
+                    break;
+                }
+            }
+            total += count;
+        }
+        Ok(total)
+    }
+
+    /// Compare the NebulaGraph space of the deployment with its entities
+    /// as of the block up to which its outbox has been written; see
+    /// `nebula::verify`. If `sample` is given, only that many entities of
+    /// each type, taken from batches at distinct random positions, are
+    /// compared. With `repair`, vertices and edges that are missing or
+    /// differ are written again. The result is recorded for the indexing
+    /// status
+    pub(crate) async fn nebula_verify(
+        self: &Arc<Self>,
+        mut reporter: Box<dyn VerifyReporter>,
+        site: Arc<Site>,
+        sample: Option<usize>,
+        repair: bool,
+    ) -> Result<Box<dyn VerifyReporter>, StoreError> {
+        const BATCH_SIZE: u32 = 1_000;
+
+        let store = self.clone();
+        let site2 = site.clone();
+        let (graph, sync) = self
+            .with_conn(move |conn, _| {
+                let graph = store.graph_layout(conn, site2.clone())?;
+                let sync = nebula::outbox::sync_status(conn, &site2)?;
+                Ok((graph, sync))
+            })
+            .await?;
+        let block = sync.synced_block.ok_or_else(|| {
+            StoreError::Unknown(anyhow!(
+                "nothing of {} has been written to NebulaGraph yet",
+                site.namespace
+            ))
+        })?;
+
+        let store = self.clone();
+        let site2 = site.clone();
+        let graph2 = graph.clone();
+        let entity_counts = self
+            .with_conn(move |conn, _| {
+                let layout = store.layout(conn, site2)?;
+                nebula::verify::entity_counts(conn, &layout, &graph2, block).map_err(Into::into)
+            })
+            .await?;
+        reporter.start(block, sample);
+
+        let mut result = NebulaConsistency {
+            block,
+            complete: sample.is_none(),
+            entities: 0,
+            count_mismatches: 0,
+            missing: 0,
+            different: 0,
+            repaired: 0,
+        };
+        // The number of edges of the types that no entity type is written
+        // as, i.e., the ones for reference fields
+        let mut edge_counts: BTreeMap<String, i64> = BTreeMap::new();
+        for (entity_type, total) in &entity_counts {
+            reporter.start_entity_type(entity_type.as_str());
+            let mut checked = 0;
+            let mut last_id = None;
+            // A sample is taken from batches at distinct positions so that
+            // no entity is compared twice
+            let mut offsets: Vec<u32> = (0..*total as u32).step_by(BATCH_SIZE as usize).collect();
+            offsets.shuffle(&mut thread_rng());
+            loop {
+                let (after, range) = match sample {
+                    None => (last_id.clone(), EntityRange::first(BATCH_SIZE)),
+                    Some(sample) => match offsets.pop() {
+                        Some(skip) => {
+                            let range = EntityRange {
+                                first: Some(BATCH_SIZE.min((sample - checked) as u32)),
+                                skip,
+                            };
+                            (None, range)
+                        }
+                        None => break,
+                    },
+                };
+                let entities = self
+                    .entities_by_id(site.clone(), entity_type.clone(), after, range, block)
+                    .await?;
+
+                let mut writes = GraphWrites::new(block);
+                for entity in &entities {
+                    graph.add_entity(entity_type, entity, &mut writes)?;
+                }
+                if sample.is_none() {
+                    for query in &writes.edges {
+                        if graph.written_as(entity_type) != Some(&query.edge_name) {
+                            *edge_counts
+                                .entry(query.edge_name.as_str().to_owned())
+                                .or_default() += 1;
+                        }
+                    }
+                }
+
+                let found =
+                    nebula::verify::Found::fetch(&self.nebula_sink, &graph, &writes).await?;
+                let mut repairs = GraphWrites::new(block);
+                for difference in nebula::verify::compare(writes, &found, &mut repairs) {
+                    match difference {
+                        NebulaDifference::MissingVertex { .. }
+                        | NebulaDifference::MissingEdge { .. } => result.missing += 1,
+                        _ => result.different += 1,
+                    }
+                    reporter.difference(&difference);
+                }
+                if repair && !repairs.is_empty() {
+                    let repaired = repairs.tags.len() + repairs.edges.len();
+                    let statements =
+                        repairs.into_statements(ENV_VARS.store.nebula_max_statement_size);
+                    reporter.repair(statements.len());
+                    self.nebula_sink.execute(statements).await?;
+                    result.repaired += repaired as i64;
+                }
+
+                checked += entities.len();
+                reporter.batch(entity_type.as_str(), entities.len(), checked);
+                let finished = match sample {
+                    None => entities.len() < BATCH_SIZE as usize,
+                    Some(sample) => entities.is_empty() || checked >= sample.min(*total as usize),
+                };
+                if finished {
+                    break;
+                }
+                if let Some(entity) = entities.last() {
+                    last_id = Some(entity.id()?);
+                }
+            }
+            result.entities += checked as i64;
+        }
+
+        // Edge types for reference fields can only be counted when all
+        // entities were compared
+        let mut expected: BTreeMap<String, i64> = entity_counts
+            .iter()
+            .filter_map(|(entity_type, count)| {
+                graph
+                    .written_as(entity_type)
+                    .map(|name| (name.as_str().to_owned(), *count))
+            })
+            .collect();
+        if sample.is_none() {
+            for edge in &graph.edges {
+                let name = edge.name.as_str();
+                if !expected.contains_key(name) {
+                    let count = edge_counts.get(name).copied().unwrap_or(0);
+                    expected.insert(name.to_owned(), count);
+                }
+            }
+        }
+        reporter.start_count();
+        let actual = nebula::verify::graph_counts(&self.nebula_sink, &graph).await?;
+        for difference in nebula::verify::count_differences(&expected, &actual) {
+            result.count_mismatches += 1;
+            reporter.difference(&difference);
+        }
+
+        let result2 = result.clone();
+        self.with_conn(move |conn, _| {
+            nebula::verify::record(conn, &site, &result2).map_err(Into::into)
+        })
+        .await?;
+        reporter.finish(&result);
+        Ok(reporter)
+    }
+
+    /// The ids of the vertices that each of `queries` reaches in the
+    /// NebulaGraph space of the deployment; see `nebula::traversal`
+    pub(crate) async fn nebula_neighbors(
+        &self,
+        graph: &GraphLayout,
+        queries: &[NeighborsQuery],
+    ) -> Result<Vec<Vec<String>>, StoreError> {
+        nebula::traversal::neighbors(&self.nebula_sink, graph, queries).await
+    }
+
+    /// The paths that each of `queries` finds in the NebulaGraph space of
+    /// the deployment; see `nebula::traversal`
+    pub(crate) async fn nebula_paths(
+        &self,
+        graph: &GraphLayout,
+        queries: &[PathsQuery],
+    ) -> Result<Vec<Vec<Vec<String>>>, StoreError> {
+        nebula::traversal::paths(&self.nebula_sink, graph, queries).await
+    }
+
+    /// Run the nGQL `statement` from a client in the NebulaGraph space of
+    /// the deployment; see `nebula::query`. A connection to Postgres is
+    /// only needed if the layout of the space is not cached yet
+    pub(crate) async fn nebula_query(
+        &self,
+        site: Arc<Site>,
+        statement: &str,
+        max_rows: usize,
+    ) -> Result<NebulaRows, StoreError> {
+        let graph = match self.cached_graph_layout(&site) {
+            Some(graph) => graph,
+            None => {
+                let store = self.clone();
+                self.with_conn(move |conn, _| store.graph_layout(conn, site).map_err(Into::into))
+                    .await?
+            }
+        };
+        nebula::query::run(&self.nebula_sink, &graph, statement, max_rows).await
+    }
+
     pub(crate) async fn vacuum(&self) -> Result<(), StoreError> {
         self.with_conn(|conn, _| {
             conn.batch_execute("vacuum (analyze) subgraphs.subgraph_deployment")?;
@@ -1029,26 +1659,94 @@ impl DeploymentStore {
         mods: &[EntityModification],
         ptr: &BlockPtr,
         stopwatch: &StopwatchMetrics,
-        entities: & mut Vec<EntityWithSpaceName>
+        graph: &GraphLayout,
+        writes: &mut GraphWrites,
     ) -> Result<i32, StoreError> {
         use EntityModification::*;
         let mut count = 0;
 
+        // The graph needs the versions that overwrites and removals replace
+        // for entities that hold edges; they are still current since this
+        // block has not been written yet
+        let mut previous_ids: BTreeMap<&EntityType, Vec<&str>> = BTreeMap::new();
+        for modification in mods {
+            match modification {
+                Overwrite { key, .. } | Remove { key }
+                    if graph.needs_previous(&key.entity_type) =>
+                {
+                    previous_ids
+                        .entry(&key.entity_type)
+                        .or_default()
+                        .push(key.entity_id.as_str());
+                }
+                _ => {}
+            }
+        }
+        let mut previous = HashMap::new();
+        for (entity_type, entities) in layout.find_many(conn, &previous_ids, BLOCK_NUMBER_MAX)? {
+            for entity in entities {
+                let key = EntityKey::data(entity_type.to_string(), entity.id()?);
+                previous.insert(key, entity);
+            }
+        }
+        for modification in mods {
+            let key = modification.entity_ref();
+            graph.add_modification(modification, previous.get(key), writes)?;
+        }
+
         // Group `Insert`s and `Overwrite`s by key, and accumulate `Remove`s.
+        let mut inserts = HashMap::new();
+        let mut overwrites = HashMap::new();
+        let mut removals = HashMap::new();
         for modification in mods.into_iter() {
             match modification {
                 Insert { key, data } => {
-                    entities.push(EntityWithSpaceName::new(key.entity_type.to_string(), data.clone(), ptr.block_number()));
+                    inserts
+                        .entry(key.entity_type.clone())
+                        .or_insert_with(Vec::new)
+                        .push((key, Cow::from(data)));
                 }
                 Overwrite { key, data } => {
-                    entities.push(EntityWithSpaceName::new(key.entity_type.to_string(), data.clone(), ptr.block_number()));
+                    overwrites
+                        .entry(key.entity_type.clone())
+                        .or_insert_with(Vec::new)
+                        .push((key, Cow::from(data)));
                 }
                 Remove { key } => {
-                    continue;
+                    removals
+                        .entry(key.entity_type.clone())
+                        .or_insert_with(Vec::new)
+                        .push(key.entity_id.as_str());
                 }
             }
         }
-        Ok(entities.len() as i32)
+
+        // Apply modification groups.
+        // Inserts:
+        for (entity_type, mut entities) in inserts.into_iter() {
+            count +=
+                self.insert_entities(&entity_type, &mut entities, conn, layout, ptr, stopwatch)?
+                    as i32
+        }
+
+        // Overwrites:
+        for (entity_type, mut entities) in overwrites.into_iter() {
+            // we do not update the count since the number of entities remains the same
+            self.overwrite_entities(&entity_type, &mut entities, conn, layout, ptr, stopwatch)?;
+        }
+
+        // Removals
+        for (entity_type, entity_keys) in removals.into_iter() {
+            count -= self.remove_entities(
+                &entity_type,
+                entity_keys.as_slice(),
+                conn,
+                layout,
+                ptr,
+                stopwatch,
+            )? as i32;
+        }
+        Ok(count)
     }
 
     fn insert_entities<'a>(
@@ -1116,20 +1814,11 @@ impl DeploymentStore {
         manifest_idx_and_name: &[(u32, String)],
         offchain_to_remove: &[StoredDynamicDataSource],
     ) -> Result<StoreEvent, StoreError> {
-
-
-        let start_time = Instant::now();
-
-
         let conn = {
             let _section = stopwatch.start_section("transact_blocks_get_conn");
             self.get_conn()?
         };
 
-        let conf_nebula = &self.conf_nebula;
-
-        let mut entities: Vec<EntityWithSpaceName> = Vec::new();
-
         let event = conn.transaction(|| -> Result<_, StoreError> {
             // Emit a store event for the changes we are about to make. We
             // wait with sending it until we have done all our other work
@@ -1139,19 +1828,21 @@ impl DeploymentStore {
 
             // Make the changes
             let layout = self.layout(&conn, site.clone())?;
+            let graph = self.graph_layout(&conn, site.clone())?;
 
             //  see also: deployment-lock-for-update
             deployment::lock(&conn, &site)?;
 
             let section = stopwatch.start_section("apply_entity_modifications");
-            
+            let mut writes = GraphWrites::new(block_ptr_to.number);
             let count = self.apply_entity_modifications(
                 &conn,
                 layout.as_ref(),
                 mods,
                 block_ptr_to,
                 stopwatch,
-                & mut entities,
+                &graph,
+                &mut writes,
             )?;
             section.end();
             dynds::insert(
@@ -1178,39 +1869,17 @@ impl DeploymentStore {
                 layout.count_query.as_str(),
                 count,
             )?;
+            // The mutations are written to NebulaGraph by the outbox job
+            // once this transaction has committed
+            nebula::outbox::insert(
+                &conn,
+                &site,
+                block_ptr_to.number,
+                writes.into_statements(ENV_VARS.store.nebula_max_statement_size),
+            )?;
             Ok(event)
         })?;
 
-        // insert tag
-        let insert_tag_queries = EntityWithSpaceName::entity_to_insert_tag_query(&entities);
-  
-        // insert edge
-        let insert_edge_queries = EntityWithSpaceName::entity_to_insert_edge_queries(&entities);
-
-
-        let start_time2 = Instant::now();
-
-        // run nebula execution
-        tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-        .block_on(async {
-            // get nebula session
-            let conn_nebula = Connection_nebula::new_from_conf(conf_nebula).await.unwrap();
-            let resp = conn_nebula.authenticate(conf_nebula.username.clone().as_str(), conf_nebula.password.clone().as_str()).await.unwrap();
-            let session_id = resp.session_id.unwrap();
-            conn_nebula.insert_tags(insert_tag_queries, session_id).await;
-            conn_nebula.insert_edges(insert_edge_queries, session_id).await;
-            conn_nebula.signout(session_id).await;
-        });
-
-        println!("insert_into_nebula:{}", start_time2.elapsed().as_secs_f64());
-
-
-        println!("transact_block_operations:{}", start_time.elapsed().as_secs_f64());
-
-
         Ok(event)
     }
 
@@ -1226,7 +1895,7 @@ impl DeploymentStore {
             deployment::lock(conn, &site)?;
 
             // Don't revert past a graft point
-            let info = self.subgraph_info_with_conn(conn, site.as_ref())?;
+            let info = self.subgraph_info_with_conn(conn, &site)?;
             if let Some(graft_block) = info.graft_block {
                 if graft_block > block_ptr_to.number {
                     return Err(anyhow!(
@@ -1243,14 +1912,37 @@ impl DeploymentStore {
 
             // The revert functions want the number of the first block that we need to get rid of
             let block = block_ptr_to.number + 1;
+            let head = block_ptr_to.number;
 
             deployment::revert_block_ptr(conn, &site.deployment, block_ptr_to, firehose_cursor)?;
 
             // Revert the data
             let layout = self.layout(conn, site.clone())?;
 
+            // Remember the entities that the revert changes so that the
+            // graph can be brought back to their state at `block_ptr_to`.
+            // Only entity types that are mirrored into the graph matter
+            let graph = self.graph_layout(conn, site.clone())?;
+            let changed = layout.changed_since(conn, &graph.entity_types(), block)?;
+            let changed_ids: BTreeMap<_, Vec<_>> = changed
+                .iter()
+                .map(|(entity_type, ids)| (entity_type, ids.iter().map(String::as_str).collect()))
+                .collect();
+            let before = layout.find_many(conn, &changed_ids, BLOCK_NUMBER_MAX)?;
+
             let (event, count) = layout.revert_block(conn, block)?;
 
+            let after = layout.find_many(conn, &changed_ids, BLOCK_NUMBER_MAX)?;
+            let mut writes = GraphWrites::new(head);
+            graph.revert_entities(before, after, &mut writes)?;
+            nebula::outbox::revert(conn, &site, head)?;
+            nebula::outbox::insert(
+                conn,
+                &site,
+                head,
+                writes.into_statements(ENV_VARS.store.nebula_max_statement_size),
+            )?;
+
             // Revert the meta data changes that correspond to this subgraph.
             // Only certain meta data changes need to be reverted, most
             // importantly creation of dynamic data sources. We ensure in the
@@ -1472,6 +2164,13 @@ impl DeploymentStore {
                     self.analyze_with_conn(site.cheap_clone(), entity_name.as_str(), &conn)?;
                 }
 
+                // The space of the deployment starts out empty. Record a
+                // backfill of the entities we just copied, which the job
+                // that writes to NebulaGraph performs in the background;
+                // it holds back the changes of the deployment until then
+                let graph = self.graph_layout(&conn, site.cheap_clone())?;
+                nebula::backfill::start(&conn, &dst.site, block.number, &graph.entity_types())?;
+
                 // Set the block ptr to the graft point to signal that we successfully
                 // performed the graft
                 crate::deployment::forward_block_ptr(&conn, &dst.site.deployment, &block)?;
@@ -1723,133 +2422,3 @@ fn resolve_column_names<'a, T: AsRef<str>>(
         })
         .collect()
 }
-
-pub struct EntityWithSpaceName{
-    pub space_name: String,
-    pub entity: Entity,
-    pub block_number: BlockNumber,
-}
-impl EntityWithSpaceName{
-    pub fn new(
-        space_name: String,
-        entity: Entity, 
-        block_number: BlockNumber,
-    ) -> Self{
-        EntityWithSpaceName{
-            space_name,
-            entity,
-            block_number
-        }
-    }
-    pub fn get_random_string(len: usize) -> String{
-        let mut rng = rand::thread_rng();
-        let mut test: Vec<u8> = vec![0; len];
-        for i in &mut test{
-            let dig_or_char: u8 = rng.gen_range(0..=1);
-            match dig_or_char{
-                0 => *i = rng.gen_range(48..=57),
-                _ => *i = rng.gen_range(97..=122),
-            }
-        }
-        String::from_utf8(test).unwrap()
-    }
-
-    pub fn entity_to_insert_tag_query(entities: &Vec<EntityWithSpaceName>) -> Vec<InsertTagQuery>{
-        let mut insert_tag_queries: Vec<InsertTagQuery> = Vec::new();
-        for entity in entities{
-            if entity.space_name == String::from("Poi$"){
-                continue;
-            }
-            let mut properties_from: HashMap<String, String> = HashMap::new();
-            let mut properties_to: HashMap<String, String> = HashMap::new();
-            let mut value = String::from("");
-            if entity.entity.0.get("operation").clone().unwrap().to_string()==String::from("1"){
-                for (k,v) in &entity.entity.0{
-                    // println!("------------kv-------------");
-                    // println!("{}",k.to_string());
-                    // println!("{}",v.to_string());
-                    // (id) VALUES "from_account"(from_account)
-                    if k.clone()==String::from("from_account"){
-                        properties_from.insert("from_account".to_string(), v.clone().to_string());
-                    }
-                    else if k.clone()==String::from("to_account"){
-                        properties_to.insert("to_account".to_string(), v.clone().to_string());
-                    }
-                    else if k.clone()==String::from("value"){
-                        value = v.to_string();
-                    } 
-                }
-                let space_name = entity.space_name.clone();
-                let tag_name = space_name.clone() + "_tag";
-                let vid_from = properties_from.get("from_account").unwrap().clone().replace("\"", "");
-                let vid_to = properties_to.get("to_account").unwrap().clone().replace("\"", "");
-                let insert_tag_query_from = InsertTagQuery::new(space_name.clone(), tag_name.clone(), properties_from, vid_from, true, value.parse::<i64>().unwrap());
-                let insert_tag_query_to = InsertTagQuery::new(space_name, tag_name, properties_to, vid_to,true, value.parse::<i64>().unwrap());
-                insert_tag_queries.push(insert_tag_query_from);
-                insert_tag_queries.push(insert_tag_query_to);
-            }
-        }
-        insert_tag_queries
-    }
-
-    pub fn entity_to_insert_edge_queries(entities: &Vec<EntityWithSpaceName>) -> Vec<InsertEdgeQueryWithRank>{
-
-        pub fn get_random_string(len: usize) -> String{
-            let mut rng = rand::thread_rng();
-            let mut test: Vec<u8> = vec![0; len];
-            for i in &mut test{
-                let dig_or_char: u8 = rng.gen_range(0..=1);
-                match dig_or_char{
-                    0 => *i = rng.gen_range(48..=57),
-                    _ => *i = rng.gen_range(97..=122),
-                }
-            }
-            String::from_utf8(test).unwrap()
-        }
-
-        let mut insert_edge_queries: Vec<InsertEdgeQueryWithRank> = Vec::new();
-        for entity in entities{
-            if entity.space_name == String::from("Poi$"){
-                continue;
-            }
-            let mut properties: HashMap<String, String> = HashMap::new();
-            if entity.entity.0.get("operation").unwrap().to_string()==String::from("1"){
-                for (k,v) in &entity.entity.0{
-                    if k.clone()==String::from("id") || k.clone()==String::from("operation"){
-                        continue;
-                    }else if k.clone()==String::from("value"){
-                        let mut transactions = String::from("(");
-                        transactions += &v.to_string();
-                        transactions += ",";
-                        transactions += &get_random_string(20);
-                        transactions += ",";
-                        transactions += "+";
-                        transactions += ")";
-                        properties.insert("transactions".to_string(),transactions);
-                        properties.insert(k.clone(), v.to_string());
-                    }else{
-                        properties.insert(k.clone(), v.to_string());
-                    }
-                }
-                let space_name = entity.space_name.clone();
-                let to_vertex = properties.get("from_account").unwrap().clone().replace("\"", "");
-                let from_vertex = properties.get("to_account").unwrap().clone().replace("\"", "");
-                //value_map
-                let value = properties.get("value").unwrap().clone().replace("\"", "");
-                
-                
-                let insert_edge_query = InsertEdgeQueryWithRank::new(
-                    space_name,
-                    "tx".to_string(),
-                    properties,
-                    from_vertex,
-                    to_vertex,
-                    entity.block_number,
-                    value.parse::<i64>().unwrap(),
-                );
-                insert_edge_queries.push(insert_edge_query);
-            }
-        }
-        insert_edge_queries
-    }
-}
@@ -24,6 +24,7 @@ use crate::deployment::{
     graph_node_versions, subgraph_deployment, subgraph_error, subgraph_manifest,
     SubgraphHealth as HealthType,
 };
+use crate::nebula;
 use crate::primary::{DeploymentId, Site};
 
 git_testament_macros!(version);
@@ -67,6 +68,7 @@ pub struct DeploymentDetail {
     current_reorg_depth: i32,
     max_reorg_depth: i32,
     firehose_cursor: Option<String>,
+    nebula_space: Option<String>,
 }
 
 #[derive(Queryable, QueryableByName)]
@@ -247,6 +249,7 @@ pub(crate) fn info_from_details(
         chains: vec![chain],
         entity_count,
         node: None,
+        nebula_consistency: None,
     })
 }
 
@@ -314,11 +317,16 @@ pub(crate) fn deployment_statuses(
         .into_group_map()
     };
 
+    let mut verifications = nebula::verify::last(conn, sites)?;
+
     details_with_fatal_error
         .into_iter()
         .map(|(detail, fatal)| {
             let non_fatal = non_fatal_errors.remove(&detail.id).unwrap_or(vec![]);
-            info_from_details(detail, fatal, non_fatal, sites)
+            let verification = verifications.remove(&detail.id);
+            let mut info = info_from_details(detail, fatal, non_fatal, sites)?;
+            info.nebula_consistency = verification;
+            Ok(info)
         })
         .collect()
 }
@@ -340,6 +348,7 @@ struct StoredSubgraphManifest {
     use_bytea_prefix: bool,
     start_block_number: Option<i32>,
     start_block_hash: Option<Bytes>,
+    graph_mapping: Option<String>,
 }
 
 impl From<StoredSubgraphManifest> for SubgraphManifestEntity {
@@ -350,6 +359,7 @@ impl From<StoredSubgraphManifest> for SubgraphManifestEntity {
             repository: value.repository,
             features: value.features,
             schema: value.schema,
+            graph_mapping: value.graph_mapping,
         }
     }
 }
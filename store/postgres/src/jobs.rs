@@ -24,6 +24,11 @@ pub fn register(
         Duration::from_secs(60),
     );
 
+    runner.register(
+        Arc::new(NebulaOutboxJob::new(store.subgraph_store())),
+        Duration::from_secs(1),
+    );
+
     runner.register(
         Arc::new(NotificationQueueUsage::new(primary_pool, registry)),
         Duration::from_secs(60),
@@ -73,6 +78,38 @@ impl Job for VacuumDeploymentsJob {
     }
 }
 
+/// A job that writes the graph mutations that were recorded in the outbox
+/// of each shard to NebulaGraph
+struct NebulaOutboxJob {
+    store: Arc<SubgraphStore>,
+}
+
+impl NebulaOutboxJob {
+    fn new(store: Arc<SubgraphStore>) -> NebulaOutboxJob {
+        NebulaOutboxJob { store }
+    }
+}
+
+#[async_trait]
+impl Job for NebulaOutboxJob {
+    fn name(&self) -> &str {
+        "Write the NebulaGraph outbox"
+    }
+
+    async fn run(&self, logger: &Logger) {
+        // Stop after a while so that other jobs get a chance to run; the
+        // rest of the outbox is written the next time the job runs
+        const DEADLINE: Duration = Duration::from_secs(30);
+
+        let deadline = Instant::now() + DEADLINE;
+        for res in self.store.replay_nebula_outbox(logger, deadline).await {
+            if let Err(e) = res {
+                error!(logger, "Writing the NebulaGraph outbox failed: {}", e);
+            }
+        }
+    }
+}
+
 struct NotificationQueueUsage {
     primary: ConnectionPool,
     usage_gauge: Box<Gauge>,
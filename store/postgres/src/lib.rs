@@ -27,6 +27,7 @@ mod fork;
 mod functions;
 mod jobs;
 mod jsonb;
+mod nebula;
 mod notification_listener;
 mod primary;
 pub mod query_store;
@@ -51,6 +52,16 @@ pub mod layout_for_tests {
     pub mod writable {
         pub use crate::writable::test_support::allow_steps;
     }
+    pub mod nebula {
+        pub mod outbox {
+            pub use crate::nebula::outbox::{
+                deployments, drop_until, insert, new_spaces, pending, space_created,
+            };
+        }
+        pub mod backfill {
+            pub use crate::nebula::backfill::{finish, record_batch, start};
+        }
+    }
 }
 
 pub use self::block_store::BlockStore;
@@ -60,9 +71,10 @@ pub use self::detail::DeploymentDetail;
 pub use self::jobs::register as register_jobs;
 pub use self::notification_listener::NotificationSender;
 pub use self::primary::{db_version, UnusedDeployment};
+pub use self::nebula::{NebulaConfig, SpaceConfig};
 pub use self::store::Store;
 pub use self::store_events::SubscriptionManager;
-pub use self::subgraph_store::{unused, DeploymentPlacer, Shard, SubgraphStore, PRIMARY_SHARD, PRIMARY_SHARD_NEBULA};
+pub use self::subgraph_store::{unused, DeploymentPlacer, Shard, SubgraphStore, PRIMARY_SHARD};
 
 /// This module is only meant to support command line tooling. It must not
 /// be used in 'normal' graph-node code
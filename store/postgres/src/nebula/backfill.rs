@@ -0,0 +1,223 @@
+//! Rebuild the NebulaGraph space of a deployment from its entity tables.
+//!
+//! A backfill loads the entities of every entity type that is mirrored
+//! into the graph as they were at one block, in batches ordered by id. It
+//! starts by dropping and creating the space again, so that the graph
+//! ends up holding exactly what Postgres holds at that block. The outbox
+//! entries for later blocks are not replayed while the backfill is in
+//! progress, since they would be overwritten by older versions of the
+//! entities; see `outbox::pending`.
+//!
+//! The progress is recorded in `subgraphs.nebula_backfill`, one row per
+//! entity type, after every batch. A backfill that was interrupted
+//! resumes after the last batch that was recorded; since batches only
+//! insert, writing a batch again is harmless. The rows are removed once
+//! all entity types have been loaded.
+//!
+//! Backfills are run by `graphman nebula backfill`, and by the job that
+//! writes to NebulaGraph, which loads the entities of grafts and copies
+//! and resumes backfills that were interrupted. The process that runs a
+//! backfill claims it for `LEASE` and extends the claim with every batch,
+//! so that no two processes load the same deployment at once, and a
+//! backfill whose process died is taken over once the claim runs out.
+use diesel::dsl::{not, now, sql};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Nullable, Timestamptz};
+use diesel::{delete, insert_into, update, PgConnection};
+use graph::components::store::{BackfillReporter, EntityType};
+use graph::prelude::{info, BlockNumber, Logger, StoreError};
+
+use crate::primary::{DeploymentId, Site};
+
+use super::outbox::nebula_new_space;
+
+/// How long a process can hold on to a backfill without recording a
+/// batch before other processes may take it over
+const LEASE: &str = "now() + interval '5 minutes'";
+
+table! {
+    subgraphs.nebula_backfill (deployment, entity_type) {
+        deployment -> Integer,
+        entity_type -> Text,
+        block_number -> Integer,
+        last_id -> Nullable<Text>,
+        entity_count -> BigInt,
+        finished_at -> Nullable<Timestamptz>,
+        locked_until -> Nullable<Timestamptz>,
+    }
+}
+
+/// The progress of the backfill for one entity type
+#[derive(Queryable)]
+pub(crate) struct TableState {
+    pub entity_type: String,
+    pub block_number: BlockNumber,
+    /// The id of the last entity that was loaded
+    pub last_id: Option<String>,
+    pub entity_count: i64,
+    pub finished: bool,
+}
+
+/// The progress of the backfill for `site`, ordered by entity type. Empty
+/// if no backfill is in progress
+pub(crate) fn state(conn: &PgConnection, site: &Site) -> Result<Vec<TableState>, StoreError> {
+    use nebula_backfill as b;
+
+    b::table
+        .filter(b::deployment.eq(site.id))
+        .select((
+            b::entity_type,
+            b::block_number,
+            b::last_id,
+            b::entity_count,
+            b::finished_at.is_not_null(),
+        ))
+        .order_by(b::entity_type)
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// Record that a backfill of `entity_types` as of `block` has started
+pub fn start(
+    conn: &PgConnection,
+    site: &Site,
+    block: BlockNumber,
+    entity_types: &[EntityType],
+) -> Result<(), StoreError> {
+    use nebula_backfill as b;
+
+    let rows: Vec<_> = entity_types
+        .iter()
+        .map(|entity_type| {
+            (
+                b::deployment.eq(site.id),
+                b::entity_type.eq(entity_type.as_str()),
+                b::block_number.eq(block),
+            )
+        })
+        .collect();
+    insert_into(b::table).values(rows).execute(conn)?;
+    Ok(())
+}
+
+/// Claim the backfill for `site` for the calling process. Returns `false`
+/// if there is no backfill or if another process holds on to it
+pub fn claim(conn: &PgConnection, site: &Site) -> Result<bool, StoreError> {
+    use nebula_backfill as b;
+
+    conn.transaction(|| {
+        let rows = b::table
+            .filter(b::deployment.eq(site.id))
+            .select(b::entity_type)
+            .for_update()
+            .load::<String>(conn)?;
+        let claimed = b::table
+            .filter(b::deployment.eq(site.id))
+            .filter(sql::<Bool>("locked_until > now()"))
+            .count()
+            .get_result::<i64>(conn)?;
+        if rows.is_empty() || claimed > 0 {
+            return Ok(false);
+        }
+        update(b::table.filter(b::deployment.eq(site.id)))
+            .set(b::locked_until.eq(sql::<Nullable<Timestamptz>>(LEASE)))
+            .execute(conn)?;
+        Ok(true)
+    })
+}
+
+/// Let go of the backfill for `site` without finishing it, so that
+/// another process can resume it right away
+pub fn release(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    use nebula_backfill as b;
+
+    update(b::table.filter(b::deployment.eq(site.id)))
+        .set(b::locked_until.eq(sql::<Nullable<Timestamptz>>("null")))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The deployments with a backfill that no process holds on to, and whose
+/// space has been created
+pub(crate) fn unclaimed(conn: &PgConnection) -> Result<Vec<DeploymentId>, StoreError> {
+    use nebula_backfill as b;
+    use nebula_new_space as ns;
+
+    b::table
+        .filter(sql::<Bool>("coalesce(locked_until < now(), true)"))
+        .filter(not(b::deployment.eq_any(ns::table.select(ns::deployment))))
+        .select(b::deployment)
+        .distinct()
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// Record that the entities of `entity_type` up to `last_id` have been
+/// loaded, `entity_count` in total, and extend the claim on the backfill
+pub fn record_batch(
+    conn: &PgConnection,
+    site: &Site,
+    entity_type: &EntityType,
+    last_id: Option<&str>,
+    entity_count: usize,
+    finished: bool,
+) -> Result<(), StoreError> {
+    use nebula_backfill as b;
+
+    let target = b::table
+        .filter(b::deployment.eq(site.id))
+        .filter(b::entity_type.eq(entity_type.as_str()));
+    update(target.clone())
+        .set((
+            b::last_id.eq(last_id),
+            b::entity_count.eq(entity_count as i64),
+            b::locked_until.eq(sql::<Nullable<Timestamptz>>(LEASE)),
+        ))
+        .execute(conn)?;
+    if finished {
+        update(target).set(b::finished_at.eq(now)).execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Forget the progress of the backfill for `site` once it has finished
+pub fn finish(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    use nebula_backfill as b;
+
+    delete(b::table.filter(b::deployment.eq(site.id))).execute(conn)?;
+    Ok(())
+}
+
+/// Reports the progress of a backfill to the log of the deployment
+pub(crate) struct LogReporter {
+    logger: Logger,
+}
+
+impl LogReporter {
+    pub fn new(logger: &Logger) -> Self {
+        LogReporter {
+            logger: logger.clone(),
+        }
+    }
+}
+
+impl BackfillReporter for LogReporter {
+    fn start(&mut self, block: BlockNumber, resumed: bool) {
+        info!(self.logger, "Loading entities into NebulaGraph";
+              "block" => block,
+              "resumed" => resumed);
+    }
+
+    fn batch(&mut self, entity_type: &str, _rows: usize, total_rows: usize, finished: bool) {
+        if finished {
+            info!(self.logger, "Loaded entities into NebulaGraph";
+                  "entity_type" => entity_type,
+                  "count" => total_rows);
+        }
+    }
+
+    fn finish(&mut self, total_rows: usize) {
+        info!(self.logger, "Finished loading entities into NebulaGraph";
+              "count" => total_rows);
+    }
+}
@@ -0,0 +1,1240 @@
+//! The layout of the NebulaGraph space that mirrors a deployment.
+//!
+//! If the manifest has a `graph` section, the layout follows that mapping:
+//! the entity types listed as vertices become tags, and the entity types
+//! listed as edges become edge types between them.
+//!
+//! Otherwise, the layout is derived from the subgraph schema in the same
+//! way in which `relational::Layout` is: every entity type becomes a tag
+//! that holds all the columns of the entity type, and every reference
+//! field, whether it is stored with the entity or declared with
+//! `@derivedFrom`, becomes an edge type that connects the vertices of the
+//! two entity types.
+//!
+//! Every tag and edge type has a `__block` property that holds the number
+//! of the block at which the vertex or edge was last written to the graph.
+//! That is not necessarily the block at which the entity was last changed:
+//! a revert writes the versions that it restores again with the new head as
+//! their `__block`, and a backfill or a repair by `graphman nebula verify`
+//! writes entities with the block as of which it reads them. `__block` is
+//! therefore not a reliable record of when an entity changed. Since the
+//! `__block` of everything in the graph is never above the block pointer
+//! of the deployment, a revert only needs to undo the writes for the
+//! entities that changed above the new head; see
+//! `GraphLayout::revert_entities`.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+use graph::components::store::{EntityModification, EntityType};
+use graph::data::graphql::ext::{DirectiveExt, DirectiveFinder, DocumentExt, TypeExt};
+use graph::data::schema::Schema;
+use graph::data::subgraph::{schema::POI_OBJECT, GraphMapping};
+use graph::prelude::{anyhow, s, BlockNumber, Entity, StoreError, Value};
+use itertools::Itertools;
+use nebula_rust::graph_client::nebula_schema::{
+    batch_insert_edges, batch_insert_tags, ColType, DataType, DeleteEdgeQuery, DeleteTagQuery,
+    InsertEdgeQueryWithRank, InsertTagQuery, UpsertEdgeQuery, UpsertTagQuery,
+};
+use nebula_rust::graph_client::ngql::{
+    in_space, CreateSchema, CreateSpace, DropSpace, Identifier, Literal, PropertyDef, VidType,
+};
+
+use super::{value, SpaceConfig};
+use crate::relational::{Column, Layout, Table, PRIMARY_KEY_COLUMN};
+
+/// The property of every tag and edge type that holds the block at which
+/// a vertex or edge was written. GraphQL reserves names starting with `__`,
+/// and the property can therefore never clash with a field
+pub const BLOCK_PROPERTY: &str = "__block";
+
+/// A property of a tag; properties are named after the GraphQL field whose
+/// value they hold
+#[derive(Clone, Debug)]
+pub struct Property {
+    pub name: Identifier,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+impl Property {
+    fn new(column: &Column) -> Result<Self, StoreError> {
+        Ok(Property {
+            name: ident(&column.field)?,
+            data_type: value::data_type(column),
+            nullable: column.is_nullable(),
+        })
+    }
+}
+
+/// The tag for the vertices of one entity type
+#[derive(Clone, Debug)]
+pub struct TagType {
+    pub name: Identifier,
+    pub properties: Vec<Property>,
+}
+
+/// An edge type that connects vertices of type `source` to vertices of type
+/// `target`. Edge types for reference fields are named `<source>_<field>`
+/// and have no properties; edge types from the `graph` section of the
+/// manifest are named after their entity type
+#[derive(Clone, Debug)]
+pub struct EdgeType {
+    pub name: Identifier,
+    pub source: EntityType,
+    pub target: EntityType,
+    pub properties: Vec<Property>,
+}
+
+/// Describes how to write the edges of `edge` when an entity is written:
+/// the attribute `attribute` of the entity holds the ids of the vertices on
+/// the other end of the edge. For fields with `@derivedFrom`, the entity
+/// that is written is the target of the edge, and `reverse` is `true`
+#[derive(Clone, Debug)]
+struct EdgeSource {
+    edge: Arc<EdgeType>,
+    attribute: String,
+    reverse: bool,
+}
+
+/// An entity type whose entities are written as edges of type `edge`. The
+/// attributes `source` and `target` hold the ids of the vertices the edge
+/// connects, and `rank`, if present, the rank of the edge
+#[derive(Clone, Debug)]
+struct EdgeEntity {
+    edge: Arc<EdgeType>,
+    source: String,
+    target: String,
+    rank: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GraphLayout {
+    /// The name of the NebulaGraph space
+    pub space: Identifier,
+    /// Maps the GraphQL name of an entity type to its tag
+    pub tags: HashMap<EntityType, TagType>,
+    /// All edge types, sorted by name
+    pub edges: Vec<Arc<EdgeType>>,
+    /// The edges that have to be written when an entity of a given type is
+    /// written
+    edge_sources: HashMap<EntityType, Vec<EdgeSource>>,
+    /// The entity types that are written as edges
+    edge_entities: HashMap<EntityType, EdgeEntity>,
+}
+
+/// The statements needed to write the changes of the block `block` into
+/// the graph
+pub struct GraphWrites {
+    pub block: BlockNumber,
+    pub deleted_edges: Vec<DeleteEdgeQuery>,
+    pub deleted_tags: Vec<DeleteTagQuery>,
+    pub tags: Vec<InsertTagQuery>,
+    pub upserted_tags: Vec<UpsertTagQuery>,
+    pub edges: Vec<InsertEdgeQueryWithRank>,
+    pub upserted_edges: Vec<UpsertEdgeQuery>,
+}
+
+/// A single statement of `GraphWrites`
+pub enum GraphMutation<'a> {
+    DeleteEdge(&'a DeleteEdgeQuery),
+    DeleteTag(&'a DeleteTagQuery),
+    InsertTag(&'a InsertTagQuery),
+    UpsertTag(&'a UpsertTagQuery),
+    InsertEdge(&'a InsertEdgeQueryWithRank),
+    UpsertEdge(&'a UpsertEdgeQuery),
+}
+
+impl fmt::Display for GraphMutation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let query = match self {
+            GraphMutation::DeleteEdge(query) => query.to_string(),
+            GraphMutation::DeleteTag(query) => query.to_string(),
+            GraphMutation::InsertTag(query) => query.to_string(),
+            GraphMutation::UpsertTag(query) => query.to_string(),
+            GraphMutation::InsertEdge(query) => query.to_string(),
+            GraphMutation::UpsertEdge(query) => query.to_string(),
+        };
+        write!(f, "{}", query)
+    }
+}
+
+impl GraphWrites {
+    pub fn new(block: BlockNumber) -> Self {
+        GraphWrites {
+            block,
+            deleted_edges: vec![],
+            deleted_tags: vec![],
+            tags: vec![],
+            upserted_tags: vec![],
+            edges: vec![],
+            upserted_edges: vec![],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mutations().next().is_none()
+    }
+
+    /// All writes in the order in which they must be applied. Deletions
+    /// come first so that an edge or vertex that is deleted and written
+    /// again ends up in the graph; vertices are written before edges
+    pub fn mutations(&self) -> impl Iterator<Item = GraphMutation<'_>> {
+        self.deleted_edges
+            .iter()
+            .map(GraphMutation::DeleteEdge)
+            .chain(self.deleted_tags.iter().map(GraphMutation::DeleteTag))
+            .chain(self.tags.iter().map(GraphMutation::InsertTag))
+            .chain(self.upserted_tags.iter().map(GraphMutation::UpsertTag))
+            .chain(self.edges.iter().map(GraphMutation::InsertEdge))
+            .chain(self.upserted_edges.iter().map(GraphMutation::UpsertEdge))
+    }
+
+    /// The nGQL statements for all writes, in the order of `mutations`.
+    /// Inserts into the same tag or edge type are combined into multi-row
+    /// statements of at most `max_statement_size` bytes
+    pub fn into_statements(self, max_statement_size: usize) -> Vec<String> {
+        let mut statements: Vec<_> = self
+            .deleted_edges
+            .iter()
+            .map(|query| query.to_string())
+            .chain(self.deleted_tags.iter().map(|query| query.to_string()))
+            .collect();
+        statements.extend(batch_insert_tags(&self.tags, max_statement_size));
+        statements.extend(self.upserted_tags.iter().map(|query| query.to_string()));
+        statements.extend(batch_insert_edges(&self.edges, max_statement_size));
+        statements.extend(self.upserted_edges.iter().map(|query| query.to_string()));
+        statements
+    }
+}
+
+impl GraphLayout {
+    /// Generate the graph layout in `space` for the deployment whose
+    /// relational layout is `layout`, following `mapping` if the manifest
+    /// has one. The `schema` is needed since fields with `@derivedFrom` do
+    /// not have columns in `layout`
+    pub fn new(
+        layout: &Layout,
+        schema: &Schema,
+        mapping: Option<&GraphMapping>,
+        space: &str,
+    ) -> Result<Self, StoreError> {
+        let space = ident(space)?;
+        match mapping {
+            Some(mapping) => Self::from_mapping(layout, mapping, space),
+            None => Self::from_schema(layout, schema, space),
+        }
+    }
+
+    /// A layout that only has `tags` and `edges` and that writes no
+    /// edges for reference fields
+    #[cfg(test)]
+    pub(crate) fn for_tests(
+        space: Identifier,
+        tags: HashMap<EntityType, TagType>,
+        edges: Vec<Arc<EdgeType>>,
+    ) -> Self {
+        GraphLayout {
+            space,
+            tags,
+            edges,
+            edge_sources: HashMap::new(),
+            edge_entities: HashMap::new(),
+        }
+    }
+
+    fn from_mapping(
+        layout: &Layout,
+        mapping: &GraphMapping,
+        space: Identifier,
+    ) -> Result<Self, StoreError> {
+        let mut tags = HashMap::new();
+        let mut edges = Vec::new();
+        let mut edge_entities = HashMap::new();
+
+        for vertex in &mapping.vertices {
+            let table = layout.table_for_entity(&EntityType::from(vertex.entity.as_str()))?;
+            tags.insert(
+                table.object.clone(),
+                TagType {
+                    name: ident(table.object.as_str())?,
+                    properties: properties(table, vertex.properties.as_ref(), &[])?,
+                },
+            );
+        }
+
+        for edge in &mapping.edges {
+            let table = layout.table_for_entity(&EntityType::from(edge.entity.as_str()))?;
+            let source = table.column_for_field(&edge.source)?;
+            let target = table.column_for_field(&edge.target)?;
+            let edge_type = Arc::new(EdgeType {
+                name: ident(table.object.as_str())?,
+                source: EntityType::from(source.field_type.get_base_type()),
+                target: EntityType::from(target.field_type.get_base_type()),
+                properties: properties(
+                    table,
+                    edge.properties.as_ref(),
+                    &[&edge.source, &edge.target],
+                )?,
+            });
+            edge_entities.insert(
+                table.object.clone(),
+                EdgeEntity {
+                    edge: edge_type.clone(),
+                    source: edge.source.clone(),
+                    target: edge.target.clone(),
+                    rank: edge.rank.clone(),
+                },
+            );
+            edges.push(edge_type);
+        }
+        edges.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(GraphLayout {
+            space,
+            tags,
+            edges,
+            edge_sources: HashMap::new(),
+            edge_entities,
+        })
+    }
+
+    fn from_schema(
+        layout: &Layout,
+        schema: &Schema,
+        space: Identifier,
+    ) -> Result<Self, StoreError> {
+        let mut tags = HashMap::new();
+        let mut edges = Vec::new();
+        let mut edge_sources: HashMap<EntityType, Vec<EdgeSource>> = HashMap::new();
+
+        for table in layout.tables.values() {
+            if table.object == *POI_OBJECT {
+                continue;
+            }
+            let properties = table
+                .columns
+                .iter()
+                .filter(|column| !column.is_fulltext())
+                .map(Property::new)
+                .collect::<Result<_, _>>()?;
+            tags.insert(
+                table.object.clone(),
+                TagType {
+                    name: ident(table.object.as_str())?,
+                    properties,
+                },
+            );
+
+            for column in table.columns.iter().filter(|column| column.is_reference()) {
+                let edge = Arc::new(EdgeType {
+                    name: edge_name(&table.object, &column.field)?,
+                    source: table.object.clone(),
+                    target: EntityType::from(column.field_type.get_base_type()),
+                    properties: vec![],
+                });
+                edge_sources
+                    .entry(table.object.clone())
+                    .or_default()
+                    .push(EdgeSource {
+                        edge: edge.clone(),
+                        attribute: column.field.clone(),
+                        reverse: false,
+                    });
+                edges.push(edge);
+            }
+        }
+
+        for object_type in schema.document.get_object_type_definitions() {
+            let source = EntityType::from(object_type);
+            if !tags.contains_key(&source) {
+                continue;
+            }
+            for field in &object_type.fields {
+                let derived_from = match field
+                    .find_directive("derivedFrom")
+                    .and_then(|directive| directive.argument("field"))
+                {
+                    Some(s::Value::String(derived_from)) => derived_from,
+                    _ => continue,
+                };
+                let target = EntityType::from(field.field_type.get_base_type());
+                let edge = Arc::new(EdgeType {
+                    name: edge_name(&source, &field.name)?,
+                    source: source.clone(),
+                    target: target.clone(),
+                    properties: vec![],
+                });
+                // The reference is stored with the entities on the other
+                // end of the edge; if that is an interface, it is stored
+                // with all the types that implement the interface
+                let holders = match schema.types_for_interface.get(&target) {
+                    Some(types) => types.iter().map(EntityType::from).collect(),
+                    None => vec![target],
+                };
+                for holder in holders {
+                    edge_sources.entry(holder).or_default().push(EdgeSource {
+                        edge: edge.clone(),
+                        attribute: derived_from.clone(),
+                        reverse: true,
+                    });
+                }
+                edges.push(edge);
+            }
+        }
+        edges.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(GraphLayout {
+            space,
+            tags,
+            edges,
+            edge_sources,
+            edge_entities: HashMap::new(),
+        })
+    }
+
+    /// The entity types that are mirrored into the graph, either as
+    /// vertices or as edges, sorted by name
+    pub fn entity_types(&self) -> Vec<EntityType> {
+        self.tags
+            .keys()
+            .chain(self.edge_entities.keys())
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// The tag or edge type as which the entities of `entity_type` are
+    /// written, if they are mirrored into the graph
+    pub fn written_as(&self, entity_type: &EntityType) -> Option<&Identifier> {
+        self.tags.get(entity_type).map(|tag| &tag.name).or_else(|| {
+            self.edge_entities
+                .get(entity_type)
+                .map(|edge| &edge.edge.name)
+        })
+    }
+
+    /// The edge types that can be traversed from the vertices of an object
+    /// type, keyed by the name of the type. Only edge types that connect a
+    /// tag with itself are traversable, since a traversal has to end on
+    /// vertices of the type it started on
+    pub fn traversal_edges(&self) -> BTreeMap<String, Vec<String>> {
+        let mut traversal: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for edge in &self.edges {
+            if edge.source == edge.target && self.tags.contains_key(&edge.source) {
+                traversal
+                    .entry(edge.source.to_string())
+                    .or_default()
+                    .push(edge.name.as_str().to_string());
+            }
+        }
+        traversal
+    }
+
+    /// The nGQL statement that creates the space for this layout
+    pub fn create_space_query(&self, conf: &SpaceConfig) -> String {
+        let query = CreateSpace {
+            name: self.space.clone(),
+            partition_num: conf.partition_num,
+            replica_factor: conf.replica_factor,
+            vid_type: VidType::FixedString(conf.vid_length),
+            comment: None,
+        };
+        format!("{};", query)
+    }
+
+    /// The nGQL statements that create all tags and edge types of this
+    /// layout, one per entry
+    pub fn as_ddl(&self) -> Vec<String> {
+        fn columns(properties: &[Property]) -> Vec<PropertyDef> {
+            properties
+                .iter()
+                .map(|prop| PropertyDef {
+                    name: prop.name.clone(),
+                    data_type: prop.data_type,
+                    nullable: prop.nullable,
+                    default: None,
+                    comment: None,
+                })
+                .chain(Some(PropertyDef {
+                    name: block_property(),
+                    data_type: DataType::Int32,
+                    nullable: false,
+                    default: None,
+                    comment: None,
+                }))
+                .collect()
+        }
+
+        let tags = self
+            .tags
+            .values()
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .map(|tag| CreateSchema {
+                kind: ColType::Tag,
+                name: tag.name.clone(),
+                properties: columns(&tag.properties),
+                comment: None,
+            });
+        let edges = self.edges.iter().map(|edge| CreateSchema {
+            kind: ColType::Edge,
+            name: edge.name.clone(),
+            properties: columns(&edge.properties),
+            comment: None,
+        });
+        tags.chain(edges)
+            .map(|query| in_space(&self.space, &query))
+            .collect()
+    }
+
+    /// Add the statements that write `entity` to `writes`. For entities
+    /// that are written as vertices, that includes all the edges for which
+    /// the entity holds the reference. Entities whose type is not mirrored
+    /// into the graph are ignored
+    pub fn add_entity(
+        &self,
+        entity_type: &EntityType,
+        entity: &Entity,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        let tag = match (
+            self.tags.get(entity_type),
+            self.edge_entities.get(entity_type),
+        ) {
+            (Some(tag), _) => tag,
+            (None, Some(edge)) => return edge.add_entity(&self.space, entity, writes),
+            (None, None) => return Ok(()),
+        };
+        let vid = entity.id()?;
+
+        let kv = property_values(&tag.properties, entity, writes.block);
+        writes.tags.push(InsertTagQuery::new(
+            self.space.clone(),
+            tag.name.clone(),
+            kv,
+            vid.clone(),
+        ));
+
+        for (edge, from, to) in self.held_edges(entity_type, &vid, entity) {
+            writes.edges.push(InsertEdgeQueryWithRank::new(
+                self.space.clone(),
+                edge.name.clone(),
+                property_values(&edge.properties, entity, writes.block),
+                from,
+                to,
+                0,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `add_modification` needs the version of an entity of type
+    /// `entity_type` that an overwrite or removal replaces. That is the
+    /// case for entities that hold edges, since the edges for the replaced
+    /// version have to be deleted
+    pub fn needs_previous(&self, entity_type: &EntityType) -> bool {
+        self.edge_entities.contains_key(entity_type)
+            || self
+                .edge_sources
+                .get(entity_type)
+                .map_or(false, |sources| !sources.is_empty())
+    }
+
+    /// Add the statements for `modification` to `writes`. Inserts become
+    /// `INSERT` statements, overwrites `UPSERT` statements, and removals
+    /// `DELETE` statements. Removing an entity that is written as a vertex
+    /// deletes its tag and the edges for which it holds the reference, but
+    /// not the edges that other entities hold. `previous` is the version of
+    /// the entity that an overwrite or removal replaces, and must be passed
+    /// for entity types for which `needs_previous` is `true`
+    pub fn add_modification(
+        &self,
+        modification: &EntityModification,
+        previous: Option<&Entity>,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        use EntityModification::*;
+
+        match modification {
+            Insert { key, data } => self.add_entity(&key.entity_type, data, writes),
+            Overwrite { key, data } => {
+                self.overwrite_entity(&key.entity_type, previous, data, writes)
+            }
+            Remove { key } => {
+                self.remove_entity(&key.entity_type, &key.entity_id, previous, writes)
+            }
+        }
+    }
+
+    fn overwrite_entity(
+        &self,
+        entity_type: &EntityType,
+        previous: Option<&Entity>,
+        entity: &Entity,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        let tag = match (
+            self.tags.get(entity_type),
+            self.edge_entities.get(entity_type),
+        ) {
+            (Some(tag), _) => tag,
+            (None, Some(edge)) => {
+                return edge.overwrite_entity(&self.space, previous, entity, writes)
+            }
+            (None, None) => return Ok(()),
+        };
+        let vid = entity.id()?;
+
+        writes.upserted_tags.push(UpsertTagQuery::new(
+            self.space.clone(),
+            tag.name.clone(),
+            property_values(&tag.properties, entity, writes.block),
+            vid.clone(),
+        ));
+
+        // Delete the edges that the previous version held but this one
+        // does not
+        let edges = self.held_edges(entity_type, &vid, entity);
+        if let Some(previous) = previous {
+            for (edge, from, to) in self.held_edges(entity_type, &vid, previous) {
+                let kept = edges
+                    .iter()
+                    .any(|(e, f, t)| e.name == edge.name && *f == from && *t == to);
+                if !kept {
+                    writes.deleted_edges.push(DeleteEdgeQuery::new(
+                        self.space.clone(),
+                        edge.name.clone(),
+                        from,
+                        to,
+                        0,
+                    ));
+                }
+            }
+        }
+        for (edge, from, to) in edges {
+            writes.upserted_edges.push(UpsertEdgeQuery::new(
+                self.space.clone(),
+                edge.name.clone(),
+                property_values(&edge.properties, entity, writes.block),
+                from,
+                to,
+                0,
+            ));
+        }
+        Ok(())
+    }
+
+    fn remove_entity(
+        &self,
+        entity_type: &EntityType,
+        id: &str,
+        previous: Option<&Entity>,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        match previous {
+            Some(previous) => self.delete_entity(entity_type, previous, true, writes),
+            None => {
+                // Without `previous`, the entity holds no edges
+                if let Some(tag) = self.tags.get(entity_type) {
+                    writes.deleted_tags.push(DeleteTagQuery::new(
+                        self.space.clone(),
+                        tag.name.clone(),
+                        id.to_owned(),
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Add the statements that undo the writes for the entities that a
+    /// revert to `writes.block` changes. `before` holds the current
+    /// versions of these entities before the revert, and `after` the
+    /// versions that are current after it; an entity that is missing from
+    /// `before` was deleted above the new head, and one that is missing
+    /// from `after` was created above it. The restored versions get
+    /// `writes.block` as their `__block`, not the block at which they were
+    /// written originally
+    pub fn revert_entities(
+        &self,
+        before: BTreeMap<EntityType, Vec<Entity>>,
+        after: BTreeMap<EntityType, Vec<Entity>>,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        let mut restored = HashMap::new();
+        for (entity_type, entities) in after {
+            for entity in entities {
+                restored.insert((entity_type.clone(), entity.id()?), entity);
+            }
+        }
+
+        for (entity_type, entities) in before {
+            for entity in entities {
+                let after = restored.remove(&(entity_type.clone(), entity.id()?));
+                self.delete_entity(&entity_type, &entity, after.is_none(), writes)?;
+                if let Some(after) = after {
+                    self.add_entity(&entity_type, &after, writes)?;
+                }
+            }
+        }
+        for ((entity_type, _), entity) in restored {
+            self.add_entity(&entity_type, &entity, writes)?;
+        }
+        Ok(())
+    }
+
+    /// Add the statements that delete the edges that were written for
+    /// `entity`, and, if `delete_vertex` is `true`, its tag, to `writes`
+    fn delete_entity(
+        &self,
+        entity_type: &EntityType,
+        entity: &Entity,
+        delete_vertex: bool,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        let tag = match (
+            self.tags.get(entity_type),
+            self.edge_entities.get(entity_type),
+        ) {
+            (Some(tag), _) => tag,
+            (None, Some(edge)) => {
+                if let Some((from, to, rank)) = edge.endpoints(entity)? {
+                    writes.deleted_edges.push(DeleteEdgeQuery::new(
+                        self.space.clone(),
+                        edge.edge.name.clone(),
+                        from,
+                        to,
+                        rank,
+                    ));
+                }
+                return Ok(());
+            }
+            (None, None) => return Ok(()),
+        };
+        let vid = entity.id()?;
+
+        for (edge, from, to) in self.held_edges(entity_type, &vid, entity) {
+            writes.deleted_edges.push(DeleteEdgeQuery::new(
+                self.space.clone(),
+                edge.name.clone(),
+                from,
+                to,
+                0,
+            ));
+        }
+        // Only the tag is deleted, not the vertex. `DELETE VERTEX` leaves
+        // the edges of the vertex in place, and with `WITH EDGE` it would
+        // also delete the edges that other entities hold, which a revert
+        // could not restore
+        if delete_vertex {
+            writes.deleted_tags.push(DeleteTagQuery::new(
+                self.space.clone(),
+                tag.name.clone(),
+                vid,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The edges, as `(edge type, from, to)`, for which `entity` holds the
+    /// reference; `vid` is the id of `entity`
+    fn held_edges<'a>(
+        &'a self,
+        entity_type: &EntityType,
+        vid: &str,
+        entity: &Entity,
+    ) -> Vec<(&'a EdgeType, String, String)> {
+        let mut edges = vec![];
+        for source in self.edge_sources.get(entity_type).into_iter().flatten() {
+            for other in vids(entity.get(&source.attribute)) {
+                let (from, to) = if source.reverse {
+                    (other, vid.to_owned())
+                } else {
+                    (vid.to_owned(), other)
+                };
+                edges.push((source.edge.as_ref(), from, to));
+            }
+        }
+        edges
+    }
+}
+
+impl EdgeEntity {
+    fn add_entity(
+        &self,
+        space: &Identifier,
+        entity: &Entity,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        if let Some((from, to, rank)) = self.endpoints(entity)? {
+            writes.edges.push(InsertEdgeQueryWithRank::new(
+                space.clone(),
+                self.edge.name.clone(),
+                property_values(&self.edge.properties, entity, writes.block),
+                from,
+                to,
+                rank,
+            ));
+        }
+        Ok(())
+    }
+
+    fn overwrite_entity(
+        &self,
+        space: &Identifier,
+        previous: Option<&Entity>,
+        entity: &Entity,
+        writes: &mut GraphWrites,
+    ) -> Result<(), StoreError> {
+        let endpoints = self.endpoints(entity)?;
+        let previous = previous
+            .map(|previous| self.endpoints(previous))
+            .transpose()?;
+        // The edge moves if its source, target or rank change
+        if let Some((from, to, rank)) = previous.flatten() {
+            if endpoints.as_ref() != Some(&(from.clone(), to.clone(), rank)) {
+                writes.deleted_edges.push(DeleteEdgeQuery::new(
+                    space.clone(),
+                    self.edge.name.clone(),
+                    from,
+                    to,
+                    rank,
+                ));
+            }
+        }
+        if let Some((from, to, rank)) = endpoints {
+            writes.upserted_edges.push(UpsertEdgeQuery::new(
+                space.clone(),
+                self.edge.name.clone(),
+                property_values(&self.edge.properties, entity, writes.block),
+                from,
+                to,
+                rank,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The source, target and rank of the edge for `entity`. Edges whose
+    /// source or target is not set can not be written
+    fn endpoints(&self, entity: &Entity) -> Result<Option<(String, String, i64)>, StoreError> {
+        let (from, to) = match (
+            vids(entity.get(&self.source)).pop(),
+            vids(entity.get(&self.target)).pop(),
+        ) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Ok(None),
+        };
+        let rank = match self.rank.as_ref().and_then(|rank| entity.get(rank)) {
+            None | Some(Value::Null) => 0,
+            Some(Value::Int(rank)) => *rank as i64,
+            Some(Value::BigInt(rank)) => rank.to_string().parse::<i64>().map_err(|_| {
+                StoreError::Unknown(anyhow!(
+                    "the rank {} of edge {} does not fit into 64 bits",
+                    rank,
+                    self.edge.name
+                ))
+            })?,
+            Some(value) => {
+                return Err(StoreError::Unknown(anyhow!(
+                    "the rank of edge {} must be a number but is {}",
+                    self.edge.name,
+                    value
+                )))
+            }
+        };
+        Ok(Some((from, to, rank)))
+    }
+}
+
+/// The properties for the columns of `table`. If `fields` is given, only
+/// the primary key and those fields are included, and otherwise all columns
+/// except fulltext columns and the ones in `skip`
+fn properties(
+    table: &Table,
+    fields: Option<&Vec<String>>,
+    skip: &[&String],
+) -> Result<Vec<Property>, StoreError> {
+    match fields {
+        Some(fields) => {
+            let mut properties = vec![Property::new(table.column_for_field(PRIMARY_KEY_COLUMN)?)?];
+            for field in fields.iter().filter(|field| *field != PRIMARY_KEY_COLUMN) {
+                properties.push(Property::new(table.column_for_field(field)?)?);
+            }
+            Ok(properties)
+        }
+        None => table
+            .columns
+            .iter()
+            .filter(|column| !column.is_fulltext() && !skip.contains(&&column.field))
+            .map(Property::new)
+            .collect(),
+    }
+}
+
+/// The nGQL literals for the values of `properties` in `entity`, together
+/// with the `block` at which they are written
+fn property_values(
+    properties: &[Property],
+    entity: &Entity,
+    block: BlockNumber,
+) -> HashMap<Identifier, Literal> {
+    properties
+        .iter()
+        .map(|prop| {
+            let value = entity.get(prop.name.as_str()).unwrap_or(&Value::Null);
+            (prop.name.clone(), value::to_literal(value))
+        })
+        .chain(Some((block_property(), Literal::from(block))))
+        .collect()
+}
+
+/// The nGQL statement that drops `space` and everything in it
+pub fn drop_space_query(space: &str) -> Result<String, StoreError> {
+    Ok(format!("{};", DropSpace(ident(space)?)))
+}
+
+fn ident(name: &str) -> Result<Identifier, StoreError> {
+    Identifier::new(name).map_err(|e| StoreError::Unknown(anyhow!("{}", e)))
+}
+
+fn block_property() -> Identifier {
+    Identifier::new(BLOCK_PROPERTY).expect("`__block` is a valid identifier")
+}
+
+fn edge_name(source: &EntityType, field: &str) -> Result<Identifier, StoreError> {
+    ident(&format!("{}_{}", source, field))
+}
+
+/// The ids of the entities that the reference `value` points to
+fn vids(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Bytes(b)) => vec![b.to_string()],
+        Some(Value::List(values)) => values.iter().flat_map(|v| vids(Some(v))).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::DeploymentHash;
+
+    use nebula_rust::graph_client::nebula_schema::DEFAULT_MAX_STATEMENT_SIZE;
+
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::layout_for_tests::{make_dummy_site, Namespace};
+
+    const GQL: &str = "
+        interface Account { id: ID!, transfers: [Transfer!]! @derivedFrom(field: \"from\") }
+        type User implements Account @entity {
+            id: ID!
+            name: String
+            tags: [String!]
+            transfers: [Transfer!]! @derivedFrom(field: \"from\")
+        }
+        type Contract implements Account @entity {
+            id: ID!
+            transfers: [Transfer!]! @derivedFrom(field: \"from\")
+        }
+        type Transfer @entity {
+            id: ID!
+            from: Account!
+            to: [User!]!
+            value: BigInt!
+        }";
+
+    fn test_layout(gql: &str) -> (Layout, Schema) {
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let schema = Schema::parse(gql, subgraph.clone()).expect("Test schema invalid");
+        let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+        let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+        let catalog = Catalog::for_tests(site.clone()).expect("Can not create catalog");
+        let layout = Layout::new(site, &schema, catalog).expect("Failed to construct Layout");
+        (layout, schema)
+    }
+
+    #[test]
+    fn tags_and_edges() {
+        let (layout, schema) = test_layout(GQL);
+        let graph = GraphLayout::new(&layout, &schema, None, "sgd0815").unwrap();
+
+        assert_eq!("sgd0815", graph.space.as_str());
+        let space = SpaceConfig {
+            partition_num: 10,
+            replica_factor: 3,
+            vid_length: 64,
+        };
+        assert_eq!(
+            "CREATE SPACE IF NOT EXISTS `sgd0815` (partition_num = 10, replica_factor = 3, \
+             vid_type = FIXED_STRING(64));",
+            graph.create_space_query(&space)
+        );
+        assert_eq!(
+            "DROP SPACE IF EXISTS `sgd0815`;",
+            drop_space_query("sgd0815").unwrap()
+        );
+        let names: Vec<_> = graph.tags.keys().map(|key| key.as_str()).sorted().collect();
+        assert_eq!(vec!["Contract", "Transfer", "User"], names);
+        assert_eq!(
+            vec!["Contract", "Transfer", "User"],
+            graph
+                .entity_types()
+                .iter()
+                .map(EntityType::as_str)
+                .collect::<Vec<_>>()
+        );
+
+        let user = &graph.tags[&EntityType::from("User")];
+        let props: Vec<_> = user
+            .properties
+            .iter()
+            .map(|p| p.name.as_str())
+            .sorted()
+            .collect();
+        assert_eq!(vec!["id", "name", "tags"], props);
+        let tags = user
+            .properties
+            .iter()
+            .find(|p| p.name.as_str() == "tags")
+            .unwrap();
+        assert_eq!(DataType::String, tags.data_type);
+        assert!(tags.nullable);
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.name.as_str(), edge.target.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Contract_transfers", "Transfer"),
+                ("Transfer_from", "Account"),
+                ("Transfer_to", "User"),
+                ("User_transfers", "Transfer"),
+            ],
+            edges
+        );
+        assert!(graph.traversal_edges().is_empty());
+    }
+
+    #[test]
+    fn entity_writes() {
+        let (layout, schema) = test_layout(GQL);
+        let graph = GraphLayout::new(&layout, &schema, None, "sgd0815").unwrap();
+
+        let mut transfer = Entity::new();
+        transfer.set("id", "t\"1");
+        transfer.set("from", "u1");
+        transfer.set("to", vec!["u2", "u3"]);
+        transfer.set("value", Value::BigInt(42.into()));
+
+        let mut writes = GraphWrites::new(1);
+        graph
+            .add_entity(&EntityType::from("Transfer"), &transfer, &mut writes)
+            .unwrap();
+        graph
+            .add_entity(&EntityType::from("Unknown"), &transfer, &mut writes)
+            .unwrap();
+
+        assert_eq!(1, writes.tags.len());
+        let insert = writes.tags[0].to_string();
+        assert!(insert.contains("\"t\\\"1\""));
+        assert!(insert.contains("42"));
+
+        // `Transfer_from` and `Transfer_to` are written from `Transfer`;
+        // the derived `User_transfers` and `Contract_transfers` as well,
+        // but with the transfer as the target
+        let edges: Vec<_> = writes.edges.iter().map(|edge| edge.to_string()).collect();
+        assert_eq!(5, edges.len());
+        assert!(edges
+            .iter()
+            .any(|edge| edge.contains("`User_transfers`") && edge.contains("\"u1\"->\"t\\\"1\"")));
+    }
+
+    #[test]
+    fn mapped_entities() {
+        use graph::data::subgraph::{EdgeMapping, VertexMapping};
+
+        const GQL: &str = "
+            type Account @entity { id: ID!, name: String, balance: BigInt! }
+            type Transfer @entity {
+                id: ID!
+                from: Account!
+                to: Account!
+                value: BigInt!
+                block: BigInt!
+            }";
+
+        let (layout, schema) = test_layout(GQL);
+        let mapping = GraphMapping {
+            vertices: vec![VertexMapping {
+                entity: "Account".to_owned(),
+                properties: Some(vec!["balance".to_owned()]),
+            }],
+            edges: vec![EdgeMapping {
+                entity: "Transfer".to_owned(),
+                source: "from".to_owned(),
+                target: "to".to_owned(),
+                rank: Some("block".to_owned()),
+                properties: None,
+            }],
+        };
+        let graph = GraphLayout::new(&layout, &schema, Some(&mapping), "sgd0815").unwrap();
+
+        assert_eq!(
+            vec![EntityType::from("Account")],
+            graph.tags.keys().cloned().collect::<Vec<_>>()
+        );
+        let account = &graph.tags[&EntityType::from("Account")];
+        let props: Vec<_> = account.properties.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(vec!["id", "balance"], props);
+
+        assert_eq!(1, graph.edges.len());
+        let transfer = &graph.edges[0];
+        assert_eq!("Transfer", transfer.name.as_str());
+        assert_eq!(
+            Some(&transfer.name),
+            graph.written_as(&EntityType::from("Transfer"))
+        );
+        assert_eq!("Account", transfer.source.as_str());
+        assert_eq!(
+            Some(&vec!["Transfer".to_string()]),
+            graph.traversal_edges().get("Account")
+        );
+        let props: Vec<_> = transfer
+            .properties
+            .iter()
+            .map(|p| p.name.as_str())
+            .sorted()
+            .collect();
+        assert_eq!(vec!["block", "id", "value"], props);
+
+        let mut entity = Entity::new();
+        entity.set("id", "t1");
+        entity.set("from", "a1");
+        entity.set("to", "a2");
+        entity.set("value", Value::BigInt(42.into()));
+        entity.set("block", Value::BigInt(7.into()));
+
+        let mut writes = GraphWrites::new(1);
+        graph
+            .add_entity(&EntityType::from("Transfer"), &entity, &mut writes)
+            .unwrap();
+        assert!(writes.tags.is_empty());
+        assert_eq!(1, writes.edges.len());
+        assert_eq!(7, writes.edges[0].rank);
+        assert!(writes.edges[0].to_string().contains("\"a1\"->\"a2\"@7"));
+
+        // Without a target, there is no edge
+        entity.set("to", Value::Null);
+        let mut writes = GraphWrites::new(1);
+        graph
+            .add_entity(&EntityType::from("Transfer"), &entity, &mut writes)
+            .unwrap();
+        assert!(writes.is_empty());
+    }
+
+    #[test]
+    fn reverted_entities() {
+        let (layout, schema) = test_layout(GQL);
+        let graph = GraphLayout::new(&layout, &schema, None, "sgd0815").unwrap();
+
+        fn transfer(id: &str, to: &str) -> Entity {
+            let mut transfer = Entity::new();
+            transfer.set("id", id);
+            transfer.set("from", "u1");
+            transfer.set("to", vec![to]);
+            transfer.set("value", Value::BigInt(42.into()));
+            transfer
+        }
+        let mut user = Entity::new();
+        user.set("id", "u4");
+
+        // `t1` was created above the new head, `t2` was updated, and `u4`
+        // was deleted
+        let transfer_type = EntityType::from("Transfer");
+        let mut before = BTreeMap::new();
+        before.insert(
+            transfer_type.clone(),
+            vec![transfer("t1", "u2"), transfer("t2", "u3")],
+        );
+        let mut after = BTreeMap::new();
+        after.insert(transfer_type, vec![transfer("t2", "u2")]);
+        after.insert(EntityType::from("User"), vec![user]);
+
+        let mut writes = GraphWrites::new(5);
+        graph.revert_entities(before, after, &mut writes).unwrap();
+
+        // All edges of `t1` and `t2` are deleted, but only the tag of `t1`
+        assert_eq!(8, writes.deleted_edges.len());
+        assert_eq!(1, writes.deleted_tags.len());
+        assert_eq!("t1", writes.deleted_tags[0].vid);
+        let mut restored: Vec<_> = writes.tags.iter().map(|tag| tag.vid.as_str()).collect();
+        restored.sort();
+        assert_eq!(vec!["t2", "u4"], restored);
+        assert_eq!(4, writes.edges.len());
+        assert!(writes
+            .tags
+            .iter()
+            .all(|tag| tag.kv[BLOCK_PROPERTY] == Literal::Int(5)));
+
+        let statements = writes.into_statements(DEFAULT_MAX_STATEMENT_SIZE);
+        assert_eq!(15, statements.len());
+        assert!(statements[0].contains("DELETE EDGE"));
+        assert!(statements[14].contains("INSERT EDGE"));
+        assert!(statements
+            .iter()
+            .any(|stmt| stmt.contains("DELETE EDGE `Transfer_to` \"t2\"->\"u3\"@0")));
+    }
+
+    #[test]
+    fn batched_inserts() {
+        let (layout, schema) = test_layout(GQL);
+        let graph = GraphLayout::new(&layout, &schema, None, "sgd0815").unwrap();
+
+        let mut writes = GraphWrites::new(1);
+        for id in ["u1", "u2", "u3"] {
+            let mut user = Entity::new();
+            user.set("id", id);
+            user.set("name", "Alice");
+            graph
+                .add_entity(&EntityType::from("User"), &user, &mut writes)
+                .unwrap();
+        }
+        let mut contract = Entity::new();
+        contract.set("id", "c1");
+        graph
+            .add_entity(&EntityType::from("Contract"), &contract, &mut writes)
+            .unwrap();
+
+        // One statement per tag
+        let statements = writes.into_statements(DEFAULT_MAX_STATEMENT_SIZE);
+        assert_eq!(
+            vec![
+                "USE `sgd0815`; INSERT VERTEX `User`(`__block`, `id`, `name`, `tags`) VALUES \
+                 \"u1\":(1, \"u1\", \"Alice\", NULL), \"u2\":(1, \"u2\", \"Alice\", NULL), \
+                 \"u3\":(1, \"u3\", \"Alice\", NULL);",
+                "USE `sgd0815`; INSERT VERTEX `Contract`(`__block`, `id`) VALUES \
+                 \"c1\":(1, \"c1\");",
+            ],
+            statements
+        );
+
+        // Statements are split when they would get too long
+        let mut writes = GraphWrites::new(1);
+        for id in ["u1", "u2", "u3"] {
+            let mut user = Entity::new();
+            user.set("id", id);
+            graph
+                .add_entity(&EntityType::from("User"), &user, &mut writes)
+                .unwrap();
+        }
+        let statements = writes.into_statements(140);
+        assert_eq!(2, statements.len());
+        assert!(statements[0].contains("\"u1\"") && statements[0].contains("\"u2\""));
+        assert!(statements[1].contains("\"u3\""));
+        assert!(statements.iter().all(|stmt| stmt.len() <= 140));
+    }
+}
@@ -0,0 +1,378 @@
+//! An in-memory stand-in for the graph service that applies `GraphWrites`
+//! the way NebulaGraph would, so that the statements we generate for a
+//! sequence of entity modifications can be checked without a server
+use std::collections::{BTreeMap, HashMap};
+
+use nebula_rust::graph_client::ngql::{Identifier, Literal};
+
+use super::layout::{GraphMutation, GraphWrites};
+
+/// Property values, rendered as nGQL literals
+type Properties = HashMap<String, String>;
+
+fn properties(kv: &HashMap<Identifier, Literal>) -> Properties {
+    kv.iter()
+        .map(|(name, value)| (name.as_str().to_owned(), value.to_string()))
+        .collect()
+}
+
+/// An edge, identified by its type, source, target and rank
+type EdgeKey = (String, String, String, i64);
+
+#[derive(Default)]
+pub struct MockGraph {
+    /// Maps vertex ids to the properties of each of their tags
+    pub vertices: BTreeMap<String, BTreeMap<String, Properties>>,
+    pub edges: BTreeMap<EdgeKey, Properties>,
+}
+
+impl MockGraph {
+    pub fn apply(&mut self, writes: &GraphWrites) {
+        use GraphMutation::*;
+
+        for mutation in writes.mutations() {
+            match mutation {
+                DeleteEdge(query) => {
+                    self.edges.remove(&(
+                        query.edge_name.as_str().to_owned(),
+                        query.from_vertex.clone(),
+                        query.to_vertex.clone(),
+                        query.rank,
+                    ));
+                }
+                DeleteTag(query) => {
+                    if let Some(tags) = self.vertices.get_mut(&query.vid) {
+                        tags.remove(query.tag_name.as_str());
+                        if tags.is_empty() {
+                            self.vertices.remove(&query.vid);
+                        }
+                    }
+                }
+                InsertTag(query) => {
+                    self.vertices
+                        .entry(query.vid.clone())
+                        .or_default()
+                        .insert(query.tag_name.as_str().to_owned(), properties(&query.kv));
+                }
+                UpsertTag(query) => {
+                    self.vertices
+                        .entry(query.vid.clone())
+                        .or_default()
+                        .entry(query.tag_name.as_str().to_owned())
+                        .or_default()
+                        .extend(properties(&query.kv));
+                }
+                InsertEdge(query) => {
+                    let key = (
+                        query.edge_name.as_str().to_owned(),
+                        query.from_vertex.clone(),
+                        query.to_vertex.clone(),
+                        query.rank,
+                    );
+                    self.edges.insert(key, properties(&query.kv));
+                }
+                UpsertEdge(query) => {
+                    let key = (
+                        query.edge_name.as_str().to_owned(),
+                        query.from_vertex.clone(),
+                        query.to_vertex.clone(),
+                        query.rank,
+                    );
+                    self.edges
+                        .entry(key)
+                        .or_default()
+                        .extend(properties(&query.kv));
+                }
+            }
+        }
+    }
+
+    /// The value of the property `prop` of the tag `tag` of vertex `vid`
+    pub fn tag_value(&self, vid: &str, tag: &str, prop: &str) -> Option<&str> {
+        self.vertices
+            .get(vid)
+            .and_then(|tags| tags.get(tag))
+            .and_then(|props| props.get(prop))
+            .map(String::as_str)
+    }
+
+    /// The edges as `(edge type, from, to, rank)`
+    pub fn edge_keys(&self) -> Vec<(&str, &str, &str, i64)> {
+        self.edges
+            .keys()
+            .map(|(edge, from, to, rank)| (edge.as_str(), from.as_str(), to.as_str(), *rank))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use graph::components::store::{EntityKey, EntityModification};
+    use graph::data::schema::Schema;
+    use graph::data::subgraph::{EdgeMapping, GraphMapping, VertexMapping};
+    use graph::prelude::{BlockNumber, DeploymentHash, Entity, Value};
+
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::layout_for_tests::{make_dummy_site, Namespace};
+    use crate::nebula::layout::BLOCK_PROPERTY;
+    use crate::nebula::GraphLayout;
+    use crate::relational::Layout;
+
+    const GQL: &str = "
+        interface Account { id: ID!, transfers: [Transfer!]! @derivedFrom(field: \"from\") }
+        type User implements Account @entity {
+            id: ID!
+            name: String
+            transfers: [Transfer!]! @derivedFrom(field: \"from\")
+        }
+        type Contract implements Account @entity {
+            id: ID!
+            transfers: [Transfer!]! @derivedFrom(field: \"from\")
+        }
+        type Transfer @entity {
+            id: ID!
+            from: Account!
+            to: [User!]!
+            value: BigInt!
+        }";
+
+    /// Plays the role of the entity store: applies modifications to the
+    /// graph, passing the versions that they replace
+    struct Harness {
+        graph: GraphLayout,
+        entities: HashMap<EntityKey, Entity>,
+        mock: MockGraph,
+    }
+
+    impl Harness {
+        fn new(gql: &str, mapping: Option<&GraphMapping>) -> Self {
+            let subgraph = DeploymentHash::new("subgraph").unwrap();
+            let schema = Schema::parse(gql, subgraph.clone()).expect("Test schema invalid");
+            let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+            let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+            let catalog = Catalog::for_tests(site.clone()).expect("Can not create catalog");
+            let layout = Layout::new(site, &schema, catalog).expect("Failed to construct Layout");
+            let graph = GraphLayout::new(&layout, &schema, mapping, "sgd0815").unwrap();
+            Harness {
+                graph,
+                entities: HashMap::new(),
+                mock: MockGraph::default(),
+            }
+        }
+
+        fn transact(&mut self, block: BlockNumber, mods: Vec<EntityModification>) {
+            let mut writes = GraphWrites::new(block);
+            for modification in &mods {
+                let key = modification.entity_ref();
+                let previous = if self.graph.needs_previous(&key.entity_type) {
+                    self.entities.get(key)
+                } else {
+                    None
+                };
+                self.graph
+                    .add_modification(modification, previous, &mut writes)
+                    .unwrap();
+            }
+            for modification in mods {
+                match modification {
+                    EntityModification::Insert { key, data }
+                    | EntityModification::Overwrite { key, data } => {
+                        self.entities.insert(key, data);
+                    }
+                    EntityModification::Remove { key } => {
+                        self.entities.remove(&key);
+                    }
+                }
+            }
+            self.mock.apply(&writes);
+        }
+    }
+
+    fn entity(entity_type: &str, values: Vec<(&str, Value)>) -> (EntityKey, Entity) {
+        let entity = Entity::from(values);
+        let key = EntityKey::data(entity_type.to_owned(), entity.id().unwrap());
+        (key, entity)
+    }
+
+    fn insert(entity_type: &str, values: Vec<(&str, Value)>) -> EntityModification {
+        let (key, data) = entity(entity_type, values);
+        EntityModification::Insert { key, data }
+    }
+
+    fn overwrite(entity_type: &str, values: Vec<(&str, Value)>) -> EntityModification {
+        let (key, data) = entity(entity_type, values);
+        EntityModification::Overwrite { key, data }
+    }
+
+    fn remove(entity_type: &str, id: &str) -> EntityModification {
+        EntityModification::Remove {
+            key: EntityKey::data(entity_type.to_owned(), id.to_owned()),
+        }
+    }
+
+    fn user(id: &str) -> EntityModification {
+        insert("User", vec![("id", Value::from(id))])
+    }
+
+    fn transfer(from: &str, to: &str, value: i32) -> Vec<(&'static str, Value)> {
+        vec![
+            ("id", Value::from("t1")),
+            ("from", Value::from(from)),
+            ("to", Value::from(vec![to])),
+            ("value", Value::BigInt(value.into())),
+        ]
+    }
+
+    #[test]
+    fn references() {
+        let mut harness = Harness::new(GQL, None);
+
+        harness.transact(
+            1,
+            vec![
+                user("u1"),
+                user("u2"),
+                user("u3"),
+                insert("Transfer", transfer("u1", "u2", 5)),
+            ],
+        );
+        assert_eq!(
+            vec!["t1", "u1", "u2", "u3"],
+            harness.mock.vertices.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("Contract_transfers", "u1", "t1", 0),
+                ("Transfer_from", "t1", "u1", 0),
+                ("Transfer_to", "t1", "u2", 0),
+                ("User_transfers", "u1", "t1", 0),
+            ],
+            harness.mock.edge_keys()
+        );
+
+        // Changing the recipient moves the `Transfer_to` edge
+        harness.transact(2, vec![overwrite("Transfer", transfer("u1", "u3", 7))]);
+        assert_eq!(
+            Some("\"7\""),
+            harness.mock.tag_value("t1", "Transfer", "value")
+        );
+        assert_eq!(
+            Some("2"),
+            harness.mock.tag_value("t1", "Transfer", BLOCK_PROPERTY)
+        );
+        assert_eq!(
+            vec![
+                ("Contract_transfers", "u1", "t1", 0),
+                ("Transfer_from", "t1", "u1", 0),
+                ("Transfer_to", "t1", "u3", 0),
+                ("User_transfers", "u1", "t1", 0),
+            ],
+            harness.mock.edge_keys()
+        );
+
+        // Removing a vertex keeps the edges that other entities hold
+        harness.transact(3, vec![remove("User", "u1")]);
+        assert_eq!(
+            vec!["t1", "u2", "u3"],
+            harness.mock.vertices.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("Contract_transfers", "u1", "t1", 0),
+                ("Transfer_from", "t1", "u1", 0),
+                ("Transfer_to", "t1", "u3", 0),
+                ("User_transfers", "u1", "t1", 0),
+            ],
+            harness.mock.edge_keys()
+        );
+
+        harness.transact(4, vec![remove("Transfer", "t1")]);
+        assert_eq!(
+            vec!["u2", "u3"],
+            harness.mock.vertices.keys().collect::<Vec<_>>()
+        );
+        assert!(harness.mock.edges.is_empty());
+    }
+
+    #[test]
+    fn mapped_edges() {
+        const GQL: &str = "
+            type Account @entity { id: ID!, balance: BigInt! }
+            type Transfer @entity {
+                id: ID!
+                from: Account!
+                to: Account!
+                value: BigInt!
+                block: BigInt!
+            }";
+
+        let mapping = GraphMapping {
+            vertices: vec![VertexMapping {
+                entity: "Account".to_owned(),
+                properties: None,
+            }],
+            edges: vec![EdgeMapping {
+                entity: "Transfer".to_owned(),
+                source: "from".to_owned(),
+                target: "to".to_owned(),
+                rank: Some("block".to_owned()),
+                properties: Some(vec!["value".to_owned()]),
+            }],
+        };
+        let mut harness = Harness::new(GQL, Some(&mapping));
+
+        fn account(id: &str, balance: i32) -> Vec<(&str, Value)> {
+            vec![
+                ("id", Value::from(id)),
+                ("balance", Value::BigInt(balance.into())),
+            ]
+        }
+        fn transfer(to: &str, value: i32) -> Vec<(&'static str, Value)> {
+            vec![
+                ("id", Value::from("t1")),
+                ("from", Value::from("a1")),
+                ("to", Value::from(to)),
+                ("value", Value::BigInt(value.into())),
+                ("block", Value::BigInt(1.into())),
+            ]
+        }
+
+        harness.transact(
+            1,
+            vec![
+                insert("Account", account("a1", 10)),
+                insert("Account", account("a2", 0)),
+                insert("Account", account("a3", 0)),
+                insert("Transfer", transfer("a2", 5)),
+            ],
+        );
+        assert_eq!(vec![("Transfer", "a1", "a2", 1)], harness.mock.edge_keys());
+
+        // Changing the target of the transfer moves the edge
+        harness.transact(
+            2,
+            vec![
+                overwrite("Account", account("a1", 4)),
+                overwrite("Transfer", transfer("a3", 6)),
+            ],
+        );
+        assert_eq!(vec![("Transfer", "a1", "a3", 1)], harness.mock.edge_keys());
+        let key = ("Transfer".to_owned(), "a1".to_owned(), "a3".to_owned(), 1);
+        assert_eq!("\"6\"", harness.mock.edges[&key]["value"]);
+        assert_eq!(
+            Some("\"4\""),
+            harness.mock.tag_value("a1", "Account", "balance")
+        );
+        assert_eq!(
+            Some("2"),
+            harness.mock.tag_value("a1", "Account", BLOCK_PROPERTY)
+        );
+
+        harness.transact(3, vec![remove("Transfer", "t1")]);
+        assert!(harness.mock.edges.is_empty());
+        assert_eq!(3, harness.mock.vertices.len());
+    }
+}
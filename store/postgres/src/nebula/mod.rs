@@ -0,0 +1,66 @@
+//! Mirror the entities of a deployment into a NebulaGraph space
+pub(crate) mod backfill;
+mod layout;
+#[cfg(test)]
+mod mock;
+pub(crate) mod outbox;
+pub(crate) mod query;
+mod sink;
+pub(crate) mod traversal;
+pub(crate) mod value;
+pub(crate) mod verify;
+
+use graph::prelude::{anyhow, warn, Logger, StoreError};
+use nebula_rust::graph_client::connection::NebulaError;
+use nebula_rust::graph_client::pool_config::PoolConfig;
+use nebula_rust::value::ResultError;
+
+pub(crate) use layout::{drop_space_query, GraphLayout, GraphWrites};
+pub(crate) use sink::NebulaSink;
+
+/// How to connect to NebulaGraph, and how to create the spaces for
+/// deployments
+#[derive(Clone, Debug)]
+pub struct NebulaConfig {
+    pub pool: PoolConfig,
+    pub space: SpaceConfig,
+}
+
+/// The settings for the spaces that we create. They only apply to new
+/// spaces; NebulaGraph can not change them for existing ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpaceConfig {
+    pub partition_num: u32,
+    pub replica_factor: u32,
+    /// Vertex ids are entity ids, and the spaces use
+    /// `FIXED_STRING(vid_length)` for them
+    pub vid_length: u32,
+}
+
+impl Default for SpaceConfig {
+    fn default() -> Self {
+        SpaceConfig {
+            partition_num: 1,
+            replica_factor: 1,
+            vid_length: 50,
+        }
+    }
+}
+
+/// The `StoreError` for an error from NebulaGraph. Errors that might go
+/// away when the request is repeated become `DatabaseUnavailable` so that
+/// callers retry them; since that drops the details, they are logged here
+pub(crate) fn store_error(logger: &Logger, e: NebulaError) -> StoreError {
+    if e.is_retryable() {
+        warn!(logger, "NebulaGraph unavailable"; "error" => e.to_string());
+        StoreError::DatabaseUnavailable
+    } else {
+        StoreError::Unknown(anyhow!("NebulaGraph request failed: {}", e))
+    }
+}
+
+/// The `StoreError` for a result from NebulaGraph that does not have the
+/// expected shape
+pub(crate) fn result_error(e: ResultError) -> StoreError {
+    StoreError::Unknown(anyhow!("unexpected result from NebulaGraph: {}", e))
+}
@@ -0,0 +1,262 @@
+//! A transactional outbox for graph mutations.
+//!
+//! The nGQL statements that mirror the changes of a block into NebulaGraph
+//! are recorded in `subgraphs.nebula_outbox` in the same transaction that
+//! writes the block to Postgres and moves the block pointer. A background
+//! job replays them to NebulaGraph in the order in which they were recorded
+//! and only removes them once NebulaGraph has acknowledged them. Since the
+//! statements only insert, overwrite and delete, replaying them more than
+//! once is harmless.
+//!
+//! When a deployment is reverted, the entries for the reverted blocks that
+//! have not been written yet are dropped, and an entry with the statements
+//! that undo the writes above the new head is recorded instead.
+//!
+//! The sync cursor of a deployment is the latest block for which all
+//! mutations have been written to NebulaGraph; it is derived from the
+//! oldest entry in the outbox and the block pointer of the deployment.
+//!
+//! When a deployment is removed, its space is recorded in
+//! `subgraphs.nebula_dropped_space` in the same transaction that removes
+//! its metadata, and the job drops the space before replaying mutations.
+//! In the same way, a new deployment is recorded in
+//! `subgraphs.nebula_new_space` in the transaction that creates it, and
+//! the job creates its space; its mutations are held back until then.
+//! They are also held back while the deployment is being backfilled.
+use diesel::dsl::{count_star, min, not};
+use diesel::prelude::*;
+use diesel::{delete, insert_into, PgConnection};
+use graph::data::subgraph::status;
+use graph::prelude::{BlockNumber, StoreError};
+
+use crate::deployment;
+use crate::primary::{DeploymentId, Site};
+
+use super::backfill::nebula_backfill;
+
+table! {
+    subgraphs.nebula_outbox (id) {
+        id -> BigInt,
+        deployment -> Integer,
+        block_number -> Integer,
+        statements -> Array<Text>,
+    }
+}
+
+table! {
+    subgraphs.nebula_dropped_space (space) {
+        space -> Text,
+        dropped_at -> Timestamptz,
+    }
+}
+
+table! {
+    subgraphs.nebula_new_space (deployment) {
+        deployment -> Integer,
+        created_at -> Timestamptz,
+    }
+}
+
+/// The mutations for one block of one deployment
+#[derive(Queryable)]
+pub struct Entry {
+    pub id: i64,
+    pub deployment: DeploymentId,
+    pub block_number: BlockNumber,
+    pub statements: Vec<String>,
+}
+
+/// Record `statements` as the mutations for `block`
+pub fn insert(
+    conn: &PgConnection,
+    site: &Site,
+    block: BlockNumber,
+    statements: Vec<String>,
+) -> Result<(), StoreError> {
+    use nebula_outbox as o;
+
+    if statements.is_empty() {
+        return Ok(());
+    }
+    insert_into(o::table)
+        .values((
+            o::deployment.eq(site.id),
+            o::block_number.eq(block),
+            o::statements.eq(statements),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Drop the entries of `site` for blocks above `block`; this is used when
+/// the deployment is reverted to `block`
+pub(crate) fn revert(
+    conn: &PgConnection,
+    site: &Site,
+    block: BlockNumber,
+) -> Result<(), StoreError> {
+    use nebula_outbox as o;
+
+    delete(
+        o::table
+            .filter(o::deployment.eq(site.id))
+            .filter(o::block_number.gt(block)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// Drop the entries of `site` for blocks up to and including `block`;
+/// this is used when a backfill writes the entities as of `block`
+pub fn drop_until(conn: &PgConnection, site: &Site, block: BlockNumber) -> Result<(), StoreError> {
+    use nebula_outbox as o;
+
+    delete(
+        o::table
+            .filter(o::deployment.eq(site.id))
+            .filter(o::block_number.le(block)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+/// The deployments that have mutations waiting in the outbox, except for
+/// the ones whose mutations are held back; see `pending`
+pub fn deployments(conn: &PgConnection) -> Result<Vec<DeploymentId>, StoreError> {
+    use nebula_backfill as b;
+    use nebula_new_space as ns;
+    use nebula_outbox as o;
+
+    let backfilling = b::table
+        .filter(b::finished_at.is_null())
+        .select(b::deployment);
+    o::table
+        .filter(not(o::deployment.eq_any(ns::table.select(ns::deployment))))
+        .filter(not(o::deployment.eq_any(backfilling)))
+        .select(o::deployment)
+        .distinct()
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// The oldest `limit` entries of `deployment`, in the order in which they
+/// were recorded. There are none while the space of the deployment has
+/// not been created, since writing them would fail. There are none either
+/// while a backfill of the deployment has not finished: the backfill
+/// recreates the space and loads the entities as of its block, and would
+/// overwrite what newer entries wrote with older versions of the
+/// entities. Since the backfill runs in `graphman`, this has to be
+/// checked in the database
+pub fn pending(
+    conn: &PgConnection,
+    deployment: DeploymentId,
+    limit: i64,
+) -> Result<Vec<Entry>, StoreError> {
+    use nebula_backfill as b;
+    use nebula_new_space as ns;
+    use nebula_outbox as o;
+
+    let backfilling = b::table
+        .filter(b::finished_at.is_null())
+        .select(b::deployment);
+    o::table
+        .filter(o::deployment.eq(deployment))
+        .filter(not(o::deployment.eq_any(ns::table.select(ns::deployment))))
+        .filter(not(o::deployment.eq_any(backfilling)))
+        .order_by(o::id)
+        .limit(limit)
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// Remove the entry `id` from the outbox once NebulaGraph has acknowledged
+/// its mutations
+pub(crate) fn acknowledge(conn: &PgConnection, id: i64) -> Result<(), StoreError> {
+    use nebula_outbox as o;
+
+    delete(o::table.filter(o::id.eq(id))).execute(conn)?;
+    Ok(())
+}
+
+/// Record that `space` has to be dropped
+pub(crate) fn drop_space(conn: &PgConnection, space: &str) -> Result<(), StoreError> {
+    use nebula_dropped_space as ds;
+
+    insert_into(ds::table)
+        .values(ds::space.eq(space))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The spaces that still have to be dropped, oldest first
+pub(crate) fn dropped_spaces(conn: &PgConnection) -> Result<Vec<String>, StoreError> {
+    use nebula_dropped_space as ds;
+
+    ds::table
+        .select(ds::space)
+        .order_by(ds::dropped_at)
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// Forget `space` once NebulaGraph has dropped it
+pub(crate) fn space_dropped(conn: &PgConnection, space: &str) -> Result<(), StoreError> {
+    use nebula_dropped_space as ds;
+
+    delete(ds::table.filter(ds::space.eq(space))).execute(conn)?;
+    Ok(())
+}
+
+/// Record that the space of `site` has to be created
+pub(crate) fn new_space(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    use nebula_new_space as ns;
+
+    insert_into(ns::table)
+        .values(ns::deployment.eq(site.id))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The deployments whose space still has to be created, oldest first
+pub fn new_spaces(conn: &PgConnection) -> Result<Vec<DeploymentId>, StoreError> {
+    use nebula_new_space as ns;
+
+    ns::table
+        .select(ns::deployment)
+        .order_by(ns::created_at)
+        .load(conn)
+        .map_err(StoreError::from)
+}
+
+/// Forget the space of `site` once NebulaGraph has created it
+pub fn space_created(conn: &PgConnection, site: &Site) -> Result<(), StoreError> {
+    use nebula_new_space as ns;
+
+    delete(ns::table.filter(ns::deployment.eq(site.id))).execute(conn)?;
+    Ok(())
+}
+
+/// How far the NebulaGraph mirror of `site` lags behind the deployment
+pub(crate) fn sync_status(
+    conn: &PgConnection,
+    site: &Site,
+) -> Result<status::NebulaSync, StoreError> {
+    use nebula_outbox as o;
+
+    let (pending_blocks, oldest): (i64, Option<BlockNumber>) = o::table
+        .filter(o::deployment.eq(site.id))
+        .select((count_star(), min(o::block_number)))
+        .first(conn)?;
+    let head_block = deployment::block_ptr(conn, &site.deployment)?.map(|ptr| ptr.number);
+    let synced_block = match oldest {
+        Some(oldest) => Some(oldest - 1).filter(|block| *block >= 0),
+        None => head_block,
+    };
+    Ok(status::NebulaSync {
+        synced_block,
+        head_block,
+        pending_blocks,
+    })
+}
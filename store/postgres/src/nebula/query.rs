@@ -0,0 +1,125 @@
+//! Run the nGQL statements that clients send to the `/nebula` route of a
+//! deployment in its NebulaGraph space.
+//!
+//! Statements are checked with `ngql::is_read_only` before they are sent,
+//! and run on the sessions for reads of the `NebulaSink`, in the space of
+//! the deployment. Since the client does not get to pick the space with
+//! `USE`, it can only see the graph of that deployment. A `LIMIT` is
+//! piped after native nGQL statements so that NebulaGraph never sends
+//! more rows than we return. openCypher statements like `MATCH ... RETURN`
+//! can not be piped, and their rows are only cut off once graphd has sent
+//! them.
+use graph::components::store::NebulaRows;
+use graph::prelude::{serde_json, StoreError};
+use nebula_rust::graph_client::connection::Value as NebulaValue;
+use nebula_rust::graph_client::ngql::{in_space, is_opencypher, is_read_only};
+use nebula_rust::value::de::from_value;
+use nebula_rust::value::{ResultError, ResultSet};
+
+use super::{result_error, GraphLayout, NebulaSink};
+
+/// `statement` without a trailing `;`, if it only reads from the space
+fn checked(statement: &str) -> Result<&str, StoreError> {
+    if !is_read_only(statement) {
+        return Err(StoreError::QueryExecutionError(
+            "only statements that read from the graph, like MATCH, GO, FETCH and LOOKUP, \
+             can be run, and they must not contain comments"
+                .to_string(),
+        ));
+    }
+    Ok(statement
+        .trim()
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace()))
+}
+
+/// `statement` limited to one row more than `max_rows`, so that we can
+/// tell whether it has more rows than we return. openCypher statements are
+/// left alone since graphd rejects a pipe after them
+fn limited(statement: &str, max_rows: usize) -> String {
+    if is_opencypher(statement) {
+        statement.to_string()
+    } else {
+        format!("{} | LIMIT {}", statement, max_rows + 1)
+    }
+}
+
+/// The `values` of a row as JSON; see `nebula_rust::value::de` for how
+/// vertices, edges and paths turn into objects
+fn json_row(values: &[NebulaValue]) -> Result<Vec<serde_json::Value>, ResultError> {
+    values.iter().cloned().map(from_value).collect()
+}
+
+/// At most `max_rows` of the rows of `result`; only the rows that are
+/// returned are turned into JSON
+fn rows(result: &ResultSet, max_rows: usize) -> Result<NebulaRows, StoreError> {
+    let rows = result
+        .rows()
+        .take(max_rows)
+        .map(|record| json_row(record.values()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(result_error)?;
+    Ok(NebulaRows {
+        columns: result.column_names().to_vec(),
+        rows,
+        truncated: result.len() > max_rows,
+    })
+}
+
+/// Run `statement` in the space of `graph` and return at most `max_rows`
+/// of its rows
+pub(crate) async fn run(
+    sink: &NebulaSink,
+    graph: &GraphLayout,
+    statement: &str,
+    max_rows: usize,
+) -> Result<NebulaRows, StoreError> {
+    let statement = limited(checked(statement)?, max_rows);
+    let result = sink.read(in_space(&graph.space, &statement)).await?;
+    rows(&result, max_rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use graph::prelude::serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn statements_are_checked() {
+        assert_eq!(
+            "GO FROM \"a\" OVER `Transfer` YIELD dst(edge)",
+            checked(" GO FROM \"a\" OVER `Transfer` YIELD dst(edge) ;\n").unwrap()
+        );
+        assert!(checked("DROP SPACE `sgd1`").is_err());
+        assert!(checked("USE `sgd2`; MATCH (v) RETURN v LIMIT 1").is_err());
+    }
+
+    #[test]
+    fn statements_are_limited() {
+        let statement = checked("LOOKUP ON `User` YIELD id(vertex) AS id;").unwrap();
+        assert_eq!(
+            "LOOKUP ON `User` YIELD id(vertex) AS id | LIMIT 11",
+            limited(statement, 10)
+        );
+
+        // graphd does not accept a pipe after openCypher statements
+        let statement = checked("MATCH (v:`User`)-[e]->(w) RETURN id(w) AS id;").unwrap();
+        assert_eq!(
+            "MATCH (v:`User`)-[e]->(w) RETURN id(w) AS id",
+            limited(statement, 10)
+        );
+    }
+
+    #[test]
+    fn values_as_json() {
+        let values = vec![
+            NebulaValue::sVal(b"a1".to_vec()),
+            NebulaValue::iVal(7),
+            NebulaValue::bVal(true),
+        ];
+        assert_eq!(
+            vec![json!("a1"), json!(7), json!(true)],
+            json_row(&values).unwrap()
+        );
+    }
+}
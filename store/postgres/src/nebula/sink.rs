@@ -0,0 +1,441 @@
+//! A long-lived task that does all the work with NebulaGraph for a shard.
+//!
+//! The task owns the connection pool for NebulaGraph and keeps one session
+//! open that it uses for all requests, reconnecting when the connection is
+//! lost. Requests reach it through a bounded channel; when the channel is
+//! full, callers wait until the task has caught up, so that writers can not
+//! get ahead of NebulaGraph by more than the size of the channel.
+//!
+//! NebulaGraph creates spaces, tags and edge types asynchronously. Instead
+//! of sleeping for a fixed time after creating them, callers use
+//! `wait_for_schema`, which polls `SHOW TAGS` and `SHOW EDGES` until they
+//! are visible or a timeout expires.
+//!
+//! Statements that only read, like the ones for GraphQL queries, do not go
+//! through that task: they are served by `GRAPH_NEBULA_READ_SESSIONS`
+//! tasks that share a channel and each keep a session of their own, so that
+//! reads neither wait for writes nor hold them up. When the caller of a
+//! statement gives up waiting for it, e.g. because it timed out, the task
+//! closes its session instead of reusing it, since graphd might still
+//! answer the statement on that connection.
+//!
+//! The tasks also check the health of the pool and of their session
+//! periodically, and the size of the pool is reported as metrics.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use graph::prelude::{
+    anyhow, debug, info, o, warn, Gauge, Logger, MetricsRegistry, StoreError, ENV_VARS,
+};
+use graph::tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex};
+use nebula_rust::graph_client::connection::NebulaError;
+use nebula_rust::graph_client::connection_pool::{
+    ConnectionPool_nebula, PoolEventHandler, PoolState,
+};
+use nebula_rust::graph_client::ngql::{Identifier, Use};
+use nebula_rust::graph_client::session::Session;
+use nebula_rust::value::ResultSet;
+
+use super::{store_error, NebulaConfig, SpaceConfig};
+use crate::Shard;
+
+/// How often `wait_for_schema` checks whether the schema is visible
+const SCHEMA_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often the pool and the session are checked
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+enum Request {
+    /// Run the statements in order, stopping at the first one that fails
+    Execute {
+        statements: Vec<String>,
+        done: oneshot::Sender<Result<(), StoreError>>,
+    },
+    /// Run `statement` and return its result
+    Query {
+        statement: String,
+        done: oneshot::Sender<Result<ResultSet, StoreError>>,
+    },
+    /// Wait until `space` and its `tags` and `edges` can be used
+    WaitForSchema {
+        space: Identifier,
+        tags: Vec<Identifier>,
+        edges: Vec<Identifier>,
+        timeout: Duration,
+        done: oneshot::Sender<Result<(), StoreError>>,
+    },
+}
+
+impl Request {
+    fn fail(self, error: StoreError) {
+        match self {
+            Request::Execute { done, .. } | Request::WaitForSchema { done, .. } => {
+                done.send(Err(error)).ok();
+            }
+            Request::Query { done, .. } => {
+                done.send(Err(error)).ok();
+            }
+        }
+    }
+}
+
+/// Reports the state of the pool as metrics and logs when graphd hosts go
+/// down or come back
+struct PoolMetrics {
+    logger: Logger,
+    open_gauge: Gauge,
+    idle_gauge: Gauge,
+    waiting_gauge: Gauge,
+}
+
+impl PoolMetrics {
+    fn new(logger: Logger, registry: &dyn MetricsRegistry, shard: &Shard) -> Self {
+        let const_labels = HashMap::from([("shard".to_string(), shard.to_string())]);
+        let gauge = |name: &str, help: &str| {
+            registry
+                .global_gauge(name, help, const_labels.clone())
+                .unwrap_or_else(|_| panic!("failed to create `{}` gauge", name))
+        };
+        PoolMetrics {
+            logger,
+            open_gauge: gauge(
+                "nebula_connection_open_count",
+                "The number of open NebulaGraph connections",
+            ),
+            idle_gauge: gauge(
+                "nebula_connection_idle_count",
+                "The number of open NebulaGraph connections that are not in use",
+            ),
+            waiting_gauge: gauge(
+                "nebula_connection_waiting_count",
+                "The number of callers waiting for a NebulaGraph connection",
+            ),
+        }
+    }
+}
+
+impl PoolEventHandler for PoolMetrics {
+    fn handle_state(&self, state: PoolState) {
+        self.open_gauge.set(state.open as f64);
+        self.idle_gauge.set(state.idle as f64);
+        self.waiting_gauge.set(state.waiting as f64);
+    }
+
+    fn handle_host_down(&self, address: &str) {
+        warn!(self.logger, "NebulaGraph host is down"; "address" => address);
+    }
+
+    fn handle_host_up(&self, address: &str) {
+        info!(self.logger, "NebulaGraph host is up again"; "address" => address);
+    }
+}
+
+/// The channels to the task that writes and to the tasks that read
+#[derive(Clone)]
+struct Senders {
+    writer: mpsc::Sender<Request>,
+    reader: mpsc::Sender<Request>,
+}
+
+pub(crate) struct NebulaSink {
+    logger: Logger,
+    conf: NebulaConfig,
+    registry: Arc<dyn MetricsRegistry>,
+    shard: Shard,
+    /// The tasks are started when the sink is first used since the sink is
+    /// created outside of the Tokio runtime
+    senders: Mutex<Option<Senders>>,
+}
+
+impl NebulaSink {
+    pub fn new(
+        logger: &Logger,
+        conf: NebulaConfig,
+        registry: Arc<dyn MetricsRegistry>,
+        shard: Shard,
+    ) -> Self {
+        NebulaSink {
+            logger: logger.new(o!("component" => "NebulaSink")),
+            conf,
+            registry,
+            shard,
+            senders: Mutex::new(None),
+        }
+    }
+
+    /// The settings for new spaces
+    pub fn space(&self) -> &SpaceConfig {
+        &self.conf.space
+    }
+
+    fn senders(&self) -> Senders {
+        let mut senders = self.senders.lock().unwrap();
+        senders
+            .get_or_insert_with(|| {
+                let metrics = PoolMetrics::new(self.logger.clone(), &*self.registry, &self.shard);
+                let pool = Arc::new(ConnectionPool_nebula::with_events(
+                    &self.conf.pool,
+                    Box::new(metrics),
+                ));
+
+                let (writer, receiver) = mpsc::channel(ENV_VARS.store.nebula_sink_queue_size);
+                let receiver = Arc::new(AsyncMutex::new(receiver));
+                graph::spawn(run(self.logger.clone(), pool.clone(), receiver));
+
+                let sessions = ENV_VARS.store.nebula_read_sessions.max(1);
+                let (reader, receiver) = mpsc::channel(sessions);
+                let receiver = Arc::new(AsyncMutex::new(receiver));
+                for _ in 0..sessions {
+                    let logger = self.logger.new(o!("reader" => true));
+                    graph::spawn(run(logger, pool.clone(), receiver.clone()));
+                }
+                Senders { writer, reader }
+            })
+            .clone()
+    }
+
+    async fn send<T>(
+        &self,
+        sender: mpsc::Sender<Request>,
+        make_request: impl FnOnce(oneshot::Sender<Result<T, StoreError>>) -> Request,
+    ) -> Result<T, StoreError> {
+        let (done, result) = oneshot::channel();
+        sender
+            .send(make_request(done))
+            .await
+            .map_err(|_| anyhow!("the NebulaGraph sink has stopped"))?;
+        result
+            .await
+            .map_err(|_| anyhow!("the NebulaGraph sink has stopped"))?
+    }
+
+    /// Run `statements` in order, stopping at the first one that fails.
+    /// Waits while the sink is busy with earlier requests
+    pub async fn execute(&self, statements: Vec<String>) -> Result<(), StoreError> {
+        self.send(self.senders().writer, |done| Request::Execute {
+            statements,
+            done,
+        })
+        .await
+    }
+
+    /// Run `statement`, which usually reads from the graph, and return its
+    /// result. Like `execute`, it waits for earlier requests
+    pub async fn query(&self, statement: String) -> Result<ResultSet, StoreError> {
+        self.send(self.senders().writer, |done| Request::Query {
+            statement,
+            done,
+        })
+        .await
+    }
+
+    /// Run `statement`, which must only read from the graph, on one of the
+    /// sessions for reads and return its result. Unlike `query`, it does
+    /// not wait for earlier writes
+    pub async fn read(&self, statement: String) -> Result<ResultSet, StoreError> {
+        self.send(self.senders().reader, |done| Request::Query {
+            statement,
+            done,
+        })
+        .await
+    }
+
+    /// Wait until `space`, its `tags`, and its `edges` are visible. Gives up
+    /// after `GRAPH_NEBULA_SCHEMA_TIMEOUT`
+    pub async fn wait_for_schema(
+        &self,
+        space: Identifier,
+        tags: Vec<Identifier>,
+        edges: Vec<Identifier>,
+    ) -> Result<(), StoreError> {
+        let timeout = ENV_VARS.store.nebula_schema_timeout;
+        self.send(self.senders().writer, |done| Request::WaitForSchema {
+            space,
+            tags,
+            edges,
+            timeout,
+            done,
+        })
+        .await
+    }
+}
+
+/// Serve the requests from `receiver`, which tasks for reads share
+async fn run(
+    logger: Logger,
+    pool: Arc<ConnectionPool_nebula>,
+    receiver: Arc<AsyncMutex<mpsc::Receiver<Request>>>,
+) {
+    let mut session: Option<Session<'_>> = None;
+    let mut health_check = graph::tokio::time::interval(HEALTH_CHECK_INTERVAL);
+
+    loop {
+        let request = graph::tokio::select! {
+            request = async { receiver.lock().await.recv().await } => match request {
+                Some(request) => request,
+                None => break,
+            },
+            _ = health_check.tick() => {
+                check_health(&logger, &pool, &mut session).await;
+                continue;
+            }
+        };
+
+        if session.is_none() {
+            match pool.get_session(true).await {
+                Ok(s) => {
+                    debug!(logger, "Opened NebulaGraph session");
+                    session = Some(s);
+                }
+                Err(e) => {
+                    warn!(logger, "Failed to open a NebulaGraph session";
+                          "error" => e.to_string());
+                    request.fail(store_error(&logger, e));
+                    continue;
+                }
+            }
+        }
+        let s = session.as_ref().expect("the session was opened above");
+
+        // The callers might have given up waiting
+        match request {
+            Request::Execute { statements, done } => {
+                let result = execute_all(s, &statements).await;
+                done.send(checked(&logger, &mut session, result)).ok();
+            }
+            Request::Query {
+                statement,
+                mut done,
+            } => {
+                if done.is_closed() {
+                    continue;
+                }
+                let result = graph::tokio::select! {
+                    result = s.execute_checked(&statement) => Some(result),
+                    _ = done.closed() => None,
+                };
+                match result {
+                    Some(result) => {
+                        done.send(checked(&logger, &mut session, result)).ok();
+                    }
+                    None => {
+                        // graphd keeps running the statement and would
+                        // answer it on the connection later, so the session
+                        // can not be used for other statements
+                        warn!(logger, "Abandoned a NebulaGraph statement, closing its session";
+                              "statement" => &statement);
+                        if let Some(s) = session.take() {
+                            s.discard();
+                        }
+                    }
+                }
+            }
+            Request::WaitForSchema {
+                space,
+                tags,
+                edges,
+                timeout,
+                done,
+            } => {
+                let result = wait_for_schema(s, &space, &tags, &edges, timeout).await;
+                done.send(checked(&logger, &mut session, result)).ok();
+            }
+        }
+    }
+}
+
+/// Turn the error of a request into a `StoreError`, and drop the session
+/// if its connection was lost
+fn checked<T>(
+    logger: &Logger,
+    session: &mut Option<Session<'_>>,
+    result: Result<T, NebulaError>,
+) -> Result<T, StoreError> {
+    result.map_err(|e| {
+        if e.is_connection_lost() {
+            warn!(logger, "Lost the connection to NebulaGraph, will reconnect";
+                  "error" => e.to_string());
+            *session = None;
+        }
+        store_error(logger, e)
+    })
+}
+
+async fn check_health<'a>(
+    logger: &Logger,
+    pool: &'a ConnectionPool_nebula,
+    session: &mut Option<Session<'a>>,
+) {
+    pool.check_health().await;
+    if let Some(s) = session {
+        if let Err(e) = s.execute_checked("YIELD 1").await {
+            if e.is_connection_lost() {
+                warn!(logger, "Lost the connection to NebulaGraph, will reconnect";
+                      "error" => e.to_string());
+                *session = None;
+            }
+        }
+    }
+}
+
+async fn execute_all(session: &Session<'_>, statements: &[String]) -> Result<(), NebulaError> {
+    for statement in statements {
+        session.execute_checked(statement).await?;
+    }
+    Ok(())
+}
+
+async fn wait_for_schema(
+    session: &Session<'_>,
+    space: &Identifier,
+    tags: &[Identifier],
+    edges: &[Identifier],
+    timeout: Duration,
+) -> Result<(), NebulaError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let missing = missing_schema(session, space, tags, edges).await?;
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(NebulaError::Timeout(format!(
+                "waiting {}s for {} in space {}",
+                timeout.as_secs(),
+                missing.join(", "),
+                space
+            )));
+        }
+        graph::tokio::time::sleep(SCHEMA_POLL_INTERVAL).await;
+    }
+}
+
+/// The names of the parts of the schema that are not visible yet. While
+/// the space itself can not be used, that is the space
+async fn missing_schema(
+    session: &Session<'_>,
+    space: &Identifier,
+    tags: &[Identifier],
+    edges: &[Identifier],
+) -> Result<Vec<String>, NebulaError> {
+    let mut missing = vec![];
+    for (kind, show, names) in [
+        ("tag", "SHOW TAGS", tags),
+        ("edge type", "SHOW EDGES", edges),
+    ] {
+        let query = format!("{}; {};", Use(space.clone()), show);
+        let existing = match session.execute_checked(&query).await {
+            Ok(result) => result.column::<String>("Name")?,
+            Err(e) if e.is_connection_lost() => return Err(e),
+            Err(_) => return Ok(vec![format!("space {}", space)]),
+        };
+        missing.extend(
+            names
+                .iter()
+                .filter(|name| !existing.iter().any(|e| e == name.as_str()))
+                .map(|name| format!("{} {}", kind, name)),
+        );
+    }
+    Ok(missing)
+}
@@ -0,0 +1,215 @@
+//! Answer the traversal fields of the GraphQL API from the NebulaGraph
+//! space of a deployment.
+//!
+//! `neighbors` turns into a `GO` and `paths` and `shortestPath` into a
+//! `FIND PATH` over one edge type. Both only return vertex ids; the
+//! entities for them are loaded from Postgres by the GraphQL execution.
+//! The statements run on the sessions for reads of the `NebulaSink`, and
+//! therefore see the graph as of the last block that has been written to
+//! it, not as of the block of the query. Both statements order their
+//! results and apply the `limit` of the query in NebulaGraph, so that only
+//! the page that the GraphQL query asks for is sent back.
+use graph::components::store::{NeighborsQuery, PathsQuery, TraversalDirection};
+use graph::prelude::futures03::future::try_join_all;
+use graph::prelude::StoreError;
+use nebula_rust::graph_client::ngql::{in_space, Direction, FindPath, Go, Identifier, PathKind};
+
+use super::{result_error, GraphLayout, NebulaSink};
+
+/// The edge type `name` of `graph`, if it is one of the
+/// `GraphLayout::traversal_edges`
+fn edge(graph: &GraphLayout, name: &str) -> Result<Identifier, StoreError> {
+    graph
+        .edges
+        .iter()
+        .find(|edge| {
+            edge.name.as_str() == name
+                && edge.source == edge.target
+                && graph.tags.contains_key(&edge.source)
+        })
+        .map(|edge| edge.name.clone())
+        .ok_or_else(|| {
+            StoreError::QueryExecutionError(format!(
+                "the edge type `{}` can not be traversed",
+                name
+            ))
+        })
+}
+
+fn direction(direction: TraversalDirection) -> Direction {
+    match direction {
+        TraversalDirection::Out => Direction::Outgoing,
+        TraversalDirection::In => Direction::Incoming,
+        TraversalDirection::Both => Direction::Both,
+    }
+}
+
+fn neighbors_query(graph: &GraphLayout, query: &NeighborsQuery) -> Result<String, StoreError> {
+    let go = Go {
+        min_steps: Some(1),
+        max_steps: query.depth,
+        from: vec![query.from.clone()],
+        over: vec![edge(graph, &query.edge)?],
+        direction: direction(query.direction),
+        filter: None,
+        yields: vec!["DISTINCT id($$) AS vid".to_string()],
+    };
+    // The vertex the traversal starts from can be among the results, and
+    // is removed from them afterwards; fetch one more row to make up for it
+    let statement = match query.limit {
+        Some(limit) => format!("{} | ORDER BY $-.vid | LIMIT {}", go, limit as u64 + 1),
+        None => format!("{} | ORDER BY $-.vid", go),
+    };
+    Ok(in_space(&graph.space, &statement))
+}
+
+fn paths_query(graph: &GraphLayout, query: &PathsQuery) -> Result<String, StoreError> {
+    let find = FindPath {
+        kind: if query.shortest {
+            PathKind::Shortest
+        } else {
+            PathKind::NoLoop
+        },
+        from: vec![query.from.clone()],
+        to: vec![query.to.clone()],
+        over: vec![edge(graph, &query.edge)?],
+        direction: direction(query.direction),
+        max_steps: query.max_hops,
+        alias: "p".to_string(),
+    };
+    let mut statement = format!(
+        "{} | YIELD [n IN nodes($-.p) | id(n)] AS vids, length($-.p) AS len \
+         | ORDER BY $-.len, $-.vids",
+        find
+    );
+    if let Some(limit) = query.limit {
+        statement.push_str(&format!(" | LIMIT {}", limit));
+    }
+    Ok(in_space(&graph.space, &statement))
+}
+
+/// The ids of the vertices that each of `queries` reaches, without the
+/// vertex it starts from, ordered by id
+pub(crate) async fn neighbors(
+    sink: &NebulaSink,
+    graph: &GraphLayout,
+    queries: &[NeighborsQuery],
+) -> Result<Vec<Vec<String>>, StoreError> {
+    let statements = queries
+        .iter()
+        .map(|query| neighbors_query(graph, query))
+        .collect::<Result<Vec<_>, _>>()?;
+    let results = try_join_all(statements.into_iter().map(|stmt| sink.read(stmt))).await?;
+    results
+        .iter()
+        .zip(queries)
+        .map(|(result, query)| {
+            let mut vids = result.column::<String>("vid").map_err(result_error)?;
+            vids.retain(|vid| vid != &query.from);
+            if let Some(limit) = query.limit {
+                vids.truncate(limit as usize);
+            }
+            Ok(vids)
+        })
+        .collect()
+}
+
+/// The paths that each of `queries` finds, as the ids of the vertices
+/// along each path, ordered by their length and then by the ids
+pub(crate) async fn paths(
+    sink: &NebulaSink,
+    graph: &GraphLayout,
+    queries: &[PathsQuery],
+) -> Result<Vec<Vec<Vec<String>>>, StoreError> {
+    let statements = queries
+        .iter()
+        .map(|query| paths_query(graph, query))
+        .collect::<Result<Vec<_>, _>>()?;
+    let results = try_join_all(statements.into_iter().map(|stmt| sink.read(stmt))).await?;
+    results
+        .iter()
+        .map(|result| result.column::<Vec<String>>("vids").map_err(result_error))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use graph::components::store::EntityType;
+
+    use super::*;
+    use crate::nebula::layout::{EdgeType, TagType};
+
+    fn graph() -> GraphLayout {
+        let account = EntityType::from("Account");
+        let edge = |name: &str, target: &EntityType| {
+            Arc::new(EdgeType {
+                name: Identifier::new(name).unwrap(),
+                source: account.clone(),
+                target: target.clone(),
+                properties: vec![],
+            })
+        };
+        let tag = TagType {
+            name: Identifier::new("Account").unwrap(),
+            properties: vec![],
+        };
+        GraphLayout::for_tests(
+            Identifier::new("sgd1").unwrap(),
+            HashMap::from([(account.clone(), tag)]),
+            vec![
+                edge("Account_token", &EntityType::from("Token")),
+                edge("Transfer", &account),
+            ],
+        )
+    }
+
+    #[test]
+    fn statements() {
+        let graph = graph();
+
+        let query = NeighborsQuery {
+            from: "a1".to_string(),
+            edge: "Transfer".to_string(),
+            direction: TraversalDirection::Both,
+            depth: 3,
+            limit: Some(10),
+        };
+        assert_eq!(
+            "USE `sgd1`; GO 1 TO 3 STEPS FROM \"a1\" OVER `Transfer` BIDIRECT \
+             YIELD DISTINCT id($$) AS vid | ORDER BY $-.vid | LIMIT 11;",
+            neighbors_query(&graph, &query).unwrap()
+        );
+
+        let query = PathsQuery {
+            from: "a1".to_string(),
+            to: "a2".to_string(),
+            edge: "Transfer".to_string(),
+            direction: TraversalDirection::Out,
+            max_hops: 4,
+            shortest: true,
+            limit: Some(1),
+        };
+        assert_eq!(
+            "USE `sgd1`; FIND SHORTEST PATH FROM \"a1\" TO \"a2\" OVER `Transfer` \
+             UPTO 4 STEPS YIELD path AS p \
+             | YIELD [n IN nodes($-.p) | id(n)] AS vids, length($-.p) AS len \
+             | ORDER BY $-.len, $-.vids | LIMIT 1;",
+            paths_query(&graph, &query).unwrap()
+        );
+
+        // Edges to other types and unknown edges can not be traversed
+        let query = PathsQuery {
+            edge: "Account_token".to_string(),
+            ..query
+        };
+        assert!(paths_query(&graph, &query).is_err());
+        let query = PathsQuery {
+            edge: "Nope".to_string(),
+            ..query
+        };
+        assert!(paths_query(&graph, &query).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! How entity values are stored as NebulaGraph properties.
+//!
+//! | Column type  | Property type | Encoding                                   |
+//! |--------------|---------------|--------------------------------------------|
+//! | `Boolean`    | `bool`        |                                            |
+//! | `Int`        | `int32`       |                                            |
+//! | `BigInt`     | `string`      | decimal digits with an optional `-`        |
+//! | `BigDecimal` | `string`      | the decimal string of the normalized value |
+//! | `Bytes`      | `string`      | hex with a `0x` prefix                     |
+//! | `String`     | `string`      |                                            |
+//! | enums        | `string`      | the name of the enum value                 |
+//!
+//! `BigInt` and `BigDecimal` have no fixed width and are stored as strings
+//! so that no precision is lost; comparisons in nGQL on these properties
+//! are therefore string comparisons.
+//!
+//! NebulaGraph can not declare properties of list type, and list columns
+//! are stored as `string` properties that hold a JSON array. Its elements
+//! are JSON booleans for `Boolean`, JSON numbers for `Int`, and JSON
+//! strings with the encoding above for all other types.
+//!
+//! `from_nebula` reverses the mapping for values that are read back.
+use std::str::FromStr;
+
+use graph::data::store::scalar::Bytes;
+use graph::prelude::{anyhow, serde_json, BigDecimal, BigInt, StoreError, Value};
+use nebula_rust::graph_client::connection::Value as NebulaValue;
+use nebula_rust::graph_client::nebula_schema::DataType;
+use nebula_rust::graph_client::ngql::Literal;
+use serde_json::Value as JsonValue;
+
+use crate::relational::{Column, ColumnType};
+
+/// The type of the property that holds the values of `column`
+pub fn data_type(column: &Column) -> DataType {
+    if column.is_list() {
+        DataType::String
+    } else {
+        column.column_type.to_nebula_type()
+    }
+}
+
+/// The literal that stores `value` in a property
+pub fn to_literal(value: &Value) -> Literal {
+    match value {
+        Value::Null => Literal::Null,
+        Value::Bool(b) => Literal::Bool(*b),
+        Value::Int(i) => Literal::from(*i),
+        Value::String(s) => Literal::from(s.as_str()),
+        Value::BigInt(_) | Value::BigDecimal(_) | Value::Bytes(_) => {
+            Literal::from(value.to_string())
+        }
+        Value::List(_) => Literal::from(to_json(value).to_string()),
+    }
+}
+
+fn to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::from(*b),
+        Value::Int(i) => JsonValue::from(*i),
+        Value::String(s) => JsonValue::from(s.as_str()),
+        Value::BigInt(_) | Value::BigDecimal(_) | Value::Bytes(_) => {
+            JsonValue::from(value.to_string())
+        }
+        Value::List(values) => JsonValue::Array(values.iter().map(to_json).collect()),
+    }
+}
+
+/// The entity value for the property `value` that was written for
+/// `column`
+pub fn from_nebula(value: &NebulaValue, column: &Column) -> Result<Value, StoreError> {
+    let column_type = &column.column_type;
+    match value {
+        NebulaValue::nVal(_) => Ok(Value::Null),
+        NebulaValue::sVal(bytes) => {
+            let s = std::str::from_utf8(bytes).map_err(|e| {
+                anyhow!("the value of `{}` is not valid UTF-8: {}", column.field, e)
+            })?;
+            if column.is_list() {
+                let json = serde_json::from_str::<JsonValue>(s).map_err(|e| {
+                    anyhow!("the value of `{}` is not a JSON list: {}", column.field, e)
+                })?;
+                from_json(&json, column_type, &column.field)
+            } else {
+                from_str(s, column_type, &column.field)
+            }
+        }
+        NebulaValue::bVal(b) if *column_type == ColumnType::Boolean => Ok(Value::Bool(*b)),
+        NebulaValue::iVal(i) if *column_type == ColumnType::Int => int(*i, &column.field),
+        value => Err(mismatch(column_type, &column.field, value)),
+    }
+}
+
+fn from_json(json: &JsonValue, column_type: &ColumnType, field: &str) -> Result<Value, StoreError> {
+    match (json, column_type) {
+        (JsonValue::Null, _) => Ok(Value::Null),
+        (JsonValue::Array(values), _) => values
+            .iter()
+            .map(|value| from_json(value, column_type, field))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (JsonValue::Bool(b), ColumnType::Boolean) => Ok(Value::Bool(*b)),
+        (JsonValue::Number(n), ColumnType::Int) => match n.as_i64() {
+            Some(i) => int(i, field),
+            None => Err(mismatch(column_type, field, json)),
+        },
+        (JsonValue::String(s), _) => from_str(s, column_type, field),
+        _ => Err(mismatch(column_type, field, json)),
+    }
+}
+
+fn from_str(s: &str, column_type: &ColumnType, field: &str) -> Result<Value, StoreError> {
+    let value = match column_type {
+        ColumnType::BigInt => BigInt::from_str(s).ok().map(Value::BigInt),
+        ColumnType::BigDecimal => BigDecimal::from_str(s).ok().map(Value::BigDecimal),
+        ColumnType::Bytes => Bytes::from_str(s).ok().map(Value::Bytes),
+        ColumnType::String | ColumnType::Enum(_) | ColumnType::TSVector(_) => {
+            Some(Value::String(s.to_owned()))
+        }
+        ColumnType::Boolean | ColumnType::Int => None,
+    };
+    value.ok_or_else(|| mismatch(column_type, field, &s))
+}
+
+fn int(i: i64, field: &str) -> Result<Value, StoreError> {
+    i32::try_from(i)
+        .map(Value::Int)
+        .map_err(|_| StoreError::Unknown(anyhow!("the value {} of `{}` is not an Int", i, field)))
+}
+
+fn mismatch(column_type: &ColumnType, field: &str, value: &dyn std::fmt::Debug) -> StoreError {
+    StoreError::Unknown(anyhow!(
+        "the value {:?} of `{}` can not be read as {:?}",
+        value,
+        field,
+        column_type
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use graph::data::schema::Schema;
+    use graph::prelude::DeploymentHash;
+
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::layout_for_tests::{make_dummy_site, Namespace};
+    use crate::relational::Layout;
+
+    const GQL: &str = "
+        enum Color { red, green }
+        type Thing @entity {
+            id: ID!
+            flag: Boolean
+            count: Int
+            big: BigInt
+            decimal: BigDecimal
+            data: Bytes
+            color: Color
+            counts: [Int!]
+            bigs: [BigInt!]
+            datas: [Bytes!]
+            names: [String]
+            colors: [Color!]
+        }";
+
+    /// What NebulaGraph returns when a property was written with `literal`
+    fn stored(literal: &Literal) -> NebulaValue {
+        match literal {
+            Literal::Null => NebulaValue::nVal(Default::default()),
+            Literal::Bool(b) => NebulaValue::bVal(*b),
+            Literal::Int(i) => NebulaValue::iVal(*i),
+            Literal::String(s) => NebulaValue::sVal(s.as_bytes().to_vec()),
+            Literal::Float(_) | Literal::List(_) => unreachable!("not used for properties"),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let subgraph = DeploymentHash::new("subgraph").unwrap();
+        let schema = Schema::parse(GQL, subgraph.clone()).expect("Test schema invalid");
+        let namespace = Namespace::new("sgd0815".to_owned()).unwrap();
+        let site = Arc::new(make_dummy_site(subgraph, namespace, "anet".to_string()));
+        let catalog = Catalog::for_tests(site.clone()).expect("Can not create catalog");
+        let layout = Layout::new(site, &schema, catalog).expect("Failed to construct Layout");
+        let table = layout.table_for_entity(&"Thing".into()).unwrap();
+
+        let huge = BigInt::from_str("-123456789012345678901234567890").unwrap();
+        let values = vec![
+            ("flag", Value::Bool(true), DataType::Bool),
+            ("count", Value::Int(i32::MIN), DataType::Int32),
+            ("big", Value::BigInt(huge.clone()), DataType::String),
+            (
+                "decimal",
+                Value::BigDecimal(BigDecimal::from_str("-0.000000000000000000001234").unwrap()),
+                DataType::String,
+            ),
+            (
+                "data",
+                Value::Bytes("0xdeadbeef".parse().unwrap()),
+                DataType::String,
+            ),
+            ("color", Value::from("green"), DataType::String),
+            ("counts", Value::from(vec![1, -2, 3]), DataType::String),
+            (
+                "bigs",
+                Value::List(vec![Value::BigInt(huge)]),
+                DataType::String,
+            ),
+            (
+                "datas",
+                Value::List(vec![Value::Bytes("0x00ff".parse().unwrap())]),
+                DataType::String,
+            ),
+            (
+                "names",
+                Value::List(vec![
+                    Value::from("a\"b"),
+                    Value::Null,
+                    Value::from("[\"not\", \"a list\"]"),
+                ]),
+                DataType::String,
+            ),
+            ("colors", Value::List(vec![]), DataType::String),
+            ("count", Value::Null, DataType::Int32),
+        ];
+        for (field, value, data_type) in values {
+            let column = table.column_for_field(field).unwrap();
+            assert_eq!(data_type, super::data_type(column), "{}", field);
+            let read = from_nebula(&stored(&to_literal(&value)), column).unwrap();
+            assert_eq!(value, read, "{}", field);
+        }
+
+        assert_eq!(
+            Literal::from("[1,-2,3]"),
+            to_literal(&Value::from(vec![1, -2, 3]))
+        );
+        assert_eq!(
+            Literal::from("[\"0x00ff\"]"),
+            to_literal(&Value::List(vec![Value::Bytes("0x00ff".parse().unwrap())]))
+        );
+
+        let count = table.column_for_field("count").unwrap();
+        assert!(from_nebula(&NebulaValue::iVal(i64::MAX), count).is_err());
+        assert!(from_nebula(&NebulaValue::sVal(b"1".to_vec()), count).is_err());
+        let big = table.column_for_field("big").unwrap();
+        assert!(from_nebula(&NebulaValue::sVal(b"1.5".to_vec()), big).is_err());
+    }
+}
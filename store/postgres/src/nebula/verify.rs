@@ -0,0 +1,541 @@
+//! Compare the NebulaGraph space of a deployment with its entity tables.
+//!
+//! The entities are turned into the statements that would write them with
+//! `GraphLayout::add_entity`, and the vertices and edges that these
+//! statements write are fetched from the space by id and compared property
+//! by property, except for `__block`. Vertices and edges that are missing
+//! or differ are repaired by running the statements for them.
+//!
+//! Vertices and edges that the space has but the entities do not call for
+//! can not be found by id, and only show up in the counts. The space
+//! counts with a `STATS` job, the entities with a `count(*)` per table;
+//! edge types for reference fields are only counted when all entities are
+//! compared.
+//!
+//! The result of the last comparison of each deployment is kept in
+//! `subgraphs.nebula_verification`.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Integer};
+use diesel::{delete, insert_into, sql_query, PgConnection};
+use graph::components::store::{EntityType, NebulaDifference};
+use graph::data::subgraph::status::NebulaConsistency;
+use graph::prelude::{anyhow, BlockNumber, StoreError, ENV_VARS};
+use nebula_rust::graph_client::connection::Value as NebulaValue;
+use nebula_rust::graph_client::ngql::{
+    in_space, EdgeKey, FetchEdges, FetchVertices, Identifier, Literal,
+};
+use nebula_rust::value::{ResultError, ResultSet};
+
+use super::layout::BLOCK_PROPERTY;
+use super::{result_error, GraphLayout, GraphWrites, NebulaSink};
+use crate::block_range::{BLOCK_COLUMN, BLOCK_RANGE_COLUMN};
+use crate::primary::{DeploymentId, Site};
+use crate::relational::Layout;
+
+/// How many vertices or edges one `FETCH` reads
+const FETCH_SIZE: usize = 1_000;
+
+/// How often the state of the `STATS` job is checked
+const STATS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+table! {
+    subgraphs.nebula_verification (deployment) {
+        deployment -> Integer,
+        block_number -> Integer,
+        complete -> Bool,
+        entities -> BigInt,
+        count_mismatches -> Integer,
+        missing -> BigInt,
+        different -> BigInt,
+        repaired -> BigInt,
+    }
+}
+
+/// Record `result` as the last comparison for `site`
+pub(crate) fn record(
+    conn: &PgConnection,
+    site: &Site,
+    result: &NebulaConsistency,
+) -> Result<(), StoreError> {
+    use nebula_verification as v;
+
+    conn.transaction(|| {
+        delete(v::table.filter(v::deployment.eq(site.id))).execute(conn)?;
+        insert_into(v::table)
+            .values((
+                v::deployment.eq(site.id),
+                v::block_number.eq(result.block),
+                v::complete.eq(result.complete),
+                v::entities.eq(result.entities),
+                v::count_mismatches.eq(result.count_mismatches),
+                v::missing.eq(result.missing),
+                v::different.eq(result.different),
+                v::repaired.eq(result.repaired),
+            ))
+            .execute(conn)
+    })?;
+    Ok(())
+}
+
+#[derive(Queryable)]
+struct Verification {
+    deployment: DeploymentId,
+    block_number: BlockNumber,
+    complete: bool,
+    entities: i64,
+    count_mismatches: i32,
+    missing: i64,
+    different: i64,
+    repaired: i64,
+}
+
+/// The last comparison for each of `sites` that has one; for all
+/// deployments if `sites` is empty
+pub(crate) fn last(
+    conn: &PgConnection,
+    sites: &[Arc<Site>],
+) -> Result<HashMap<DeploymentId, NebulaConsistency>, StoreError> {
+    use nebula_verification as v;
+
+    let rows = if sites.is_empty() {
+        v::table.load::<Verification>(conn)?
+    } else {
+        v::table
+            .filter(v::deployment.eq_any(sites.iter().map(|site| site.id)))
+            .load::<Verification>(conn)?
+    };
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let result = NebulaConsistency {
+                block: row.block_number,
+                complete: row.complete,
+                entities: row.entities,
+                count_mismatches: row.count_mismatches,
+                missing: row.missing,
+                different: row.different,
+                repaired: row.repaired,
+            };
+            (row.deployment, result)
+        })
+        .collect())
+}
+
+/// The number of entities of each entity type in `graph` as of `block`
+pub(crate) fn entity_counts(
+    conn: &PgConnection,
+    layout: &Layout,
+    graph: &GraphLayout,
+    block: BlockNumber,
+) -> Result<BTreeMap<EntityType, i64>, StoreError> {
+    #[derive(QueryableByName)]
+    struct Count {
+        #[sql_type = "BigInt"]
+        count: i64,
+    }
+
+    let mut counts = BTreeMap::new();
+    for entity_type in graph.entity_types() {
+        let table = layout.table_for_entity(&entity_type)?;
+        let at_block = if table.immutable {
+            format!("{} <= $1", BLOCK_COLUMN)
+        } else {
+            format!("{} @> $1", BLOCK_RANGE_COLUMN)
+        };
+        let Count { count } = sql_query(format!(
+            "select count(*) as count from {} where {}",
+            table.qualified_name, at_block
+        ))
+        .bind::<Integer, _>(block)
+        .get_result(conn)?;
+        counts.insert(entity_type, count);
+    }
+    Ok(counts)
+}
+
+/// The number of vertices with each tag and of edges of each edge type in
+/// `graph`'s space. Runs a `STATS` job and waits for it; gives up after
+/// `GRAPH_NEBULA_SCHEMA_TIMEOUT`
+pub(crate) async fn graph_counts(
+    sink: &NebulaSink,
+    graph: &GraphLayout,
+) -> Result<HashMap<String, i64>, StoreError> {
+    let space = &graph.space;
+    let job: i64 = sink
+        .query(in_space(space, &"SUBMIT JOB STATS"))
+        .await?
+        .column::<i64>("New Job Id")
+        .map_err(result_error)?
+        .pop()
+        .ok_or_else(|| anyhow!("SUBMIT JOB STATS did not return a job id"))?;
+
+    let timeout = ENV_VARS.store.nebula_schema_timeout;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = sink
+            .query(in_space(space, &format!("SHOW JOB {}", job)))
+            .await?
+            .column::<String>("Status")
+            .map_err(result_error)?;
+        match status.first().map(String::as_str) {
+            Some("FINISHED") => break,
+            Some("FAILED") | Some("STOPPED") => {
+                return Err(StoreError::Unknown(anyhow!(
+                    "the STATS job {} for space {} did not finish",
+                    job,
+                    space
+                )))
+            }
+            _ if Instant::now() >= deadline => {
+                return Err(StoreError::Unknown(anyhow!(
+                    "timed out waiting {}s for the STATS job {} for space {}",
+                    timeout.as_secs(),
+                    job,
+                    space
+                )))
+            }
+            _ => graph::tokio::time::sleep(STATS_POLL_INTERVAL).await,
+        }
+    }
+
+    let stats = sink.query(in_space(space, &"SHOW STATS")).await?;
+    stats
+        .rows()
+        .filter(|row| {
+            row.get::<String>("Type")
+                .map_or(false, |kind| kind == "Tag" || kind == "Edge")
+        })
+        .map(|row| -> Result<(String, i64), ResultError> {
+            Ok((row.get("Name")?, row.get("Count")?))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(result_error)
+}
+
+/// The differences between the expected and the actual number of vertices
+/// and edges. Tags and edge types without an expected count are skipped
+pub(crate) fn count_differences(
+    expected: &BTreeMap<String, i64>,
+    actual: &HashMap<String, i64>,
+) -> Vec<NebulaDifference> {
+    expected
+        .iter()
+        .filter_map(|(name, expected)| {
+            let actual = actual.get(name).copied().unwrap_or(0);
+            (actual != *expected).then(|| NebulaDifference::Count {
+                name: name.clone(),
+                expected: *expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// The literal that would have written `value`, if `value` is of a type
+/// that we write
+fn literal(value: &NebulaValue) -> Option<Literal> {
+    match value {
+        NebulaValue::nVal(_) => Some(Literal::Null),
+        NebulaValue::bVal(b) => Some(Literal::Bool(*b)),
+        NebulaValue::iVal(i) => Some(Literal::Int(*i)),
+        NebulaValue::sVal(bytes) => std::str::from_utf8(bytes).ok().map(Literal::from),
+        _ => None,
+    }
+}
+
+/// The names of the properties in `expected` whose values differ from
+/// `actual`, sorted by name
+fn differing_props(
+    expected: &HashMap<Identifier, Literal>,
+    actual: &HashMap<String, NebulaValue>,
+) -> Vec<String> {
+    let mut props: Vec<_> = expected
+        .iter()
+        .filter(|(name, _)| name.as_str() != BLOCK_PROPERTY)
+        .filter(|(name, value)| {
+            actual.get(name.as_str()).and_then(literal).as_ref() != Some(*value)
+        })
+        .map(|(name, _)| name.as_str().to_owned())
+        .collect();
+    props.sort();
+    props
+}
+
+/// Compare the vertices and edges that `writes` would write with the ones
+/// in `found`, and move the statements for the ones that are missing or
+/// differ to `repairs`
+pub(crate) fn compare(
+    writes: GraphWrites,
+    found: &Found,
+    repairs: &mut GraphWrites,
+) -> Vec<NebulaDifference> {
+    let mut differences = vec![];
+    for query in writes.tags {
+        let key = (query.tag_name.as_str().to_owned(), query.vid.clone());
+        let difference = match found.vertices.get(&key) {
+            None => NebulaDifference::MissingVertex {
+                tag: key.0,
+                id: key.1,
+            },
+            Some(actual) => {
+                let props = differing_props(&query.kv, actual);
+                if props.is_empty() {
+                    continue;
+                }
+                NebulaDifference::Vertex {
+                    tag: key.0,
+                    id: key.1,
+                    props,
+                }
+            }
+        };
+        differences.push(difference);
+        repairs.tags.push(query);
+    }
+    for query in writes.edges {
+        let key = (
+            query.edge_name.as_str().to_owned(),
+            query.from_vertex.clone(),
+            query.to_vertex.clone(),
+            query.rank,
+        );
+        let difference = match found.edges.get(&key) {
+            None => NebulaDifference::MissingEdge {
+                edge: key.0,
+                from: key.1,
+                to: key.2,
+                rank: key.3,
+            },
+            Some(actual) => {
+                let props = differing_props(&query.kv, actual);
+                if props.is_empty() {
+                    continue;
+                }
+                NebulaDifference::Edge {
+                    edge: key.0,
+                    from: key.1,
+                    to: key.2,
+                    rank: key.3,
+                    props,
+                }
+            }
+        };
+        differences.push(difference);
+        repairs.edges.push(query);
+    }
+    differences
+}
+
+/// The properties of the vertices and edges that were fetched, by tag and
+/// vertex id, and by edge type, source, target and rank
+#[derive(Default)]
+pub(crate) struct Found {
+    vertices: HashMap<(String, String), HashMap<String, NebulaValue>>,
+    edges: HashMap<(String, String, String, i64), HashMap<String, NebulaValue>>,
+}
+
+impl Found {
+    /// Fetch the vertices and edges that `writes` would write from the
+    /// space of `graph`
+    pub(crate) async fn fetch(
+        sink: &NebulaSink,
+        graph: &GraphLayout,
+        writes: &GraphWrites,
+    ) -> Result<Found, StoreError> {
+        let mut found = Found::default();
+
+        let mut vids: BTreeMap<_, Vec<String>> = BTreeMap::new();
+        for query in &writes.tags {
+            vids.entry(&query.tag_name)
+                .or_default()
+                .push(query.vid.clone());
+        }
+        for (tag, vids) in vids {
+            for chunk in vids.chunks(FETCH_SIZE) {
+                let fetch = FetchVertices {
+                    tags: vec![tag.clone()],
+                    vids: chunk.to_vec(),
+                    yields: vec![
+                        "id(vertex) AS vid".to_string(),
+                        "properties(vertex) AS props".to_string(),
+                    ],
+                };
+                let result = sink.query(in_space(&graph.space, &fetch)).await?;
+                found.add_vertices(tag.as_str(), &result)?;
+            }
+        }
+
+        let mut keys: BTreeMap<_, Vec<EdgeKey>> = BTreeMap::new();
+        for query in &writes.edges {
+            keys.entry(&query.edge_name).or_default().push(EdgeKey {
+                src: query.from_vertex.clone(),
+                dst: query.to_vertex.clone(),
+                rank: query.rank,
+            });
+        }
+        for (edge, mut keys) in keys {
+            keys.sort();
+            keys.dedup();
+            for chunk in keys.chunks(FETCH_SIZE) {
+                let fetch = FetchEdges {
+                    edge: edge.clone(),
+                    keys: chunk.to_vec(),
+                    yields: vec![
+                        "src(edge) AS src".to_string(),
+                        "dst(edge) AS dst".to_string(),
+                        "rank(edge) AS rank".to_string(),
+                        "properties(edge) AS props".to_string(),
+                    ],
+                };
+                let result = sink.query(in_space(&graph.space, &fetch)).await?;
+                found.add_edges(edge.as_str(), &result)?;
+            }
+        }
+        Ok(found)
+    }
+
+    fn add_vertices(&mut self, tag: &str, result: &ResultSet) -> Result<(), StoreError> {
+        for row in result.rows() {
+            let vid: String = row.get("vid").map_err(result_error)?;
+            let props = row.get("props").map_err(result_error)?;
+            self.vertices.insert((tag.to_owned(), vid), props);
+        }
+        Ok(())
+    }
+
+    fn add_edges(&mut self, edge: &str, result: &ResultSet) -> Result<(), StoreError> {
+        for row in result.rows() {
+            let key = (
+                edge.to_owned(),
+                row.get("src").map_err(result_error)?,
+                row.get("dst").map_err(result_error)?,
+                row.get("rank").map_err(result_error)?,
+            );
+            let props = row.get("props").map_err(result_error)?;
+            self.edges.insert(key, props);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nebula_rust::graph_client::nebula_schema::{InsertEdgeQueryWithRank, InsertTagQuery};
+
+    use super::*;
+
+    fn ident(name: &str) -> Identifier {
+        Identifier::new(name).unwrap()
+    }
+
+    fn s(s: &str) -> NebulaValue {
+        NebulaValue::sVal(s.as_bytes().to_vec())
+    }
+
+    fn kv(name: &str, balance: i32) -> HashMap<Identifier, Literal> {
+        HashMap::from([
+            (ident("id"), Literal::from(name)),
+            (ident("balance"), Literal::from(balance)),
+            (ident(BLOCK_PROPERTY), Literal::from(7)),
+        ])
+    }
+
+    fn props(name: &str, balance: i64) -> HashMap<String, NebulaValue> {
+        HashMap::from([
+            ("id".to_string(), s(name)),
+            ("balance".to_string(), NebulaValue::iVal(balance)),
+            (BLOCK_PROPERTY.to_string(), NebulaValue::iVal(3)),
+        ])
+    }
+
+    #[test]
+    fn differences() {
+        let space = ident("sgd1");
+        let mut writes = GraphWrites::new(7);
+        for (id, balance) in [("a", 1), ("b", 2), ("c", 3)] {
+            writes.tags.push(InsertTagQuery::new(
+                space.clone(),
+                ident("Account"),
+                kv(id, balance),
+                id.to_string(),
+            ));
+        }
+        for to in ["b", "c"] {
+            writes.edges.push(InsertEdgeQueryWithRank::new(
+                space.clone(),
+                ident("Account_friends"),
+                HashMap::from([(ident(BLOCK_PROPERTY), Literal::from(7))]),
+                "a".to_string(),
+                to.to_string(),
+                0,
+            ));
+        }
+
+        // `a` is as it should be even though it was written at another
+        // block, `b` has the wrong balance, `c` and the edge to it are
+        // missing
+        let mut found = Found::default();
+        for (id, balance) in [("a", 1), ("b", 5)] {
+            found
+                .vertices
+                .insert(("Account".to_string(), id.to_string()), props(id, balance));
+        }
+        found.edges.insert(
+            (
+                "Account_friends".to_string(),
+                "a".to_string(),
+                "b".to_string(),
+                0,
+            ),
+            HashMap::from([(BLOCK_PROPERTY.to_string(), NebulaValue::iVal(2))]),
+        );
+
+        let mut repairs = GraphWrites::new(7);
+        let differences = compare(writes, &found, &mut repairs);
+        assert_eq!(
+            vec![
+                NebulaDifference::Vertex {
+                    tag: "Account".to_string(),
+                    id: "b".to_string(),
+                    props: vec!["balance".to_string()]
+                },
+                NebulaDifference::MissingVertex {
+                    tag: "Account".to_string(),
+                    id: "c".to_string()
+                },
+                NebulaDifference::MissingEdge {
+                    edge: "Account_friends".to_string(),
+                    from: "a".to_string(),
+                    to: "c".to_string(),
+                    rank: 0
+                }
+            ],
+            differences
+        );
+        let repaired: Vec<_> = repairs
+            .tags
+            .iter()
+            .map(|query| query.vid.as_str())
+            .collect();
+        assert_eq!(vec!["b", "c"], repaired);
+        assert_eq!(1, repairs.edges.len());
+    }
+
+    #[test]
+    fn counts() {
+        let expected = BTreeMap::from([("Account".to_string(), 3), ("Transfer".to_string(), 0)]);
+        let actual = HashMap::from([("Account".to_string(), 4), ("Other".to_string(), 1)]);
+        assert_eq!(
+            vec![NebulaDifference::Count {
+                name: "Account".to_string(),
+                expected: 3,
+                actual: 4
+            }],
+            count_differences(&expected, &actual)
+        );
+    }
+}
@@ -1,11 +1,15 @@
 use std::collections::BTreeMap;
 
 use crate::deployment_store::{DeploymentStore, ReplicaId};
-use graph::components::store::QueryStore as QueryStoreTrait;
+use graph::block_on;
+use graph::components::store::{
+    NebulaRows, NeighborsQuery, PathsQuery, QueryStore as QueryStoreTrait,
+};
 use graph::data::query::Trace;
 use graph::data::value::Word;
 use graph::prelude::*;
 
+use crate::nebula::GraphLayout;
 use crate::primary::Site;
 
 pub(crate) struct QueryStore {
@@ -32,6 +36,17 @@ impl QueryStore {
             api_version,
         }
     }
+
+    fn graph_layout(&self) -> Result<Arc<GraphLayout>, QueryExecutionError> {
+        if let Some(graph) = self.store.cached_graph_layout(&self.site) {
+            return Ok(graph);
+        }
+        let conn = self
+            .store
+            .get_replica_conn(self.replica_id)
+            .map_err(|e| QueryExecutionError::StoreError(e.into()))?;
+        Ok(self.store.graph_layout(&conn, self.site.clone())?)
+    }
 }
 
 #[async_trait]
@@ -48,6 +63,34 @@ impl QueryStoreTrait for QueryStore {
         self.store.execute_query(&conn, self.site.clone(), query)
     }
 
+    // GraphQL queries are executed synchronously on a blocking thread of
+    // the tokio runtime, and we can therefore block on the NebulaGraph
+    // reads here; the reads need the runtime since the tasks that serve
+    // them run on it
+    fn neighbors(
+        &self,
+        queries: &[NeighborsQuery],
+    ) -> Result<Vec<Vec<String>>, QueryExecutionError> {
+        let graph = self.graph_layout()?;
+        Ok(block_on(self.store.nebula_neighbors(&graph, queries))?)
+    }
+
+    fn paths(&self, queries: &[PathsQuery]) -> Result<Vec<Vec<Vec<String>>>, QueryExecutionError> {
+        let graph = self.graph_layout()?;
+        Ok(block_on(self.store.nebula_paths(&graph, queries))?)
+    }
+
+    async fn nebula_query(
+        &self,
+        statement: &str,
+        max_rows: usize,
+    ) -> Result<NebulaRows, QueryExecutionError> {
+        Ok(self
+            .store
+            .nebula_query(self.site.clone(), statement, max_rows)
+            .await?)
+    }
+
     /// Return true if the deployment with the given id is fully synced,
     /// and return false otherwise. Errors from the store are passed back up
     async fn is_deployment_synced(&self) -> Result<bool, Error> {
@@ -39,8 +39,9 @@ use crate::relational_queries::{FindChangesQuery, FindPossibleDeletionsQuery};
 use crate::{
     primary::{Namespace, Site},
     relational_queries::{
-        ClampRangeQuery, ConflictingEntityQuery, EntityData, EntityDeletion, FilterCollection,
-        FilterQuery, FindManyQuery, FindQuery, InsertQuery, RevertClampQuery, RevertRemoveQuery,
+        ChangedSinceQuery, ClampRangeQuery, ConflictingEntityQuery, EntityData, EntityDeletion,
+        FilterCollection, FilterQuery, FindManyQuery, FindQuery, InsertQuery, RevertClampQuery,
+        RevertRemoveQuery,
     },
 };
 use graph::components::store::{EntityKey, EntityType};
@@ -53,7 +54,11 @@ use graph::prelude::{
     EntityFilter, EntityOperation, EntityOrder, EntityRange, Logger, QueryExecutionError,
     StoreError, StoreEvent, ValueType, BLOCK_NUMBER_MAX,
 };
-use nebula_rust::graph_client::{pool_config, connection_pool, session, nebula_schema::{ColType, Tag, DataType}};
+use nebula_rust::graph_client::{
+    connection_pool,
+    nebula_schema::{ColType, DataType, Tag},
+    pool_config, session,
+};
 
 use crate::block_range::{BLOCK_COLUMN, BLOCK_RANGE_COLUMN};
 pub use crate::catalog::Catalog;
@@ -527,6 +532,31 @@ impl Layout {
         Ok(entities_for_type)
     }
 
+    /// The ids of the entities of the types in `entity_types`, grouped by
+    /// entity type, that were inserted, updated or deleted at `block` or
+    /// later. These are the entities of those types that
+    /// `revert_block(conn, block)` changes
+    pub fn changed_since(
+        &self,
+        conn: &PgConnection,
+        entity_types: &[EntityType],
+        block: BlockNumber,
+    ) -> Result<BTreeMap<EntityType, Vec<String>>, StoreError> {
+        let mut changed = BTreeMap::new();
+        for entity_type in entity_types {
+            let table = self.table_for_entity(entity_type)?;
+            let ids: Vec<_> = ChangedSinceQuery::new(table, block)
+                .get_results(conn)?
+                .into_iter()
+                .map(|data| data.id)
+                .collect();
+            if !ids.is_empty() {
+                changed.insert(table.object.clone(), ids);
+            }
+        }
+        Ok(changed)
+    }
+
     pub fn find_changes(
         &self,
         conn: &PgConnection,
@@ -955,15 +985,18 @@ impl From<IdType> for ColumnType {
 }
 
 impl ColumnType {
-    pub fn to_nebula_type(&self) -> DataType{
-        match self{
+    /// The type of the NebulaGraph property for a scalar column of this
+    /// type; see `nebula::value` for how values are encoded
+    pub fn to_nebula_type(&self) -> DataType {
+        match self {
             ColumnType::Boolean => DataType::Bool,
-            ColumnType::BigDecimal => DataType::Double,
-            ColumnType::BigInt => DataType::Int32,
+            ColumnType::BigDecimal => DataType::String,
+            ColumnType::BigInt => DataType::String,
             ColumnType::Bytes => DataType::String,
-            ColumnType::Int => DataType::Int8,
+            ColumnType::Int => DataType::Int32,
             ColumnType::String => DataType::String,
-            _ => DataType::Bool,
+            ColumnType::Enum(_) => DataType::String,
+            ColumnType::TSVector(_) => DataType::String,
         }
     }
     fn from_field_type(
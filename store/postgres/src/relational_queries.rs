@@ -3389,9 +3389,80 @@ impl<'a> LoadQuery<PgConnection, ReturnedEntityData> for RevertClampQuery<'a> {
 
 impl<'a, Conn> RunQueryDsl<Conn> for RevertClampQuery<'a> {}
 
+/// A query that finds the ids of all entities that were inserted, updated
+/// or deleted at `block` or later, i.e., the entities that reverting to
+/// `block - 1` changes
+#[derive(Debug, Clone)]
+pub struct ChangedSinceQuery<'a> {
+    table: &'a Table,
+    br_column: BlockRangeColumn<'a>,
+    block: BlockNumber,
+}
+
+impl<'a> ChangedSinceQuery<'a> {
+    pub fn new(table: &'a Table, block: BlockNumber) -> Self {
+        let br_column = BlockRangeColumn::new(table, "", block);
+        Self {
+            table,
+            br_column,
+            block,
+        }
+    }
+}
+
+impl<'a> QueryFragment<Pg> for ChangedSinceQuery<'a> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.unsafe_to_cache_prepared();
+
+        // Construct a query
+        //   select distinct id::text from table
+        //    where lower(block_range) >= $block
+        //       or (coalesce(upper(block_range), INTMAX) >= $block
+        //           and coalesce(upper(block_range), INTMAX) < INTMAX)
+        // For immutable tables, entities are never updated or deleted,
+        // and only the first condition applies.
+        //
+        // Both conditions are stated in terms of the expressions of the
+        // BRIN index on the table so that Postgres can use it and does not
+        // have to scan the whole table
+        out.push_sql("select distinct ");
+        out.push_sql(PRIMARY_KEY_COLUMN);
+        out.push_sql("::text\n  from ");
+        out.push_sql(self.table.qualified_name.as_str());
+        out.push_sql("\n where ");
+        self.br_column.changed_since(&mut out)?;
+        if !self.table.immutable {
+            out.push_sql("\n    or (coalesce(upper(");
+            out.push_identifier(BLOCK_RANGE_COLUMN)?;
+            out.push_sql("), 2147483647) >= ");
+            out.push_bind_param::<Integer, _>(&self.block)?;
+            out.push_sql(" and coalesce(upper(");
+            out.push_identifier(BLOCK_RANGE_COLUMN)?;
+            out.push_sql("), 2147483647) < 2147483647)");
+        }
+        Ok(())
+    }
+}
+
+impl<'a> QueryId for ChangedSinceQuery<'a> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<'a> LoadQuery<PgConnection, ReturnedEntityData> for ChangedSinceQuery<'a> {
+    fn internal_load(self, conn: &PgConnection) -> QueryResult<Vec<ReturnedEntityData>> {
+        conn.query_by_name(&self)
+            .map(|data| ReturnedEntityData::bytes_as_str(self.table, data))
+    }
+}
+
+impl<'a, Conn> RunQueryDsl<Conn> for ChangedSinceQuery<'a> {}
+
 #[test]
 fn block_number_max_is_i32_max() {
-    // The code in RevertClampQuery::walk_ast embeds i32::MAX
+    // The code in RevertClampQuery::walk_ast and ChangedSinceQuery::walk_ast
+    // embeds i32::MAX
     // aka BLOCK_NUMBER_MAX in strings for efficiency. This assertion
     // makes sure that BLOCK_NUMBER_MAX still is what we think it is
     assert_eq!(2147483647, graph::prelude::BLOCK_NUMBER_MAX);
@@ -9,15 +9,18 @@ use std::{
     sync::{Arc, Mutex},
 };
 use std::{fmt, io::Write};
-use std::{iter::FromIterator, time::Duration};
+use std::{
+    iter::FromIterator,
+    time::{Duration, Instant},
+};
 
 use graph::{
     cheap_clone::CheapClone,
     components::{
         server::index_node::VersionInfo,
         store::{
-            self, BlockStore, DeploymentLocator, DeploymentSchemaVersion,
-            EnsLookup as EnsLookupTrait, PruneReporter, SubgraphFork,
+            self, BackfillReporter, BlockStore, DeploymentLocator, DeploymentSchemaVersion,
+            EnsLookup as EnsLookupTrait, PruneReporter, SubgraphFork, VerifyReporter,
         },
     },
     constraint_violation,
@@ -25,10 +28,10 @@ use graph::{
     data::subgraph::{schema::DeploymentCreate, status},
     prelude::StoreEvent,
     prelude::{
-        anyhow, futures03::future::join_all, lazy_static, o, web3::types::Address, ApiSchema,
-        ApiVersion, BlockHash, BlockNumber, BlockPtr, ChainStore, DeploymentHash, EntityOperation,
-        Logger, MetricsRegistry, NodeId, PartialBlockPtr, Schema, StoreError, SubgraphName,
-        SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
+        anyhow, error, futures03::future::join_all, lazy_static, o, web3::types::Address,
+        ApiSchema, ApiVersion, BlockHash, BlockNumber, BlockPtr, ChainStore, DeploymentHash,
+        EntityOperation, Logger, MetricsRegistry, NodeId, PartialBlockPtr, Schema, StoreError,
+        SubgraphName, SubgraphStore as SubgraphStoreTrait, SubgraphVersionSwitchingMode,
     },
     url::Url,
     util::timed_cache::TimedCache,
@@ -43,7 +46,7 @@ use crate::{
     primary::{DeploymentId, Mirror as PrimaryMirror, Site},
     relational::Layout,
     writable::WritableStore,
-    NotificationSender,
+    NebulaConfig, NotificationSender,
 };
 use crate::{
     deployment_store::{DeploymentStore, ReplicaId},
@@ -58,7 +61,6 @@ pub struct Shard(String);
 lazy_static! {
     /// The name of the primary shard that contains all instance-wide data
     pub static ref PRIMARY_SHARD: Shard = Shard("primary".to_string());
-    pub static ref PRIMARY_SHARD_NEBULA: Shard = Shard("primary_nebula".to_string());
 }
 
 /// How long to cache information about a deployment site
@@ -222,12 +224,12 @@ impl SubgraphStore {
         sender: Arc<NotificationSender>,
         fork_base: Option<Url>,
         registry: Arc<dyn MetricsRegistry>,
-        nebula_url: String,
+        nebula_conf: NebulaConfig,
         // pool_nebula: connection_test::ConnectionPool,
     ) -> Self {
         Self {
             inner: Arc::new(SubgraphStoreInner::new(
-                logger, stores, placer, sender, registry, nebula_url,
+                logger, stores, placer, sender, registry, nebula_conf,
             )),
             fork_base,
         }
@@ -303,7 +305,7 @@ impl SubgraphStoreInner {
         placer: Arc<dyn DeploymentPlacer + Send + Sync + 'static>,
         sender: Arc<NotificationSender>,
         registry: Arc<dyn MetricsRegistry>,
-        nebula_url: String,
+        nebula_conf: NebulaConfig,
     ) -> Self {
         let mirror = {
             let pools = HashMap::from_iter(
@@ -324,7 +326,8 @@ impl SubgraphStoreInner {
                         main_pool,
                         read_only_pools,
                         weights,
-                        nebula_url.clone(),
+                        nebula_conf.clone(),
+                        registry.cheap_clone(),
                     )),
                 )
             },
@@ -580,7 +583,7 @@ impl SubgraphStoreInner {
     ) -> Result<DeploymentLocator, StoreError> {
         let src = self.find_site(src.id.into())?;
         let src_store = self.for_site(src.as_ref())?;
-        let src_info = src_store.subgraph_info(src.as_ref())?;
+        let src_info = src_store.subgraph_info(&src)?;
         let src_loc = DeploymentLocator::from(src.as_ref());
 
         let dst = Arc::new(self.primary_conn()?.copy_site(&src, shard.clone())?);
@@ -878,7 +881,7 @@ impl SubgraphStoreInner {
                 .ok_or_else(|| constraint_violation!("no chain info for {}", deployment_id))?;
             let latest_ethereum_block_number =
                 chain.latest_block.as_ref().map(|block| block.number());
-            let subgraph_info = store.subgraph_info(site.as_ref())?;
+            let subgraph_info = store.subgraph_info(&site)?;
             let network = site.network.clone();
 
             let info = VersionInfo {
@@ -924,6 +927,49 @@ impl SubgraphStoreInner {
         join_all(self.stores.values().map(|store| store.vacuum())).await
     }
 
+    /// Create the NebulaGraph spaces of new deployments and write the
+    /// mutations waiting in the NebulaGraph outbox of each shard. Backfills
+    /// that no process runs are started in the background since they can
+    /// take a long time; the outbox holds back the mutations of their
+    /// deployments until they have finished
+    pub(crate) async fn replay_nebula_outbox(
+        &self,
+        logger: &Logger,
+        deadline: Instant,
+    ) -> Vec<Result<usize, StoreError>> {
+        join_all(self.stores.values().map(|store| async move {
+            let ids = store.new_nebula_spaces().await?;
+            let new_spaces = self.mirror.find_sites_by_id(&ids)?;
+            let written = store
+                .replay_nebula_outbox(logger, new_spaces, deadline)
+                .await?;
+
+            let ids = store.unclaimed_nebula_backfills().await?;
+            for site in self.mirror.find_sites_by_id(&ids)? {
+                let store = store.clone();
+                let logger = logger.new(o!("sgd" => site.namespace.to_string()));
+                graph::spawn(async move {
+                    if let Err(e) = store.resume_nebula_backfill(&logger, Arc::new(site)).await {
+                        error!(logger, "Loading entities into NebulaGraph failed, will retry";
+                               "error" => e.to_string());
+                    }
+                });
+            }
+            Ok(written)
+        }))
+        .await
+    }
+
+    /// Report how far the NebulaGraph mirror of the deployment `id` lags
+    /// behind the deployment
+    pub fn nebula_sync_status(
+        &self,
+        id: &DeploymentHash,
+    ) -> Result<status::NebulaSync, StoreError> {
+        let (store, site) = self.store(id)?;
+        store.nebula_sync_status(&site)
+    }
+
     pub fn rewind(&self, id: DeploymentHash, block_ptr_to: BlockPtr) -> Result<(), StoreError> {
         let (store, site) = self.store(&id)?;
         let event = store.rewind(site, block_ptr_to)?;
@@ -1099,6 +1145,36 @@ impl SubgraphStoreInner {
             .prune(reporter, site, earliest_block, reorg_threshold, prune_ratio)
             .await
     }
+
+    /// Load the entities of `deployment` as of `block` into its
+    /// NebulaGraph space, or resume a backfill that was interrupted
+    pub async fn nebula_backfill(
+        &self,
+        reporter: Box<dyn BackfillReporter>,
+        deployment: &DeploymentLocator,
+        block: Option<BlockNumber>,
+    ) -> Result<Box<dyn BackfillReporter>, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+
+        store.nebula_backfill(reporter, site, block).await
+    }
+
+    /// Compare the NebulaGraph space of `deployment` with its entities,
+    /// or only `sample` entities of each type, and write what is missing
+    /// or differs again if `repair` is set
+    pub async fn nebula_verify(
+        &self,
+        reporter: Box<dyn VerifyReporter>,
+        deployment: &DeploymentLocator,
+        sample: Option<usize>,
+        repair: bool,
+    ) -> Result<Box<dyn VerifyReporter>, StoreError> {
+        let site = self.find_site(deployment.id.into())?;
+        let store = self.for_site(&site)?;
+
+        store.nebula_verify(reporter, site, sample, repair).await
+    }
 }
 
 struct EnsLookup {
@@ -0,0 +1,141 @@
+//! Test how the NebulaGraph outbox and backfills of a deployment interact,
+//! and that the entities that are mirrored into NebulaGraph are also
+//! written to Postgres
+use graph::components::store::{EntityKey, EntityType, ReadStore, StatusStore};
+use graph::data::subgraph::status;
+use graph::entity;
+use graph::prelude::{
+    BlockNumber, CheapClone, DeploymentHash, EntityOperation, SubgraphStore, Value,
+};
+use graph_store_postgres::layout_for_tests::nebula::{backfill, outbox};
+use test_store::*;
+
+const GQL: &str = "type Thing @entity { id: ID!, name: String! }";
+
+#[test]
+fn outbox_waits_for_backfill() {
+    run_test_with_conn(|conn| {
+        remove_subgraphs();
+        let id = DeploymentHash::new("nebulaBackfill").unwrap();
+        let deployment = STORE_RUNTIME
+            .handle()
+            .block_on(create_test_subgraph(&id, GQL));
+        let site = primary_connection()
+            .locate_site(deployment)
+            .unwrap()
+            .expect("the deployment exists");
+        let thing = EntityType::from("Thing");
+
+        let pending = || -> Vec<BlockNumber> {
+            outbox::pending(conn, site.id, 100)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.block_number)
+                .collect()
+        };
+        let record =
+            |block| outbox::insert(conn, &site, block, vec![format!("block {}", block)]).unwrap();
+
+        // The job creates the space of a new deployment before it replays
+        // its mutations
+        record(1);
+        assert!(outbox::deployments(conn).unwrap().is_empty());
+        assert!(pending().is_empty());
+        let new_spaces = outbox::new_spaces(conn).unwrap();
+        assert_eq!(vec![site.id], new_spaces);
+        outbox::space_created(conn, &site).unwrap();
+        record(2);
+        record(3);
+        assert_eq!(vec![site.id], outbox::deployments(conn).unwrap());
+        assert_eq!(vec![1, 2, 3], pending());
+
+        // A backfill as of block 2 holds back the mutations for block 3
+        // and for blocks that are written while it runs
+        outbox::drop_until(conn, &site, 2).unwrap();
+        backfill::start(conn, &site, 2, &[thing.clone()]).unwrap();
+        record(4);
+        assert!(outbox::deployments(conn).unwrap().is_empty());
+        assert!(pending().is_empty());
+
+        backfill::record_batch(conn, &site, &thing, Some("thing9"), 10, false).unwrap();
+        assert!(pending().is_empty());
+
+        // Once all entity types have been loaded, the mutations are
+        // replayed on top of the backfill
+        backfill::record_batch(conn, &site, &thing, Some("thing9"), 10, true).unwrap();
+        assert_eq!(vec![3, 4], pending());
+        backfill::finish(conn, &site).unwrap();
+        assert_eq!(vec![site.id], outbox::deployments(conn).unwrap());
+        assert_eq!(vec![3, 4], pending());
+
+        remove_subgraphs();
+    })
+}
+
+#[test]
+fn entity_writes_reach_postgres() {
+    fn set(id: &str, name: &str) -> EntityOperation {
+        EntityOperation::Set {
+            key: EntityKey::data("Thing".to_owned(), id.to_owned()),
+            data: entity! { id: id, name: name },
+        }
+    }
+
+    run_test_sequentially(|store| async move {
+        remove_subgraphs();
+        let id = DeploymentHash::new("nebulaWrites").unwrap();
+        let deployment = create_test_subgraph(&id, GQL).await;
+        let subgraph_store = store.subgraph_store();
+
+        let entity_count = || {
+            let infos = store
+                .status(status::Filter::Deployments(vec![id.to_string()]))
+                .unwrap();
+            infos[0].entity_count
+        };
+        let writable = subgraph_store
+            .cheap_clone()
+            .writable(LOGGER.clone(), deployment.id)
+            .await
+            .unwrap();
+        let name = |id: &str| {
+            writable
+                .get(&EntityKey::data("Thing".to_owned(), id.to_owned()))
+                .unwrap()
+                .map(|entity| entity.get("name").cloned().unwrap())
+        };
+
+        // Inserts are counted and written to the entity tables
+        transact_and_wait(
+            &subgraph_store,
+            &deployment,
+            BLOCKS[1].clone(),
+            vec![set("thing1", "one"), set("thing2", "two")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(2, entity_count());
+        assert_eq!(Some(Value::from("one")), name("thing1"));
+        assert_eq!(Some(Value::from("two")), name("thing2"));
+
+        // An overwrite does not change the count, and a removal lowers it
+        transact_and_wait(
+            &subgraph_store,
+            &deployment,
+            BLOCKS[2].clone(),
+            vec![
+                set("thing1", "uno"),
+                EntityOperation::Remove {
+                    key: EntityKey::data("Thing".to_owned(), "thing2".to_owned()),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(1, entity_count());
+        assert_eq!(Some(Value::from("uno")), name("thing1"));
+        assert_eq!(None, name("thing2"));
+
+        remove_subgraphs();
+    })
+}
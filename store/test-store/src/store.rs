@@ -164,6 +164,7 @@ pub async fn create_subgraph(
         data_sources: vec![],
         graft: None,
         templates: vec![],
+        graph: None,
         chain: PhantomData,
     };
 
@@ -511,12 +512,13 @@ pub async fn deployment_state(store: &Store, subgraph_id: &DeploymentHash) -> De
 }
 
 pub fn store_is_sharded() -> bool {
-    CONFIG.stores.len() > 1
+    CONFIG.stores.shards.len() > 1
 }
 
 pub fn all_shards() -> Vec<Shard> {
     CONFIG
         .stores
+        .shards
         .keys()
         .map(|shard| Shard::new(shard.clone()))
         .collect::<Result<Vec<_>, _>>()